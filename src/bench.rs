@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use bevy::app::{Events, ManualEventReader};
+use bevy::ecs::schedule::Stage;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::{
+    shift_segments, steer, Args, Food, FrameDelta, GameOverEvent, GameRng, GridDimensions, Materials, MovementTimer, Obstacle,
+    Position, RivalTiles, Score, SnekHead, SnekMovement, SnekPlugin, SnekSegment, SnekSegments, WIDTH,
+};
+
+// every benchmark run sees the same food, so runs can be compared
+const BENCH_SEED: u64 = 0x5e4e;
+const SHIFT_BENCH_TICKS: u32 = 2000;
+
+// runs the game without a window for a fixed number of movement ticks and prints how long they took
+pub fn run(args: &Args, ticks: u32) {
+    let mut builder = App::build();
+    builder
+        .add_plugins(MinimalPlugins)
+        .insert_resource(GameRng(StdRng::seed_from_u64(BENCH_SEED)))
+        .init_resource::<Materials>()
+        .add_plugin(SnekPlugin)
+        .add_system(autopilot.system().before(SnekMovement::Movement));
+    args.insert_settings(&mut builder);
+    let mut app = builder.app;
+
+    let mut game_overs = ManualEventReader::<GameOverEvent>::default();
+    let mut deaths = 0;
+    let mut longest = 0;
+    let mut tick_times = Vec::with_capacity(ticks as usize);
+    for _ in 0..ticks {
+        // pretend exactly one movement step passed, so every update is one tick
+        let step = app.world.get_resource::<MovementTimer>().unwrap().0.duration();
+        app.world.get_resource_mut::<FrameDelta>().unwrap().0 = step;
+
+        let start = Instant::now();
+        app.update();
+        tick_times.push(start.elapsed());
+
+        deaths += game_overs.iter(app.world.get_resource::<Events<GameOverEvent>>().unwrap()).count();
+        longest = longest.max(app.world.get_resource::<SnekSegments>().unwrap().0.len());
+    }
+
+    let total: Duration = tick_times.iter().sum();
+    println!("bench: {} ticks in {:?}", ticks, total);
+    if let (Some(min), Some(max)) = (tick_times.iter().min(), tick_times.iter().max()) {
+        println!("  per tick: mean {:?}, min {:?}, max {:?}", total / ticks, min, max);
+    }
+    println!(
+        "  longest snek {}, final score {}, deaths {}",
+        longest,
+        app.world.get_resource::<Score>().unwrap().0,
+        deaths,
+    );
+}
+
+// steers towards the closest fruit without running into walls, obstacles, rivals or the body, if it can help it
+fn autopilot(
+    mut heads: Query<(&mut SnekHead, &Position)>,
+    segments: Query<&Position, With<SnekSegment>>,
+    food: Query<&Position, With<Food>>,
+    obstacles: Query<&Obstacle>,
+    rival_tiles: Res<RivalTiles>,
+    grid: Res<GridDimensions>,
+) {
+    if let Some((mut head, head_pos)) = heads.iter_mut().next() {
+        let mut blocked: HashSet<Position> = segments.iter().copied().collect();
+        blocked.extend(rival_tiles.0.iter());
+        // the obstacle steps before the snek does, so avoid where it is and where it is going
+        for obstacle in obstacles.iter() {
+            blocked.insert(obstacle.position());
+            blocked.insert(obstacle.path[(obstacle.index + 1) % obstacle.path.len()]);
+        }
+        let food = food.iter().copied().collect::<Vec<Position>>();
+
+        if let Some(direction) = steer(*head_pos, head.direction, &blocked, &food, *grid) {
+            head.next_direction = direction;
+        }
+    }
+}
+
+// times shift_segments against the two pass shift snek_movement used before, on a snek of `length` segments
+pub fn run_shift(length: usize) {
+    let two_pass = time_shift(length, SystemStage::single(two_pass_shift.system()));
+    let single_pass = time_shift(length, SystemStage::single(single_pass_shift.system()));
+    println!("bench-shift: {} ticks with {} segments", SHIFT_BENCH_TICKS, length);
+    println!("  two pass: {:?}, {:?} per tick", two_pass, two_pass / SHIFT_BENCH_TICKS);
+    println!("  single pass: {:?}, {:?} per tick", single_pass, single_pass / SHIFT_BENCH_TICKS);
+}
+
+fn time_shift(length: usize, mut stage: SystemStage) -> Duration {
+    let mut world = shift_world(length);
+    let start = Instant::now();
+    for _ in 0..SHIFT_BENCH_TICKS {
+        stage.run(&mut world);
+    }
+    start.elapsed()
+}
+
+// just the segments, winding back and forth over the board, head first
+fn shift_world(length: usize) -> World {
+    let mut world = World::new();
+    let segments = (0..length)
+        .map(|i| {
+            let (row, column) = ((i / WIDTH as usize) as i32, (i % WIDTH as usize) as i32);
+            let x = if row % 2 == 0 { column } else { WIDTH as i32 - 1 - column };
+            world.spawn().insert(Position { x, y: row }).id()
+        })
+        .collect();
+    world.insert_resource(SnekSegments(segments));
+    world
+}
+
+// only the shifting is measured, so the head just keeps going without caring about the board
+fn next_head(head: Position) -> Position {
+    Position { x: head.x, y: head.y - 1 }
+}
+
+// how snek_movement moved the body before: read every position, then write them all back one segment further
+fn two_pass_shift(segments: Res<SnekSegments>, mut positions: Query<&mut Position>) {
+    let segment_positions = segments.0.iter()
+        .map(|e| *positions.get_mut(*e).unwrap())
+        .collect::<Vec<Position>>();
+    *positions.get_mut(segments.0[0]).unwrap() = next_head(segment_positions[0]);
+    segment_positions.iter()
+        .zip(segments.0.iter().skip(1))
+        .for_each(|(segpos, segment)| {
+            *positions.get_mut(*segment).unwrap() = *segpos;
+        });
+}
+
+fn single_pass_shift(
+    segments: Res<SnekSegments>,
+    mut positions: Query<&mut Position>,
+    mut old_positions: Local<Vec<Position>>,
+) {
+    let head = *positions.get_mut(segments.0[0]).unwrap();
+    shift_segments(&segments.0, next_head(head), &mut positions, &mut old_positions);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions_after(mut stage: SystemStage, ticks: u32) -> Vec<Position> {
+        let mut world = shift_world(500);
+        for _ in 0..ticks {
+            stage.run(&mut world);
+        }
+        let segments = world.get_resource::<SnekSegments>().unwrap().0.clone();
+        segments.iter().map(|e| *world.get::<Position>(*e).unwrap()).collect()
+    }
+
+    #[test]
+    fn single_pass_shift_matches_two_passes() {
+        let two_pass = positions_after(SystemStage::single(two_pass_shift.system()), 40);
+        let single_pass = positions_after(SystemStage::single(single_pass_shift.system()), 40);
+        assert_eq!(single_pass, two_pass);
+        // it did move
+        assert_eq!(single_pass[0], Position { x: 0, y: -40 });
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{Position, Score, SnekSegments};
+
+// Resource holding the sending end of the broadcast channel.
+// The socket work happens on two background threads so a slow client never stalls the game.
+pub struct Broadcast(Sender<String>);
+
+#[derive(Serialize)]
+struct Snapshot {
+    head: Position,
+    segments: Vec<Position>,
+    score: u32,
+}
+
+pub fn start(port: u16) -> io::Result<Broadcast> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let clients: Arc<Mutex<Vec<TcpStream>>> = Arc::default();
+
+    let accepting = clients.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            accepting.lock().unwrap().push(stream);
+        }
+    });
+
+    let (sender, receiver) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for line in receiver {
+            // a failed write means the client went away, so we simply forget about it
+            clients.lock().unwrap()
+                .retain(|mut client| client.write_all(line.as_bytes()).is_ok());
+        }
+    });
+
+    Ok(Broadcast(sender))
+}
+
+// sends one JSON line per movement tick. does nothing unless --broadcast was given.
+pub fn broadcast_state(
+    broadcast: Option<Res<Broadcast>>,
+    segments: Res<SnekSegments>,
+    score: Res<Score>,
+    positions: Query<&Position>,
+) {
+    if let Some(broadcast) = broadcast {
+        let segment_positions = segments.0.iter()
+            .filter_map(|e| positions.get(*e).ok().copied())
+            .collect::<Vec<Position>>();
+
+        if let Some(head) = segment_positions.first().copied() {
+            let snapshot = Snapshot {
+                head,
+                segments: segment_positions,
+                score: score.0,
+            };
+            let mut line = serde_json::to_string(&snapshot).unwrap();
+            line.push('\n');
+            // the writer thread only stops if the whole app is shutting down
+            let _ = broadcast.0.send(line);
+        }
+    }
+}
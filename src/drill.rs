@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::food_script::{FoodScript, ScriptEnd};
+use crate::{DrillText, Food, FrameDelta, GameOverEvent, GridDimensions, Lives, Position};
+
+// one drill: fruit placed in an order that only works out with a certain maneuver
+struct Pattern {
+    name: &'static str,
+    fruit: Vec<Position>,
+}
+
+// how the last finished run of a drill went
+#[derive(PartialEq, Copy, Clone, Debug)]
+struct Run {
+    seconds: f32,
+    deaths: u32,
+}
+
+// --drill: the fruit comes from a fixed sequence of patterns instead of at random, through a food
+// script per pattern. A drill is done once its last fruit was eaten, then the next one starts.
+// Dying starts the drill over from its first fruit, and counts against it.
+pub struct Drill {
+    patterns: Vec<Pattern>,
+    current: usize,
+    // game time and deaths since the current drill started
+    time: Duration,
+    deaths: u32,
+    // by pattern
+    last_runs: Vec<Option<Run>>,
+}
+
+impl Drill {
+    pub fn new(grid: GridDimensions) -> Drill {
+        let patterns = patterns(grid);
+        let last_runs = vec![None; patterns.len()];
+        Drill { patterns, current: 0, time: Duration::ZERO, deaths: 0, last_runs }
+    }
+
+    // the fruit of the current drill
+    pub fn script(&self) -> FoodScript {
+        FoodScript::new(self.patterns[self.current].fruit.clone(), ScriptEnd::Stop)
+    }
+
+    fn finish(&mut self) {
+        self.last_runs[self.current] = Some(Run { seconds: self.time.as_secs_f32(), deaths: self.deaths });
+        self.current = (self.current + 1) % self.patterns.len();
+        self.time = Duration::ZERO;
+        self.deaths = 0;
+    }
+
+    fn caption(&self) -> String {
+        let pattern = &self.patterns[self.current];
+        let mut caption = format!(
+            "Drill {}/{}: {}\n{:.1}s, deaths: {}",
+            self.current + 1,
+            self.patterns.len(),
+            pattern.name,
+            self.time.as_secs_f32(),
+            self.deaths,
+        );
+        for (pattern, run) in self.patterns.iter().zip(self.last_runs.iter()) {
+            if let Some(run) = run {
+                caption += &format!("\n{}: {:.1}s, deaths: {}", pattern.name, run.seconds, run.deaths);
+            }
+        }
+        caption
+    }
+}
+
+// laid out for the board the game starts with. fruit a smaller board doesn't have is skipped.
+fn patterns(grid: GridDimensions) -> Vec<Pattern> {
+    let (width, height) = (grid.width as i32, grid.height as i32);
+    let at = |x, y| Position { x, y };
+    // up one column and straight back down the next
+    let first_column = width / 2 - 3;
+    let u_turns = (0..6)
+        .map(|i| at(first_column + i, if i % 2 == 0 { height - 4 } else { 3 }))
+        .collect();
+    // the corners of smaller and smaller rings, turning the same way every time
+    let spiral = (0..3)
+        .flat_map(|ring| {
+            let (low, high_x, high_y) = (3 + 3 * ring, width - 4 - 3 * ring, height - 4 - 3 * ring);
+            vec![at(low, high_y), at(high_x, high_y), at(high_x, low), at(low + 3, low)]
+        })
+        .collect();
+    // round and round a box barely bigger than a turn
+    let (x, y) = (width / 2, height / 2);
+    let tight_box = [at(x, y), at(x + 2, y), at(x + 2, y + 2), at(x, y + 2)].repeat(2);
+    vec![
+        Pattern { name: "U-turns", fruit: u_turns },
+        Pattern { name: "Spiral", fruit: spiral },
+        Pattern { name: "Tight box", fruit: tight_box },
+    ]
+}
+
+// runs before game_over: a death is always the end of the game in a drill, so the board is cleared
+// and the drill starts over from its first fruit
+pub fn progress(
+    drill: Option<ResMut<Drill>>,
+    delta: Res<FrameDelta>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut lives: ResMut<Lives>,
+    mut script: Option<ResMut<FoodScript>>,
+    food: Query<&Position, With<Food>>,
+) {
+    let (mut drill, script) = match (drill, script.as_deref_mut()) {
+        (Some(drill), Some(script)) => (drill, script),
+        _ => return,
+    };
+    drill.time += delta.0;
+    if game_over_reader.iter().next().is_some() {
+        lives.0 = 1;
+        drill.deaths += 1;
+        script.rewind();
+    } else if script.is_done() && food.iter().next().is_none() {
+        drill.finish();
+        *script = drill.script();
+    }
+}
+
+pub fn drill_text(drill: Option<Res<Drill>>, mut texts: Query<&mut Text, With<DrillText>>) {
+    if let Some(drill) = drill.filter(|drill| drill.is_changed()) {
+        let caption = drill.caption();
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = caption.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn drills_follow_each_other() {
+        let grid = GridDimensions::default();
+        let mut drill = Drill::new(grid);
+        for pattern in drill.patterns.iter() {
+            assert!(pattern.fruit.iter().all(|fruit| fruit.in_bounds(grid)), "{} is off the board", pattern.name);
+        }
+
+        let mut script = drill.script();
+        let placed = std::iter::from_fn(|| script.next(&HashSet::new(), grid)).collect::<Vec<Position>>();
+        assert_eq!(placed, drill.patterns[0].fruit);
+        assert!(script.is_done());
+
+        drill.time = Duration::from_secs(12);
+        drill.deaths = 2;
+        drill.finish();
+        assert_eq!(drill.current, 1);
+        assert_eq!(drill.last_runs[0], Some(Run { seconds: 12.0, deaths: 2 }));
+        assert_eq!((drill.time, drill.deaths), (Duration::ZERO, 0));
+        assert!(drill.caption().starts_with("Drill 2/3: Spiral"));
+        assert!(drill.caption().ends_with("U-turns: 12.0s, deaths: 2"));
+
+        // after the last one it goes back to the first
+        drill.finish();
+        drill.finish();
+        assert_eq!(drill.current, 0);
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::{convert, convert_y, cursor_tile, hud_text, usable_window, Args, GridDimensions, Position, YAxisDown, TITLE};
+
+// A level is a plain text map of the board, one line per row with the top row first. # is a wall,
+// S is where the snek starts and . is an empty tile. The board is as big as the map.
+//
+//     ..........
+//     ..####....
+//     ..........
+//     .....S....
+//
+#[derive(PartialEq, Debug)]
+pub struct Level {
+    grid: GridDimensions,
+    walls: HashSet<Position>,
+    start: Option<Position>,
+}
+
+impl Level {
+    fn empty(grid: GridDimensions) -> Level {
+        Level { grid, walls: HashSet::new(), start: None }
+    }
+
+    fn tile(&self, pos: Position) -> char {
+        if self.start == Some(pos) {
+            'S'
+        } else if self.walls.contains(&pos) {
+            '#'
+        } else {
+            '.'
+        }
+    }
+
+    // there is only one start, placing it somewhere else moves it
+    fn apply(&mut self, tool: Tool, pos: Position) {
+        if self.start == Some(pos) {
+            self.start = None;
+        }
+        match tool {
+            Tool::Wall => {
+                self.walls.insert(pos);
+            }
+            Tool::Start => {
+                self.walls.remove(&pos);
+                self.start = Some(pos);
+            }
+            Tool::Erase => {
+                self.walls.remove(&pos);
+            }
+        }
+    }
+
+    fn to_ascii(&self) -> String {
+        (0..self.grid.height as i32).rev()
+            .map(|y| {
+                let mut row = (0..self.grid.width as i32).map(|x| self.tile(Position { x, y })).collect::<String>();
+                row.push('\n');
+                row
+            })
+            .collect()
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_ascii())
+    }
+}
+
+pub fn load(path: &Path) -> io::Result<Level> {
+    parse(&fs::read_to_string(path)?)
+}
+
+fn parse(map: &str) -> io::Result<Level> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let rows = map.lines().map(str::trim_end).filter(|row| !row.is_empty()).collect::<Vec<&str>>();
+    let width = rows.first().map_or(0, |row| row.chars().count());
+    if width == 0 {
+        return Err(invalid("the map is empty".to_string()));
+    }
+    let mut level = Level::empty(GridDimensions { width: width as u32, height: rows.len() as u32 });
+    for (i, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            return Err(invalid(format!("row {} isn't {} tiles wide", i + 1, width)));
+        }
+        let y = rows.len() as i32 - 1 - i as i32;
+        for (x, tile) in row.chars().enumerate() {
+            let pos = Position { x: x as i32, y };
+            match tile {
+                '#' => {
+                    level.walls.insert(pos);
+                }
+                'S' if level.start.is_none() => level.start = Some(pos),
+                'S' => return Err(invalid("the map has more than one start".to_string())),
+                '.' => {}
+                _ => return Err(invalid(format!("unknown tile {:?} in row {}", tile, i + 1))),
+            }
+        }
+    }
+    Ok(level)
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum Tool {
+    Wall,
+    Start,
+    Erase,
+}
+
+// the level being edited, and where it goes
+struct Editor {
+    level: Level,
+    tool: Tool,
+    path: PathBuf,
+    // how the last save went, until the next change
+    message: Option<String>,
+}
+
+struct EditorMaterials {
+    empty: Handle<ColorMaterial>,
+    wall: Handle<ColorMaterial>,
+    start: Handle<ColorMaterial>,
+}
+
+struct EditorTile(Position);
+
+struct EditorText;
+
+// --editor: a window of its own with the board to draw on instead of the game. the file is opened
+// if it is already there, S saves it. 1, 2 and 3 pick what the left mouse button puts down: walls,
+// the start or empty tiles.
+pub fn run(path: &Path) {
+    let level = if path.exists() {
+        match load(path) {
+            Ok(level) => level,
+            Err(e) => Args::fail(&format!("could not load {}: {}", path.display(), e)),
+        }
+    } else {
+        Level::empty(GridDimensions::default())
+    };
+    App::build()
+        .insert_resource(WindowDescriptor {
+            title: format!("{} level editor - {}", TITLE, path.display()),
+            width: 1000.0,
+            height: 1000.0,
+            ..Default::default()
+        })
+        .insert_resource(Editor { level, tool: Tool::Wall, path: path.to_path_buf(), message: None })
+        .add_plugins(DefaultPlugins)
+        .add_startup_system(setup.system())
+        .add_system(pick_tool.system())
+        .add_system(paint.system())
+        .add_system(save.system())
+        .add_system(draw_tiles.system())
+        .add_system(status_text.system())
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    editor: Res<Editor>,
+) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(UiCameraBundle::default());
+    // the same colors as in the game: walls like the obstacle, the start like the head
+    let editor_materials = EditorMaterials {
+        empty: materials.add(Color::hex("1A1A1A").unwrap().into()),
+        wall: materials.add(Color::hex("FF5C5C").unwrap().into()),
+        start: materials.add(Color::hex("EFEFEF").unwrap().into()),
+    };
+    for pos in editor.level.grid.tiles() {
+        commands.spawn_bundle(SpriteBundle {
+            material: editor_materials.empty.clone(),
+            ..Default::default()
+        })
+            .insert(EditorTile(pos));
+    }
+    commands.insert_resource(editor_materials);
+    commands.spawn_bundle(hud_text(
+        asset_server.load("fonts/DejaVuSans.ttf"),
+        Rect { top: Val::Px(5.0), left: Val::Px(10.0), ..Default::default() },
+        Color::hex("EFEFEF").unwrap(),
+    ))
+        .insert(EditorText);
+}
+
+fn pick_tool(keyboard_input: Res<Input<KeyCode>>, mut editor: ResMut<Editor>) {
+    let tool = if keyboard_input.just_pressed(KeyCode::Key1) {
+        Tool::Wall
+    } else if keyboard_input.just_pressed(KeyCode::Key2) {
+        Tool::Start
+    } else if keyboard_input.just_pressed(KeyCode::Key3) {
+        Tool::Erase
+    } else {
+        return;
+    };
+    editor.tool = tool;
+}
+
+// holding the button down paints every tile the cursor passes over
+fn paint(mouse_input: Res<Input<MouseButton>>, windows: Res<Windows>, mut editor: ResMut<Editor>) {
+    if !mouse_input.pressed(MouseButton::Left) {
+        return;
+    }
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+    let grid = editor.level.grid;
+    // the editor's camera never moves or zooms, and there are no border walls around the board
+    let pos = window.cursor_position()
+        .and_then(|cursor| cursor_tile(cursor, window_size, (Vec2::ZERO, 1.0), (grid, 0.0), YAxisDown(false)));
+    if let Some(pos) = pos {
+        let tool = editor.tool;
+        let before = editor.level.tile(pos);
+        editor.level.apply(tool, pos);
+        if editor.level.tile(pos) != before {
+            editor.message = None;
+        }
+    }
+}
+
+fn save(keyboard_input: Res<Input<KeyCode>>, mut editor: ResMut<Editor>) {
+    if !keyboard_input.just_pressed(KeyCode::S) {
+        return;
+    }
+    let message = match editor.level.save(&editor.path) {
+        Ok(()) => format!("Saved {}", editor.path.display()),
+        Err(e) => format!("Could not save {}: {}", editor.path.display(), e),
+    };
+    editor.message = Some(message);
+}
+
+fn draw_tiles(
+    editor: Res<Editor>,
+    windows: Res<Windows>,
+    materials: Res<EditorMaterials>,
+    mut tiles: Query<(&EditorTile, &mut Handle<ColorMaterial>, &mut Sprite, &mut Transform)>,
+) {
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    let grid = editor.level.grid;
+    let tile_size = Vec2::new(window.width() / grid.width as f32, window.height() / grid.height as f32);
+    for (tile, mut material, mut sprite, mut transform) in tiles.iter_mut() {
+        let wanted = match editor.level.tile(tile.0) {
+            '#' => &materials.wall,
+            'S' => &materials.start,
+            _ => &materials.empty,
+        };
+        if *material != *wanted {
+            *material = wanted.clone();
+        }
+        // a small gap between the tiles, so they can be counted
+        sprite.size = tile_size * 0.92;
+        transform.translation = Vec3::new(
+            convert(tile.0.x as f32, window.width(), grid.width as f32),
+            convert_y(tile.0.y as f32, window.height(), grid, YAxisDown(false)),
+            0.0,
+        );
+    }
+}
+
+fn status_text(editor: Res<Editor>, mut texts: Query<&mut Text, With<EditorText>>) {
+    if !editor.is_changed() {
+        return;
+    }
+    let mut status = format!("{:?} (1 wall, 2 start, 3 erase, S to save)", editor.tool);
+    if let Some(message) = &editor.message {
+        status = format!("{}\n{}", status, message);
+    }
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_saved_the_way_they_are_read() {
+        let map = "....\n.##.\n...S\n";
+        let mut level = parse(map).unwrap();
+        assert_eq!(level.grid, GridDimensions { width: 4, height: 3 });
+        assert_eq!(level.start, Some(Position { x: 3, y: 0 }));
+        assert!(level.walls.contains(&Position { x: 1, y: 1 }));
+        assert_eq!(level.to_ascii(), map);
+
+        // the start moves instead of showing up twice, and walls can cover it
+        level.apply(Tool::Start, Position { x: 0, y: 2 });
+        level.apply(Tool::Wall, Position { x: 3, y: 0 });
+        level.apply(Tool::Erase, Position { x: 2, y: 1 });
+        assert_eq!(level.to_ascii(), "S...\n.#..\n...#\n");
+        assert_eq!(parse(&level.to_ascii()).unwrap(), level);
+
+        assert!(parse("..\n...\n").is_err());
+        assert!(parse("S.\n.S\n").is_err());
+        assert!(parse(".x\n").is_err());
+        assert!(parse("\n").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::{GameOverEvent, GridDimensions, Lives, Position};
+
+// A food script is a plain text file with one fruit per line, placed in that order instead of at
+// random. Lines starting with # are comments.
+//
+//     # a zigzag up the board
+//     5 5
+//     24 10
+//     5 15
+//
+pub struct FoodScript {
+    positions: Vec<Position>,
+    // the line to place next
+    next: usize,
+    end: ScriptEnd,
+}
+
+// what happens once every fruit in the script was placed
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+pub enum ScriptEnd {
+    // no more fruit
+    #[default]
+    Stop,
+    // start over from the first line
+    Loop,
+}
+
+impl std::str::FromStr for ScriptEnd {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(Self::Stop),
+            "loop" => Ok(Self::Loop),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn load(path: &Path, end: ScriptEnd) -> io::Result<FoodScript> {
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid line: {}", line));
+    let mut positions = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let position = trimmed.split_once(' ')
+            .and_then(|(x, y)| Some(Position { x: x.parse().ok()?, y: y.trim().parse().ok()? }))
+            .ok_or_else(|| invalid(&line))?;
+        positions.push(position);
+    }
+    Ok(FoodScript::new(positions, end))
+}
+
+impl FoodScript {
+    pub fn new(positions: Vec<Position>, end: ScriptEnd) -> FoodScript {
+        FoodScript { positions, next: 0, end }
+    }
+
+    // the next scripted tile that is free. taken tiles, and ones a resized board doesn't have, are
+    // skipped. a looping script only comes back to them on its next round.
+    pub fn next(&mut self, occupied: &HashSet<Position>, grid: GridDimensions) -> Option<Position> {
+        for _ in 0..self.positions.len() {
+            if self.next == self.positions.len() {
+                match self.end {
+                    ScriptEnd::Stop => return None,
+                    ScriptEnd::Loop => self.next = 0,
+                }
+            }
+            let position = self.positions[self.next];
+            self.next += 1;
+            if position.in_bounds(grid) && !occupied.contains(&position) {
+                return Some(position);
+            }
+        }
+        None
+    }
+
+    // back to the first line
+    pub fn rewind(&mut self) {
+        self.next = 0;
+    }
+
+    // every fruit of a script that stops was placed
+    pub fn is_done(&self) -> bool {
+        self.end == ScriptEnd::Stop && self.next == self.positions.len()
+    }
+}
+
+// a new game starts the script over. losing a life doesn't, the fruit on the board stays then.
+// runs before game_over, which resets the lives.
+pub fn restart(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    lives: Res<Lives>,
+    script: Option<ResMut<FoodScript>>,
+) {
+    if game_over_reader.iter().next().is_none() || lives.0 > 1 {
+        return;
+    }
+    if let Some(mut script) = script {
+        script.rewind();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_is_used_in_order() {
+        let path = std::env::temp_dir().join(format!("snek-food-{}.txt", std::process::id()));
+        std::fs::write(&path, "# three fruits\n1 1\n\n2 2\n3 3\n").unwrap();
+        let stopping = load(&path, ScriptEnd::Stop);
+        let looping = load(&path, ScriptEnd::Loop);
+        std::fs::remove_file(&path).unwrap();
+
+        let grid = GridDimensions::default();
+        let taken = [Position { x: 2, y: 2 }].iter().copied().collect::<HashSet<Position>>();
+        let mut stopping = stopping.unwrap();
+        assert_eq!(stopping.next(&taken, grid), Some(Position { x: 1, y: 1 }));
+        assert_eq!(stopping.next(&taken, grid), Some(Position { x: 3, y: 3 }));
+        assert_eq!(stopping.next(&HashSet::new(), grid), None);
+
+        let mut looping = looping.unwrap();
+        let placed = (0..4).map(|_| looping.next(&HashSet::new(), grid)).collect::<Vec<_>>();
+        assert_eq!(placed[3], placed[0]);
+    }
+}
@@ -0,0 +1,94 @@
+// golden-file tests: a seeded game is played with scripted turns and its final state is compared
+// against a snapshot in tests/golden. run with UPDATE_GOLDEN=1 to rewrite the snapshots after an
+// intended change in behavior.
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+
+use crate::{
+    Args, Direction, Food, FrameDelta, GameRng, Materials, MovementTimer, Position, Score, SnekHead, SnekPlugin,
+    SnekSegments,
+};
+
+const GOLDEN_SEED: u64 = 0x901d;
+
+#[derive(Serialize)]
+struct GoldenState {
+    segments: Vec<Position>,
+    food: Vec<Position>,
+    score: u32,
+}
+
+// plays `ticks` movement steps. the scripted turns are pressed right before their tick.
+fn play(args: &Args, ticks: u32, turns: &[(u32, Direction)]) -> GoldenState {
+    let mut builder = App::build();
+    builder
+        .add_plugins(MinimalPlugins)
+        .insert_resource(GameRng(StdRng::seed_from_u64(GOLDEN_SEED)))
+        .init_resource::<Materials>()
+        .add_plugin(SnekPlugin);
+    args.insert_settings(&mut builder);
+    let mut app = builder.app;
+    // spawns the snek. no game time passes, so nothing moves yet.
+    app.update();
+
+    for tick in 0..ticks {
+        for (_, direction) in turns.iter().filter(|(at, _)| *at == tick) {
+            for mut head in app.world.query::<&mut SnekHead>().iter_mut(&mut app.world) {
+                head.next_direction = *direction;
+            }
+        }
+        let step = app.world.get_resource::<MovementTimer>().unwrap().0.duration();
+        app.world.get_resource_mut::<FrameDelta>().unwrap().0 = step;
+        app.update();
+    }
+
+    let segments = app.world.get_resource::<SnekSegments>().unwrap().0.clone();
+    let mut food = app.world.query_filtered::<&Position, With<Food>>()
+        .iter(&app.world)
+        .copied()
+        .collect::<Vec<Position>>();
+    food.sort_by_key(|pos| (pos.x, pos.y));
+    GoldenState {
+        segments: segments.iter().map(|e| *app.world.get::<Position>(*e).unwrap()).collect(),
+        food,
+        score: app.world.get_resource::<Score>().unwrap().0,
+    }
+}
+
+fn assert_golden(name: &str, state: &GoldenState) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", &format!("{}.json", name)].iter().collect();
+    let actual = serde_json::to_string_pretty(state).unwrap() + "\n";
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("could not read {}: {}", path.display(), e));
+    assert_eq!(actual, expected, "{} changed, rerun with UPDATE_GOLDEN=1 if that was intended", name);
+}
+
+#[test]
+fn fruit_run() {
+    use Direction::*;
+    // picks up the first three fruits on the way
+    let turns = [(16, Right), (34, Up), (36, Right), (41, Down), (46, Left)];
+    assert_golden("fruit_run", &play(&Args::default(), 60, &turns));
+}
+
+#[test]
+fn feast_from_the_center() {
+    use Direction::*;
+    let args = Args {
+        feast: true,
+        center_start: true,
+        ..Default::default()
+    };
+    // the first fruit brings two more with it
+    let turns = [(0, Right), (6, Up), (12, Left), (22, Down)];
+    assert_golden("feast_from_the_center", &play(&args, 40, &turns));
+}
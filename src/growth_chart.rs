@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+
+use crate::instant_replay::InstantReplay;
+use crate::{usable_window, GameOverEvent, Lives, SnekSegments, TickCount};
+
+// a long game is thinned out to at most this many points
+const CHART_POINTS: usize = 60;
+// how thick the line is, in pixels
+const LINE_WIDTH: f32 = 3.0;
+
+// the snek's length on every movement tick, as (tick, length)
+#[derive(Default)]
+pub struct LengthSamples {
+    current: Vec<(u64, usize)>,
+    // the game that just ended, for the chart on the game over screen
+    last_game: Vec<(u64, usize)>,
+}
+
+// runs on every movement tick, before game_over puts a new snek on the board
+pub fn sample(tick: Res<TickCount>, segments: Res<SnekSegments>, mut samples: ResMut<LengthSamples>) {
+    samples.current.push((tick.0, segments.0.len()));
+}
+
+// losing a life goes on with the same game, only the end of the game starts a new chart
+pub fn end_game(mut game_over_reader: EventReader<GameOverEvent>, lives: Res<Lives>, mut samples: ResMut<LengthSamples>) {
+    if game_over_reader.iter().next().is_some() && lives.0 <= 1 {
+        samples.last_game = std::mem::take(&mut samples.current);
+    }
+}
+
+pub struct ChartSprite;
+
+// the length over the game that just ended, in a panel at the bottom of the instant replay. it is
+// drawn once when the replay starts and taken down with it.
+pub fn draw(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    replay: Res<InstantReplay>,
+    samples: Res<LengthSamples>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut chart_materials: Local<Option<(Handle<ColorMaterial>, Handle<ColorMaterial>)>>,
+    sprites: Query<Entity, With<ChartSprite>>,
+) {
+    let shown = sprites.iter().next().is_some();
+    if replay.playing() == shown {
+        return;
+    }
+    if shown {
+        for sprite in sprites.iter() {
+            commands.entity(sprite).despawn();
+        }
+        return;
+    }
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    let (panel_material, line_material) = chart_materials
+        .get_or_insert_with(|| (
+            color_materials.add(Color::rgba(1.0, 1.0, 1.0, 0.08).into()),
+            color_materials.add(Color::hex("EFEFEF").unwrap().into()),
+        ))
+        .clone();
+    let mut draw = |material: Handle<ColorMaterial>, size: Vec2, transform: Transform| {
+        commands.spawn_bundle(SpriteBundle {
+            material,
+            sprite: Sprite::new(size),
+            transform,
+            ..Default::default()
+        })
+            .insert(ChartSprite);
+    };
+
+    // above the replay, which is at 5 and 6
+    let size = Vec2::new(window.width() * 0.5, window.height() * 0.2);
+    let centre = Vec3::new(0.0, -window.height() * 0.35, 7.0);
+    draw(panel_material, size, Transform::from_translation(centre));
+    let bottom_left = centre.truncate() - size / 2.0;
+    let points = chart_points(&samples.last_game, size);
+    for (from, to) in points.iter().zip(points.iter().skip(1)) {
+        let (from, to) = (bottom_left + *from, bottom_left + *to);
+        let along = to - from;
+        // a bit longer than the gap, so the corners between the lines are filled in
+        let line = Vec2::new(along.length() + LINE_WIDTH, LINE_WIDTH);
+        let mut transform = Transform::from_translation(((from + to) / 2.0).extend(7.1));
+        transform.rotation = Quat::from_rotation_z(along.y.atan2(along.x));
+        draw(line_material.clone(), line, transform);
+    }
+}
+
+// where the samples go in a panel of the given size, from its bottom left corner. the first tick is on
+// the left edge and the last one on the right, a length of 0 at the bottom and the longest at the top.
+fn chart_points(samples: &[(u64, usize)], size: Vec2) -> Vec<Vec2> {
+    let (first, last) = match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) if samples.len() > 1 => (first.0, last.0),
+        _ => return Vec::new(),
+    };
+    let longest = samples.iter().map(|(_, length)| *length).max().unwrap_or(1).max(1);
+    let every = samples.len().div_ceil(CHART_POINTS);
+    let mut kept = samples.iter().step_by(every).copied().collect::<Vec<(u64, usize)>>();
+    // the end of the game is always on the chart
+    if kept.last() != samples.last() {
+        kept.push(samples[samples.len() - 1]);
+    }
+    let ticks = (last - first).max(1) as f32;
+    kept.iter()
+        .map(|(tick, length)| Vec2::new(
+            (tick - first) as f32 / ticks * size.x,
+            *length as f32 / longest as f32 * size.y,
+        ))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_chart_fits_the_panel() {
+        let size = Vec2::new(200.0, 50.0);
+        assert!(chart_points(&[(3, 2)], size).is_empty());
+
+        let points = chart_points(&[(10, 2), (11, 3), (12, 4), (14, 2)], size);
+        assert_eq!(points, vec![
+            Vec2::new(0.0, 25.0),
+            Vec2::new(50.0, 37.5),
+            Vec2::new(100.0, 50.0),
+            Vec2::new(200.0, 25.0),
+        ]);
+
+        // a long game is thinned out, but still ends on the right edge
+        let long = (0..1000).map(|tick| (tick, 2 + tick as usize / 10)).collect::<Vec<(u64, usize)>>();
+        let points = chart_points(&long, size);
+        assert!(points.len() <= CHART_POINTS + 1);
+        assert_eq!(*points.last().unwrap(), size);
+    }
+}
@@ -0,0 +1,141 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{Direction, Position, Score, SnekHead, SnekSegments};
+
+// Requests are answered one at a time, so a client that connects and then says nothing only gets
+// this long before the next one's turn.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Resource holding the sending end of the state channel, see start.
+pub struct HttpState(Sender<String>);
+
+#[derive(Serialize)]
+struct State {
+    score: u32,
+    length: usize,
+    head: Position,
+    direction: Direction,
+}
+
+// Serves GET /state on localhost with the state as of the last movement tick, as JSON.
+// Like the broadcast, the socket work happens on background threads: one keeps the latest state
+// that comes through the channel, the other answers requests one at a time.
+pub fn start(port: u16) -> io::Result<HttpState> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| match e.kind() {
+        io::ErrorKind::AddrInUse => io::Error::new(e.kind(), format!("port {} is already in use", port)),
+        _ => e,
+    })?;
+    let latest = Arc::new(Mutex::new("{}".to_string()));
+
+    let (sender, receiver) = mpsc::channel::<String>();
+    let updating = latest.clone();
+    thread::spawn(move || {
+        for state in receiver {
+            *updating.lock().unwrap() = state;
+        }
+    });
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let state = latest.lock().unwrap().clone();
+            // a client that hangs up early just doesn't get an answer
+            let _ = respond(stream, &state);
+        }
+    });
+
+    Ok(HttpState(sender))
+}
+
+fn respond(mut stream: TcpStream, state: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // the headers are read too, closing with them still unread would reset the connection
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let request = request_line.split_whitespace().take(2).collect::<Vec<&str>>();
+    let (status, body) = match request.as_slice() {
+        ["GET", "/state"] => ("200 OK", state),
+        ["GET", _] => ("404 Not Found", "not found\n"),
+        _ => ("405 Method Not Allowed", "only GET is supported\n"),
+    };
+    let content_type = if status == "200 OK" { "application/json" } else { "text/plain" };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body,
+    )
+}
+
+// does nothing unless --http was given
+pub fn publish_state(
+    http: Option<Res<HttpState>>,
+    segments: Res<SnekSegments>,
+    score: Res<Score>,
+    heads: Query<(&SnekHead, &Position)>,
+) {
+    if let Some(http) = http {
+        if let Some((head, position)) = heads.iter().next() {
+            let state = State {
+                score: score.0,
+                length: segments.0.len(),
+                head: *position,
+                direction: head.direction,
+            };
+            // the state thread only stops if the whole app is shutting down
+            let _ = http.0.send(serde_json::to_string(&state).unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn serves_the_latest_state() {
+        // port 0 picks a free one, so the test can't run into a port that is in use
+        let probe = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = probe.local_addr().unwrap().port();
+        drop(probe);
+        let http = start(port).unwrap();
+        assert_eq!(start(port).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+
+        http.0.send("{\"score\":10}".to_string()).unwrap();
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        // the state thread might not have picked it up on the first try
+        let mut state = get("/state");
+        for _ in 0..100 {
+            if state.ends_with("{\"score\":10}") {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+            state = get("/state");
+        }
+        assert!(state.starts_with("HTTP/1.1 200 OK"));
+        assert!(state.ends_with("{\"score\":10}"));
+        assert!(get("/nope").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    convert, convert_y, usable_window, BorderWalls, Food, FoodKind, GameOverEvent, GridDimensions,
+    InstantReplayText, Lives, Materials, MovementTimer, Position, SnekSegments, YAxisDown,
+};
+
+// how much of the game before a game over is kept, in game time
+const REPLAY_SECONDS: f32 = 5.0;
+// how dark the board gets behind the replay
+const BACKDROP_ALPHA: f32 = 0.85;
+
+// the board as it was after one movement tick
+struct Frame {
+    segments: Vec<Position>,
+    food: Vec<(Position, FoodKind)>,
+    // how long the tick lasted, so the replay runs at the speed the game did
+    length: Duration,
+}
+
+// The last REPLAY_SECONDS of the game, one frame per movement tick. On a game over they are played
+// back on a loop over the new board, with game time standing still, until Enter starts the next game.
+#[derive(Default)]
+pub struct InstantReplay {
+    frames: VecDeque<Frame>,
+    recorded: Duration,
+    // the frame on screen and how long it has been, while the replay plays
+    playing: Option<(usize, Timer)>,
+}
+
+impl InstantReplay {
+    pub fn playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    fn push(&mut self, frame: Frame) {
+        self.recorded += frame.length;
+        self.frames.push_back(frame);
+        let kept = Duration::from_secs_f32(REPLAY_SECONDS);
+        while self.recorded > kept {
+            if let Some(oldest) = self.frames.pop_front() {
+                self.recorded -= oldest.length;
+            }
+        }
+    }
+
+    fn play(&mut self) {
+        self.playing = self.frames.front().map(|first| (0, Timer::new(first.length, false)));
+    }
+
+    fn stop(&mut self) {
+        self.frames.clear();
+        self.recorded = Duration::ZERO;
+        self.playing = None;
+    }
+}
+
+// keeps a frame whenever the snek moved. runs after snek_movement and before game_over, so the
+// tick that ends the game is in it, with the head in whatever it ran into.
+#[allow(clippy::too_many_arguments)]
+pub fn record(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    lives: Res<Lives>,
+    movement_timer: Res<MovementTimer>,
+    segments: Res<SnekSegments>,
+    positions: Query<&Position>,
+    food: Query<(&Position, &FoodKind), With<Food>>,
+    mut replay: ResMut<InstantReplay>,
+    mut last_segments: Local<Vec<Position>>,
+) {
+    if replay.playing() {
+        return;
+    }
+    let segment_positions = segments.0.iter()
+        .filter_map(|e| positions.get(*e).ok().copied())
+        .collect::<Vec<Position>>();
+    if segment_positions != *last_segments {
+        replay.push(Frame {
+            segments: segment_positions.clone(),
+            food: food.iter().map(|(pos, kind)| (*pos, *kind)).collect(),
+            length: movement_timer.0.duration(),
+        });
+        *last_segments = segment_positions;
+    }
+    // losing a life goes on right away, only the end of the game gets a replay
+    if game_over_reader.iter().next().is_some() && lives.0 <= 1 {
+        replay.play();
+    }
+}
+
+// the frames are drawn on their own sprites, over a dark backdrop that hides the board underneath.
+// they run on real time, since game time stands still during the replay.
+#[allow(clippy::too_many_arguments)]
+pub fn play(
+    mut commands: Commands,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    (grid, border_walls, y_axis_down): (Res<GridDimensions>, Res<BorderWalls>, Res<YAxisDown>),
+    materials: Res<Materials>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut replay: ResMut<InstantReplay>,
+    mut backdrop_material: Local<Option<Handle<ColorMaterial>>>,
+    sprites: Query<Entity, With<ReplaySprite>>,
+    mut texts: Query<&mut Text, With<InstantReplayText>>,
+) {
+    if !replay.playing() {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        replay.stop();
+        for sprite in sprites.iter() {
+            commands.entity(sprite).despawn();
+        }
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = String::new();
+        }
+        return;
+    }
+
+    let frame_count = replay.frames.len();
+    let (shown, timer) = replay.playing.as_mut().unwrap();
+    let first_frame = sprites.iter().next().is_none();
+    if !timer.tick(time.delta()).finished() && !first_frame {
+        return;
+    }
+    if !first_frame {
+        *shown = (*shown + 1) % frame_count;
+    }
+    let shown = *shown;
+    let length = replay.frames[shown].length;
+    replay.playing = Some((shown, Timer::new(length, false)));
+    let frame = &replay.frames[shown];
+
+    for sprite in sprites.iter() {
+        commands.entity(sprite).despawn();
+    }
+    let caption = format!("Instant replay  -{:.1}s\nEnter to play again", seconds_left(&replay, shown));
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = caption.clone();
+    }
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    let (board, inset) = border_walls.drawn(*grid);
+    let tile = Vec2::new(window.width() / board.width as f32, window.height() / board.height as f32);
+    let mut draw = |material: Handle<ColorMaterial>, size: Vec2, translation: Vec3| {
+        commands.spawn_bundle(SpriteBundle {
+            material,
+            sprite: Sprite::new(size),
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        })
+            .insert(ReplaySprite);
+    };
+
+    let backdrop = backdrop_material
+        .get_or_insert_with(|| color_materials.add(Color::rgba(0.0, 0.0, 0.0, BACKDROP_ALPHA).into()))
+        .clone();
+    // drawn above everything else on the board
+    draw(backdrop, Vec2::new(window.width(), window.height()), Vec3::new(0.0, 0.0, 5.0));
+    let at = |pos: Position| Vec3::new(
+        convert(pos.x as f32 + inset, window.width(), board.width as f32),
+        convert_y(pos.y as f32 + inset, window.height(), board, *y_axis_down),
+        6.0,
+    );
+    for (pos, kind) in frame.food.iter() {
+        draw(materials.food_material(*kind), tile * 0.7, at(*pos));
+    }
+    for (i, pos) in frame.segments.iter().enumerate() {
+        let material = if i == 0 { &materials.head_material } else { &materials.segment_material };
+        draw(material.clone(), tile * 0.8, at(*pos));
+    }
+}
+
+// how long before the game over the frame on screen is
+fn seconds_left(replay: &InstantReplay, shown: usize) -> f32 {
+    replay.frames.iter().skip(shown + 1).map(|frame| frame.length.as_secs_f32()).sum()
+}
+
+pub struct ReplaySprite;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_last_seconds_are_kept() {
+        let mut replay = InstantReplay::default();
+        let tick = Duration::from_millis(200);
+        for x in 0..40 {
+            replay.push(Frame { segments: vec![Position { x, y: 0 }], food: Vec::new(), length: tick });
+        }
+        let kept = (REPLAY_SECONDS / tick.as_secs_f32()).round() as usize;
+        assert_eq!(replay.frames.len(), kept);
+        assert_eq!(replay.frames.back().unwrap().segments[0].x, 39);
+        assert!((seconds_left(&replay, 0) - (REPLAY_SECONDS - tick.as_secs_f32())).abs() < 0.001);
+
+        replay.play();
+        assert!(replay.playing());
+        replay.stop();
+        assert!(!replay.playing());
+        assert!(replay.frames.is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    GameOverEvent, GridDimensions, KeysText, Lives, Materials, Position, RoomWalls, Size, Stopwatch, DOOR_WIDTH,
+    MIN_ROOMS_BOARD,
+};
+
+// how long "Cleared" stays up after reaching the goal
+const CLEARED_MESSAGE_SECONDS: f32 = 4.0;
+
+// --keys: the doorways between the rooms start out locked. every room has a key that opens the door
+// to the next one, and the goal is in the last room. reaching it wins and starts the level over.
+pub struct KeyDoors(pub bool);
+
+// a key opens every door with the same id
+pub struct Key {
+    pub door: u32,
+}
+
+// one tile of a locked doorway. it is in RoomWalls until its key is picked up.
+pub struct DoorWall {
+    pub id: u32,
+}
+
+pub struct GoalTile;
+
+pub struct GameWonEvent;
+
+// where the goal and the keys that are still lying around are, so fruit isn't put on top of them
+#[derive(Default)]
+pub struct Objective {
+    pub goal: Option<Position>,
+    pub taken: HashSet<Position>,
+}
+
+// the rooms go around the board: from the bottom left, where the snek starts, to the top left, the
+// top right and the bottom right, with the goal. the doorway straight back from the last room to the
+// first opens with the last key too, otherwise it would be a shortcut.
+struct Layout {
+    doors: Vec<(Position, u32)>,
+    keys: Vec<(Position, u32)>,
+    goal: Position,
+}
+
+impl Layout {
+    fn new(grid: GridDimensions) -> Option<Layout> {
+        if grid.width < MIN_ROOMS_BOARD || grid.height < MIN_ROOMS_BOARD {
+            return None;
+        }
+        let (width, height) = (grid.width as i32, grid.height as i32);
+        let (middle_x, middle_y) = (width / 2, height / 2);
+        let at = |x, y| Position { x, y };
+        // the same doorways as room_layout leaves open
+        let doors = (0..DOOR_WIDTH)
+            .flat_map(|i| vec![
+                (at(width / 4 - i, middle_y), 0),
+                (at(middle_x, height * 3 / 4 - i), 1),
+                (at(width * 3 / 4 - i, middle_y), 2),
+                (at(middle_x, height / 4 - i), 2),
+            ])
+            .collect();
+        // in the middle of their rooms
+        let (left, right, bottom, top) = (width / 4, width * 3 / 4, height / 4, height * 3 / 4);
+        let keys = vec![(at(left, bottom), 0), (at(left, top), 1), (at(right, top), 2)];
+        Some(Layout { doors, keys, goal: at(right, bottom) })
+    }
+}
+
+// puts the doors, keys and goal back at the start of every game, and on a resized board. runs
+// after fit_rooms has rebuilt the walls around the doorways.
+#[allow(clippy::too_many_arguments)]
+pub fn fit(
+    mut commands: Commands,
+    key_doors: Res<KeyDoors>,
+    grid: Res<GridDimensions>,
+    materials: Res<Materials>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut room_walls: ResMut<RoomWalls>,
+    mut objective: ResMut<Objective>,
+    placed: Query<Entity, Or<(With<Key>, With<DoorWall>, With<GoalTile>)>>,
+) {
+    let game_over = game_over_reader.iter().next().is_some();
+    if !key_doors.is_changed() && !grid.is_changed() && !game_over {
+        return;
+    }
+    for ent in placed.iter() {
+        commands.entity(ent).despawn();
+    }
+    *objective = Objective::default();
+    let layout = match Layout::new(*grid).filter(|_| key_doors.0) {
+        Some(layout) => layout,
+        None => return,
+    };
+    for (position, id) in layout.doors {
+        room_walls.0.insert(position);
+        spawn_tile(&mut commands, &materials.door_material, position, 1.0).insert(DoorWall { id });
+    }
+    for (position, door) in layout.keys {
+        objective.taken.insert(position);
+        spawn_tile(&mut commands, &materials.key_material, position, 0.7).insert(Key { door });
+    }
+    objective.taken.insert(layout.goal);
+    objective.goal = Some(layout.goal);
+    spawn_tile(&mut commands, &materials.goal_material, layout.goal, 1.0).insert(GoalTile);
+}
+
+fn spawn_tile<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    material: &Handle<ColorMaterial>,
+    position: Position,
+    size: f32,
+) -> bevy::ecs::system::EntityCommands<'a, 'b> {
+    let mut tile = commands.spawn_bundle(SpriteBundle {
+        material: material.clone(),
+        ..Default::default()
+    });
+    tile.insert(position).insert(Size::square(size));
+    tile
+}
+
+// takes the walls of a door down, called by snek_eating when the head is on its key
+pub fn open(
+    commands: &mut Commands,
+    id: u32,
+    doors: &Query<(Entity, &Position, &DoorWall)>,
+    room_walls: &mut RoomWalls,
+) {
+    for (ent, position, door) in doors.iter() {
+        if door.id == id {
+            commands.entity(ent).despawn();
+            room_walls.0.remove(position);
+        }
+    }
+}
+
+// runs before game_over: reaching the goal is the end of the game, however many lives are left, and
+// the level starts over
+pub fn win(
+    mut won_reader: EventReader<GameWonEvent>,
+    stopwatch: Res<Stopwatch>,
+    mut lives: ResMut<Lives>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    mut cleared: ResMut<Cleared>,
+) {
+    if won_reader.iter().next().is_some() {
+        lives.0 = 1;
+        game_over_writer.send(GameOverEvent);
+        cleared.0 = Some((stopwatch.0.as_secs_f32(), Timer::from_seconds(CLEARED_MESSAGE_SECONDS, false)));
+    }
+}
+
+// how long the last win took, while it is shown
+#[derive(Default)]
+pub struct Cleared(Option<(f32, Timer)>);
+
+pub fn keys_text(
+    time: Res<Time>,
+    key_doors: Res<KeyDoors>,
+    mut cleared: ResMut<Cleared>,
+    keys: Query<&Key>,
+    mut texts: Query<&mut Text, With<KeysText>>,
+) {
+    if cleared.0.as_mut().is_some_and(|(_, timer)| timer.tick(time.delta()).finished()) {
+        cleared.0 = None;
+    }
+    let caption = match (&cleared.0, key_doors.0) {
+        (Some((seconds, _)), _) => format!("Cleared in {:.1}s!", seconds),
+        (None, true) => keys_caption(keys.iter().count()),
+        (None, false) => String::new(),
+    };
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != caption {
+            text.sections[0].value = caption.clone();
+        }
+    }
+}
+
+fn keys_caption(left: usize) -> String {
+    match left {
+        0 => "All keys, on to the goal".to_string(),
+        _ => format!("Keys left: {}", left),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::room_layout;
+
+    #[test]
+    fn the_doors_lock_the_doorways_between_the_rooms() {
+        let grid = GridDimensions::default();
+        let layout = Layout::new(grid).unwrap();
+        let walls = room_layout(grid);
+        let (middle_x, middle_y) = (grid.width as i32 / 2, grid.height as i32 / 2);
+        // every gap in the walls between the rooms gets a door, and nothing else does
+        let gaps = (0..grid.width as i32).map(|x| Position { x, y: middle_y })
+            .chain((0..grid.height as i32).map(|y| Position { x: middle_x, y }))
+            .filter(|pos| !walls.contains(pos))
+            .collect::<HashSet<Position>>();
+        let doors = layout.doors.iter().map(|(pos, _)| *pos).collect::<HashSet<Position>>();
+        assert_eq!(doors, gaps);
+        // the last key opens both ways into the goal's room
+        assert_eq!(layout.doors.iter().filter(|(_, id)| *id == 2).count(), 2 * DOOR_WIDTH as usize);
+        for (key, _) in layout.keys.iter() {
+            assert!(!walls.contains(key) && !doors.contains(key));
+        }
+        assert!(Layout::new(GridDimensions { width: 5, height: 5 }).is_none());
+        assert_eq!(keys_caption(2), "Keys left: 2");
+    }
+}
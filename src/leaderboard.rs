@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameOverEvent, Lives, Score};
+
+const SCORES_FILE: &str = "snek-scores.json";
+
+// A calendar day in UTC. Its number, e.g. 20261015, seeds the daily challenge.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Date {
+    year: i64,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    pub fn today() -> Date {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Date::from_days(seconds as i64 / 86400)
+    }
+
+    // days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    fn from_days(days: i64) -> Date {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year, month, day }
+    }
+
+    pub fn seed(&self) -> u64 {
+        (self.year * 10000 + self.month as i64 * 100 + self.day as i64) as u64
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Scores {
+    best: u32,
+    // the best score of each daily challenge, by date
+    daily: BTreeMap<String, u32>,
+}
+
+// Best scores, kept in snek-scores.json in the working directory.
+// Daily challenges are kept apart from free games, since only those are comparable.
+pub struct Leaderboard {
+    path: PathBuf,
+    scores: Scores,
+    day: Option<Date>,
+}
+
+impl Leaderboard {
+    pub fn load(day: Option<Date>) -> io::Result<Leaderboard> {
+        let path = PathBuf::from(SCORES_FILE);
+        let scores = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Scores::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(Leaderboard { path, scores, day })
+    }
+
+    // the score to beat in this kind of game
+    pub fn best(&self) -> u32 {
+        match self.day {
+            Some(day) => self.scores.daily.get(&day.to_string()).copied().unwrap_or(0),
+            None => self.scores.best,
+        }
+    }
+
+    // returns true for a new best score
+    fn submit(&mut self, score: u32) -> bool {
+        if score <= self.best() {
+            return false;
+        }
+        match self.day {
+            Some(day) => {
+                self.scores.daily.insert(day.to_string(), score);
+            }
+            None => self.scores.best = score,
+        }
+        true
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.scores).map_err(io::Error::other)?;
+        fs::write(&self.path, json + "\n")
+    }
+}
+
+// runs before game_over, which resets the score once the last life is gone
+pub fn record_final_score(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    lives: Res<Lives>,
+    score: Res<Score>,
+    leaderboard: Option<ResMut<Leaderboard>>,
+) {
+    if game_over_reader.iter().next().is_none() || lives.0 > 1 {
+        return;
+    }
+    if let Some(mut leaderboard) = leaderboard {
+        if leaderboard.submit(score.0) {
+            match leaderboard.day {
+                Some(day) => println!("new best score for the {} challenge: {}", day, score.0),
+                None => println!("new best score: {}", score.0),
+            }
+            if let Err(e) = leaderboard.save() {
+                eprintln!("snek: could not save {}: {}", leaderboard.path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_become_dates() {
+        assert_eq!(Date::from_days(0), Date { year: 1970, month: 1, day: 1 });
+        // a leap day
+        assert_eq!(Date::from_days(19782), Date { year: 2024, month: 2, day: 29 });
+        assert_eq!(Date::from_days(20741).to_string(), "2026-10-15");
+        assert_eq!(Date::from_days(20741).seed(), 20261015);
+    }
+
+    #[test]
+    fn daily_scores_are_kept_apart() {
+        let day = Date { year: 2026, month: 10, day: 15 };
+        let mut free = Leaderboard { path: PathBuf::new(), scores: Scores::default(), day: None };
+        assert!(free.submit(30));
+        assert!(!free.submit(20));
+
+        let mut daily = Leaderboard { path: PathBuf::new(), scores: free.scores, day: Some(day) };
+        assert_eq!(daily.best(), 0);
+        assert!(daily.submit(10));
+        assert_eq!(daily.scores.best, 30);
+        assert_eq!(daily.scores.daily["2026-10-15"], 10);
+    }
+}
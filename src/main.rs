@@ -1,23 +1,213 @@
 use bevy::prelude::*;
-use rand::random;
-use bevy::core::FixedTimestep;
+use bevy::app::{AppExit, ScheduleRunnerSettings};
+use bevy::asset::LoadState;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::render::camera::{Camera, CameraProjection, OrthographicProjection};
+use bevy::render::texture::FilterMode;
+use bevy::window::{WindowFocused, WindowId, WindowResized};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
 
+mod bench;
+mod broadcast;
+mod drill;
+mod editor;
+mod food_script;
+mod growth_chart;
+mod http;
+mod instant_replay;
+mod keys;
+mod leaderboard;
+mod replay;
+mod stats;
+mod tutorial;
+#[cfg(test)]
+mod golden;
+
+const TITLE: &str = "Snek";
+// the board size at the start, see GridDimensions
 const WIDTH: u32 = 30;
 const HEIGHT: u32 = 30;
-const SPAWN_RATE: f64 = 0.5;
+// smallest and largest board the pause menu allows, in tiles either way
+const GRID_LIMITS: (u32, u32) = (20, 60);
+const SPAWN_RATE: f32 = 0.5;
 const MOVEMENT_RATE: f64 = 5.0;
+const MAX_MOVEMENT_RATE: f64 = 15.0;
+const MOVEMENT_RATE_PER_SEGMENT: f64 = 0.2;
+const START_LENGTH: usize = 2;
+// chance for a newly spawned fruit to be one of the special kinds, otherwise it's a regular fruit
+// split fruit only shows up with --wild, fleeing fruit with --fleeing-food and momentum fruit with
+// --momentum-food, otherwise their share goes to regular fruit
+const SPECIAL_FOOD_CHANCES: [(FoodKind, f32); 8] = [
+    (FoodKind::Freeze, 0.1),
+    (FoodKind::Reverse, 0.05),
+    (FoodKind::Target, 0.05),
+    (FoodKind::DoubleScore, 0.05),
+    (FoodKind::Warp, 0.05),
+    (FoodKind::Split, 0.05),
+    (FoodKind::Fleeing, 0.05),
+    (FoodKind::Momentum, 0.05),
+];
+// a snek needs at least this many segments to split, so both halves keep a head and a body
+const SPLIT_MIN_LENGTH: usize = 4;
+const SPEED_FREEZE_SECONDS: f32 = 5.0;
+const DIRECTION_LOCK_SECONDS: f32 = 2.0;
+const DOUBLE_SCORE_FACTOR: f32 = 2.0;
+const DOUBLE_SCORE_SECONDS: f32 = 8.0;
+// full width of the bars in the corners, in pixels
+const HUD_BAR_WIDTH: f32 = 120.0;
+const FOOD_POINTS: u32 = 10;
+const TARGET_FOOD_POINTS: u32 = 50;
+const TARGET_FOOD_SECONDS: f32 = 6.0;
+// with --slow-decay a target fruit lasts this much longer for every segment past the start, up to
+// MAX_SLOW_DECAY times as long
+const SLOW_DECAY_PER_SEGMENT: f32 = 0.02;
+const MAX_SLOW_DECAY: f32 = 2.0;
+// fleeing fruit is hard to catch until it is cornered, so it is worth a bit more
+const FLEEING_FOOD_POINTS: u32 = 30;
+// with --boss-fruit a 2x2 boss shows up after every BOSS_EVERY_FRUITS fruits. it takes BOSS_HP
+// bumps with the head to eat and is worth BOSS_POINTS.
+const BOSS_EVERY_FRUITS: u32 = 10;
+const BOSS_HP: u32 = 3;
+const BOSS_POINTS: u32 = 150;
+// with --cluster-chance a timed spawn sometimes puts down a 2x2 cluster of fruit instead of one.
+// eating the whole cluster within CLUSTER_SECONDS of its first fruit is worth CLUSTER_BONUS_POINTS on top.
+const CLUSTER_SIZE: usize = 4;
+const CLUSTER_SECONDS: f32 = 4.0;
+const CLUSTER_BONUS_POINTS: u32 = 60;
+// in the snake II style every this many fruits a bonus shows up
+const SNAKE_II_BONUS_EVERY: u32 = 5;
+// the ring around a target fruit starts this big (in tiles) and shrinks down to the fruit
+const TARGET_RING_SIZE: f32 = 2.5;
+const SHUFFLE_CHARGES: u32 = 3;
+const PANIC_CHARGES: u32 = 2;
+const PANIC_SLOW_SECONDS: f32 = 2.0;
+// how fast game time runs after a panic
+const PANIC_TIME_SCALE: f32 = 0.5;
+const FEAST_REPLACEMENTS: usize = 2;
+const FEAST_FOOD_CAP: usize = 20;
+// with instant turns, two ticks are at least this fraction of a normal tick apart
+const INSTANT_TURN_MIN_SPACING: f32 = 0.5;
+// how many ticks of the head's path are remembered for the path overlay
+const PATH_HISTORY_LENGTH: usize = 30;
+// how long the "press Q again" prompt waits for the second press
+const QUIT_CONFIRM_SECONDS: f32 = 2.0;
+// how long the screen takes to fade to or from black
+const FADE_SECONDS: f32 = 0.4;
+const FLOATING_SCORE_SECONDS: f32 = 0.8;
+const START_LIVES: u32 = 3;
+// after losing a life, the snek can't bite itself for this long
+const INVULNERABLE_SECONDS: f32 = 2.0;
+// a dead snek dissolves one segment this often, but never takes longer than DISSOLVE_MAX_SECONDS in all
+const DISSOLVE_STEP_SECONDS: f32 = 0.04;
+const DISSOLVE_MAX_SECONDS: f32 = 1.5;
+// how long the segments take to grow or shrink to the new size when B switches the segment style
+const SIZE_TWEEN_SECONDS: f32 = 0.25;
+// more turns than anyone can press on purpose, so only key mashing runs into it
+const MAX_TURNS_PER_SECOND: f32 = 20.0;
+// the thickest --thickness still leaves room to turn on the default board
+const MAX_THICKNESS: u32 = 4;
+// how long the final score stays up after the clock of --time-limit ran out
+const TIME_UP_MESSAGE_SECONDS: f32 = 4.0;
+// how thick the hitbox outlines are, as a part of a tile
+const HITBOX_LINE_WIDTH: f32 = 0.06;
+// --rooms shows a quarter of the board at a time
+const ROOM_ZOOM: f32 = 0.5;
+// how long a segment takes to slide out over one edge and back in over the other with --wall-wrap
+const WRAP_SLIDE_SECONDS: f32 = 0.12;
+// --rooms: how many tiles wide the doorways between two rooms are, and the smallest board that is
+// still split up
+const DOOR_WIDTH: i32 = 2;
+const MIN_ROOMS_BOARD: u32 = 8;
+// pixels per second the score popups drift upwards
+const FLOATING_SCORE_SPEED: f32 = 60.0;
+// the window border flashes in these colors, then fades out over BORDER_FLASH_SECONDS
+const DEATH_FLASH_COLOR: Color = Color::rgb(1.0, 0.36, 0.36);
+const NEAR_MISS_FLASH_COLOR: Color = Color::rgb(1.0, 0.82, 0.3);
+const BORDER_FLASH_SECONDS: f32 = 0.5;
+// in pixels
+const BORDER_FLASH_WIDTH: f32 = 12.0;
+// once less than this share of the board is free, the border pulses and the background shifts
+// towards CAPACITY_WARNING_COLOR. both get stronger, and the pulse faster, the fuller the board gets.
+const CAPACITY_WARNING_FREE: f32 = 0.1;
+const CAPACITY_WARNING_COLOR: Color = Color::rgb(1.0, 0.5, 0.15);
+// pulses per second, from the first warning to a full board
+const CAPACITY_PULSE_RATES: (f32, f32) = (1.0, 4.0);
+// how far the background goes towards the warning color on a full board
+const CAPACITY_BACKGROUND_SHIFT: f32 = 0.25;
+// with --adaptive the speed creeps up while the player survives and drops back on every death.
+// the gains are per second of game time and per fruit eaten in a combo.
+const ADAPTIVE_GAIN_PER_SECOND: f64 = 0.002;
+const ADAPTIVE_COMBO_GAIN: f64 = 0.01;
+const ADAPTIVE_DEATH_PENALTY: f64 = 0.15;
+const ADAPTIVE_LIMITS: (f64, f64) = (0.75, 1.5);
+// fruits eaten less than this far apart (in game time) keep a combo going
+const COMBO_SECONDS: f32 = 3.0;
+// the flame on the head stops growing at this combo
+const COMBO_FLAME_MAX: u32 = 6;
+// with --stretch, body segments catch up with their tiles at this rate (per second), divided by
+// 1 + stretch factor * how far back they are. the head is always right on its tile.
+const STRETCH_CATCH_UP: f32 = 30.0;
+// a lagging segment is pulled along so it never gets further than this from the one in front (in tiles)
+const STRETCH_MAX_GAP: f32 = 1.2;
+// jumps longer than this (in tiles), like a warp or a new snek, aren't stretched
+const STRETCH_SNAP: f32 = 2.0;
+// while paused, in pixels per second at the default zoom
+const SPECTATE_PAN_SPEED: f32 = 400.0;
+// how much the zoom changes per second of holding +/-
+const SPECTATE_ZOOM_SPEED: f32 = 1.5;
+// camera scale: below 1 zooms in, above 1 zooms out
+const ZOOM_LIMITS: (f32, f32) = (0.25, 2.0);
+// how much one notch of the scroll wheel zooms
+const SCROLL_ZOOM_STEP: f32 = 1.1;
+// touchpads scroll in pixels instead of notches
+const SCROLL_PIXELS_PER_NOTCH: f32 = 40.0;
+// the patrolling obstacle walks back and forth on the row this far below the top, away from both
+// start positions. it keeps this far away from the side walls.
+const OBSTACLE_ROW_FROM_TOP: i32 = 7;
+const OBSTACLE_MARGIN: i32 = 8;
+// the background drifts through these (dark) tints as the score goes up, one step per BACKGROUND_SCORE_STEP points
+const BACKGROUND_COLORS: [Color; 4] = [
+    Color::rgb(0.04, 0.04, 0.04),
+    Color::rgb(0.03, 0.05, 0.10),
+    Color::rgb(0.08, 0.03, 0.10),
+    Color::rgb(0.10, 0.05, 0.03),
+];
+const BACKGROUND_SCORE_STEP: f32 = 200.0;
 
-#[derive(Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize)]
 struct Position {
     x: i32,
     y: i32,
 }
 
+#[derive(Copy, Clone)]
 struct Size {
     width: f32,
     height: f32,
 }
 
+impl Position {
+    fn neighbour(self, direction: Direction) -> Self {
+        match direction {
+            Direction::Left => Self { x: self.x - 1, ..self },
+            Direction::Right => Self { x: self.x + 1, ..self },
+            Direction::Up => Self { y: self.y + 1, ..self },
+            Direction::Down => Self { y: self.y - 1, ..self },
+        }
+    }
+
+    fn in_bounds(self, grid: GridDimensions) -> bool {
+        self.x >= 0 && self.x < grid.width as i32 && self.y >= 0 && self.y < grid.height as i32
+    }
+}
+
 impl Size {
     pub fn square(x: f32) -> Self {
         Self {
@@ -27,77 +217,1064 @@ impl Size {
     }
 }
 
+// command line options, e.g. `snek --broadcast 7777`
+#[derive(Default)]
+struct Args {
+    broadcast: Option<u16>,
+    http: Option<u16>,
+    bench: Option<u32>,
+    bench_shift: Option<usize>,
+    feast: bool,
+    truncate_tail: bool,
+    wall_bounce: bool,
+    wall_wrap: bool,
+    grow_delay: u32,
+    y_axis_down: bool,
+    segment_style: SegmentStyle,
+    center_start: bool,
+    spawn_pattern: SpawnPattern,
+    spawn_mode: SpawnMode,
+    instant_turn: bool,
+    difficulty: Difficulty,
+    textured: bool,
+    tick_indicator: bool,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    max_food: Option<usize>,
+    spawn_gauge: bool,
+    wild: bool,
+    start_length: Option<usize>,
+    daily: bool,
+    pixel_art: bool,
+    style: GameStyle,
+    wall_hug_penalty: f32,
+    food_script: Option<PathBuf>,
+    food_script_end: food_script::ScriptEnd,
+    fixed_length: Option<u32>,
+    adaptive: bool,
+    debug: bool,
+    border_walls: bool,
+    cluster_chance: f32,
+    tutorial: bool,
+    stretch: f32,
+    fleeing_food: bool,
+    no_render: bool,
+    steering: SteeringMode,
+    death_anim: DeathAnimation,
+    spawn_bias: SpawnBias,
+    seed: Option<u64>,
+    eat_range: u32,
+    editor: Option<PathBuf>,
+    checkerboard: bool,
+    max_turns_per_second: Option<f32>,
+    boss_fruit: bool,
+    vision: Option<u32>,
+    batch_sprites: bool,
+    thickness: Option<u32>,
+    time_limit: Option<f32>,
+    drill: bool,
+    require_input: bool,
+    rooms: bool,
+    no_focus_pause: bool,
+    keys: bool,
+    no_pixel_snap: bool,
+    momentum_food: bool,
+    slow_decay: bool,
+    border_warning: bool,
+    co_op: Option<InputAggregation>,
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut args = Args::default();
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--broadcast" => args.broadcast = Some(Self::value(&arg, iter.next())),
+                "--http" => args.http = Some(Self::value(&arg, iter.next())),
+                "--bench" => args.bench = Some(Self::value(&arg, iter.next())),
+                "--bench-shift" => args.bench_shift = Some(Self::value(&arg, iter.next())),
+                "--feast" => args.feast = true,
+                "--truncate-tail" => args.truncate_tail = true,
+                "--wall-bounce" => args.wall_bounce = true,
+                "--wall-wrap" => args.wall_wrap = true,
+                "--grow-delay" => args.grow_delay = Self::value(&arg, iter.next()),
+                "--y-down" => args.y_axis_down = true,
+                "--segment-style" => args.segment_style = Self::value(&arg, iter.next()),
+                // the older name for --segment-style solid
+                "--solid-body" => args.segment_style = SegmentStyle::Solid,
+                "--center-start" => args.center_start = true,
+                "--spawn-pattern" => args.spawn_pattern = Self::value(&arg, iter.next()),
+                "--spawn-mode" => args.spawn_mode = Self::value(&arg, iter.next()),
+                "--instant-turn" => args.instant_turn = true,
+                "--difficulty" => args.difficulty = Self::value(&arg, iter.next()),
+                "--textured" => args.textured = true,
+                "--tick-indicator" => args.tick_indicator = true,
+                "--max-food" => args.max_food = Some(Self::value(&arg, iter.next())),
+                "--spawn-gauge" => args.spawn_gauge = true,
+                "--wild" => args.wild = true,
+                "--start-length" => args.start_length = Some(Self::value(&arg, iter.next())),
+                "--daily" => args.daily = true,
+                "--pixel-art" => args.pixel_art = true,
+                "--style" => args.style = Self::value(&arg, iter.next()),
+                "--wall-hug-penalty" => args.wall_hug_penalty = Self::value(&arg, iter.next()),
+                "--record" => args.record = Some(Self::value(&arg, iter.next())),
+                "--replay" => args.replay = Some(Self::value(&arg, iter.next())),
+                "--food-script" => args.food_script = Some(Self::value(&arg, iter.next())),
+                "--food-script-end" => args.food_script_end = Self::value(&arg, iter.next()),
+                "--fixed-length" => args.fixed_length = Some(Self::value(&arg, iter.next())),
+                "--adaptive" => args.adaptive = true,
+                "--debug" => args.debug = true,
+                "--border-walls" => args.border_walls = true,
+                "--cluster-chance" => args.cluster_chance = Self::value(&arg, iter.next()),
+                "--tutorial" => args.tutorial = true,
+                "--stretch" => args.stretch = Self::value(&arg, iter.next()),
+                "--fleeing-food" => args.fleeing_food = true,
+                "--momentum-food" => args.momentum_food = true,
+                "--slow-decay" => args.slow_decay = true,
+                "--no-render" => args.no_render = true,
+                "--steering" => args.steering = Self::value(&arg, iter.next()),
+                "--co-op" => args.co_op = Some(Self::value(&arg, iter.next())),
+                "--death-anim" => args.death_anim = Self::value(&arg, iter.next()),
+                "--spawn-bias" => args.spawn_bias = Self::value(&arg, iter.next()),
+                "--seed" => args.seed = Some(Self::value(&arg, iter.next())),
+                "--eat-range" => args.eat_range = Self::value(&arg, iter.next()),
+                "--editor" => args.editor = Some(Self::value(&arg, iter.next())),
+                "--checkerboard" => args.checkerboard = true,
+                "--border-warning" => args.border_warning = true,
+                "--max-turns-per-second" => args.max_turns_per_second = Some(Self::value(&arg, iter.next())),
+                "--boss-fruit" => args.boss_fruit = true,
+                "--vision" => args.vision = Some(Self::value(&arg, iter.next())),
+                "--batch-sprites" => args.batch_sprites = true,
+                "--thickness" => args.thickness = Some(Self::value(&arg, iter.next())),
+                "--time-limit" => args.time_limit = Some(Self::value(&arg, iter.next())),
+                "--drill" => args.drill = true,
+                "--require-input" => args.require_input = true,
+                "--rooms" => args.rooms = true,
+                "--no-focus-pause" => args.no_focus_pause = true,
+                "--no-pixel-snap" => args.no_pixel_snap = true,
+                // the doors are in the walls between the rooms
+                "--keys" => {
+                    args.keys = true;
+                    args.rooms = true;
+                }
+                _ => Self::fail(&format!("unknown argument {}", arg)),
+            }
+        }
+        if args.start_length.is_some_and(|length| length == 0 || length > HEIGHT as usize) {
+            Self::fail(&format!("--start-length has to be between 1 and {}", HEIGHT));
+        }
+        if args.fixed_length == Some(0) {
+            Self::fail("--fixed-length has to be at least 1");
+        }
+        if args.thickness.is_some_and(|thickness| thickness == 0 || thickness > MAX_THICKNESS) {
+            Self::fail(&format!("--thickness has to be between 1 and {}", MAX_THICKNESS));
+        }
+        // the middle of the board is where the walls between the rooms cross
+        if args.rooms && args.center_start {
+            Self::fail("--rooms and --center-start can't be used together");
+        }
+        if args.drill && args.food_script.is_some() {
+            Self::fail("--drill and --food-script can't be used together");
+        }
+        if args.wall_bounce && args.wall_wrap {
+            Self::fail("--wall-bounce and --wall-wrap can't be used together");
+        }
+        // a block wouldn't fit back onto the board wherever it went over the edge
+        if args.wall_wrap && args.thickness.is_some_and(|thickness| thickness > 1) {
+            Self::fail("--wall-wrap only works with a --thickness of 1");
+        }
+        if args.time_limit.is_some_and(|limit| limit <= 0.0) {
+            Self::fail("--time-limit has to be more than 0 seconds");
+        }
+        if args.max_turns_per_second.is_some_and(|max| max <= 0.0) {
+            Self::fail("--max-turns-per-second has to be more than 0");
+        }
+        if args.stretch < 0.0 {
+            Self::fail("--stretch can't be negative");
+        }
+        if args.wall_hug_penalty < 0.0 {
+            Self::fail("--wall-hug-penalty can't be negative");
+        }
+        if !(0.0..=1.0).contains(&args.cluster_chance) {
+            Self::fail("--cluster-chance has to be between 0 and 1");
+        }
+        // recordings only have the turns in them, not how long the keys were held
+        if args.require_input && (args.record.is_some() || args.replay.is_some()) {
+            Self::fail("--require-input can't be used with --record or --replay");
+        }
+        // recordings take the turns from the input, before the votes are counted
+        if args.co_op.is_some() && (args.record.is_some() || args.replay.is_some()) {
+            Self::fail("--co-op can't be used with --record or --replay");
+        }
+        if args.co_op.is_some() && args.steering == SteeringMode::Relative {
+            Self::fail("--co-op only works with --steering absolute");
+        }
+        if args.record.is_some() && args.replay.is_some() {
+            Self::fail("--record and --replay can't be used together");
+        }
+        if args.style == GameStyle::SnakeII && args.grow_delay != 0 {
+            Self::fail("--style snake2 has its own growth, it can't be used with --grow-delay");
+        }
+        if args.daily && args.replay.is_some() {
+            Self::fail("--daily and --replay can't be used together");
+        }
+        if args.seed.is_some() && (args.daily || args.replay.is_some()) {
+            Self::fail("--daily and --replay bring their own seed, they can't be used with --seed");
+        }
+        if args.daily && args.custom_rules() {
+            Self::fail("--daily always plays by the default rules");
+        }
+        args
+    }
+
+    fn lockstep(&self) -> bool {
+        self.record.is_some() || self.replay.is_some() || self.daily || self.seed.is_some()
+    }
+
+    // anything that changes how the game plays, as opposed to how it looks
+    fn custom_rules(&self) -> bool {
+        self.feast
+            || self.truncate_tail
+            || self.wall_bounce
+            || self.wall_wrap
+            || self.grow_delay != 0
+            || self.segment_style != SegmentStyle::default()
+            || self.center_start
+            || self.spawn_pattern != SpawnPattern::default()
+            || self.spawn_bias != SpawnBias::default()
+            || self.spawn_mode != SpawnMode::default()
+            || self.instant_turn
+            || self.difficulty != Difficulty::default()
+            || self.max_food.is_some()
+            || self.wild
+            || self.start_length.is_some()
+            || self.style != GameStyle::Classic
+            || self.wall_hug_penalty != 0.0
+            || self.food_script.is_some()
+            || self.fixed_length.is_some()
+            || self.adaptive
+            || self.debug
+            || self.cluster_chance != 0.0
+            || self.fleeing_food
+            || self.momentum_food
+            || self.slow_decay
+            || self.eat_range != 0
+            || self.max_turns_per_second.is_some()
+            || self.boss_fruit
+            || self.vision.is_some()
+            || self.thickness.is_some()
+            || self.time_limit.is_some()
+            || self.drill
+            || self.require_input
+            || self.rooms
+            || self.keys
+    }
+
+    fn value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+        match value.map(|v| v.parse()) {
+            Some(Ok(v)) => v,
+            _ => Self::fail(&format!("{} expects a valid value", flag)),
+        }
+    }
+
+    fn fail(message: &str) -> ! {
+        eprintln!("snek: {}", message);
+        std::process::exit(2)
+    }
+
+    // the game settings picked on the command line
+    fn insert_settings(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(FeastMode(self.feast))
+            .insert_resource(CenterStart(self.center_start))
+            .insert_resource(StartLength(self.start_length.unwrap_or(START_LENGTH)))
+            .insert_resource(self.spawn_pattern)
+            .insert_resource(self.spawn_bias)
+            .insert_resource(self.style)
+            // snake II has one fruit at a time, plus the occasional bonus. so do drills, one after the other.
+            .insert_resource(if self.style == GameStyle::SnakeII || self.drill { SpawnMode::OnDemand } else { self.spawn_mode })
+            .insert_resource(InstantTurn(self.instant_turn))
+            .insert_resource(self.difficulty)
+            .insert_resource(EatGrowDelay(self.grow_delay))
+            .insert_resource(YAxisDown(self.y_axis_down))
+            .insert_resource(self.steering)
+            .insert_resource(CoOp(self.co_op))
+            .insert_resource(MaxTurnsPerSecond(self.max_turns_per_second.unwrap_or(MAX_TURNS_PER_SECOND)))
+            .insert_resource(self.segment_style)
+            .insert_resource(MaxFood(self.max_food))
+            .insert_resource(FixedSnakeLength(self.fixed_length))
+            .insert_resource(AdaptiveMode(self.adaptive))
+            .insert_resource(ClusterChance(self.cluster_chance))
+            .insert_resource(WildMode(self.wild))
+            .insert_resource(FleeingFood(self.fleeing_food))
+            .insert_resource(MomentumFood(self.momentum_food))
+            .insert_resource(SlowDecay(self.slow_decay))
+            .insert_resource(EatRange(self.eat_range))
+            .insert_resource(BossFruits(self.boss_fruit))
+            .insert_resource(VisionRadius(self.vision))
+            .insert_resource(Thickness(self.thickness.unwrap_or(1)))
+            .insert_resource(TimeLimit(self.time_limit))
+            .insert_resource(RequireInput(self.require_input))
+            .insert_resource(Rooms(self.rooms))
+            .insert_resource(keys::KeyDoors(self.keys))
+            .insert_resource(WallHugPenalty(self.wall_hug_penalty))
+            .insert_resource(if self.truncate_tail { TailCollisionMode::Truncate } else { TailCollisionMode::Die })
+            .insert_resource(if self.wall_bounce {
+                WallMode::Bounce
+            } else if self.wall_wrap {
+                WallMode::Wrap
+            } else {
+                WallMode::Die
+            });
+    }
+}
+
+// everything that makes up the game itself, without windows, input or rendering.
+// the normal game and the headless benchmark both build on this.
+struct SnekPlugin;
+
+impl Plugin for SnekPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(GridDimensions::default())
+            .insert_resource(SnekSegments::default())
+            .insert_resource(Occupied::default())
+            .insert_resource(PathHistory::default())
+            .insert_resource(PendingGrowth::default())
+            .insert_resource(RivalTiles::default())
+            .insert_resource(RoomWalls::default())
+            .insert_resource(keys::Objective::default())
+            .insert_resource(keys::Cleared::default())
+            .insert_resource(BossTiles::default())
+            .insert_resource(LastTailPosition::default())
+            .insert_resource(FrameDelta::default())
+            // apply_difficulty sets up the actual durations
+            .insert_resource(MovementTimer(Timer::new(movement_interval(START_LENGTH, Difficulty::Normal, 1.0), true)))
+            .insert_resource(AdaptiveMode(false))
+            .insert_resource(AdaptiveDifficulty::default())
+            .insert_resource(ClusterProgress::default())
+            .insert_resource(SpawnTimer(Timer::from_seconds(1.0 / SPAWN_RATE, true)))
+            .insert_resource(SpeedFreeze::default())
+            .insert_resource(DirectionLock::default())
+            .insert_resource(ScoreMultiplier::default())
+            .insert_resource(Score::default())
+            .insert_resource(ShuffleCharges(SHUFFLE_CHARGES))
+            .insert_resource(PanicCharges(PANIC_CHARGES))
+            .insert_resource(Lives(START_LIVES))
+            .insert_resource(Stopwatch::default())
+            .insert_resource(SteerKeyHeld::default())
+            .insert_resource(TurnVotes::default())
+            .insert_resource(Invulnerability::default())
+            .insert_resource(TickCount::default())
+            .insert_resource(growth_chart::LengthSamples::default())
+            .insert_resource(Lockstep(false))
+            // we need a new stage here, since the material used here is created in the setup system.
+            // "single" means that there's only one system called in this stage. there are other options like "serial" or "parallel"
+            .add_startup_stage("game_setup", SystemStage::single(spawn_snek.system()))
+            .add_startup_system_to_stage("game_setup", spawn_obstacle.system())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(movement_timestep.system())
+                    .with_system(
+                        step_obstacles.system()
+                            .label(SnekMovement::Obstacles)
+                            .before(SnekMovement::Movement)
+                    )
+                    .with_system(
+                        rival_movement.system()
+                            .label(SnekMovement::Rivals)
+                            .after(SnekMovement::Obstacles)
+                            .before(SnekMovement::Movement)
+                    )
+                    .with_system(
+                        replay::record_turns.system()
+                            .label(SnekMovement::Recording)
+                            .before(SnekMovement::Movement)
+                    )
+                    .with_system(replay::replay_turns.system().before(SnekMovement::Movement))
+                    .with_system(snek_movement.system().label(SnekMovement::Movement)) // we label this system "movement"
+                    .with_system(
+                        snek_eating.system()
+                            .label(SnekMovement::Eating)
+                            .after(SnekMovement::Movement)
+                    )
+                    .with_system(
+                        snek_warp.system()
+                            .label(SnekMovement::Warp)
+                            .after(SnekMovement::Eating)
+                    )
+                    .with_system(
+                        snek_split.system()
+                            .label(SnekMovement::Split)
+                            .after(SnekMovement::Warp)
+                    )
+                    .with_system(
+                        snek_reverse.system()
+                            .label(SnekMovement::Reverse)
+                            .after(SnekMovement::Split)
+                    )
+                    .with_system(
+                        snek_growth.system()
+                            .label(SnekMovement::Growth)
+                            .after(SnekMovement::Reverse)
+                    )
+                    .with_system(broadcast::broadcast_state.system().after(SnekMovement::Growth))
+                    .with_system(http::publish_state.system().after(SnekMovement::Growth))
+                    .with_system(wall_hug_penalty.system().after(SnekMovement::Growth))
+                    .with_system(detect_near_miss.system().after(SnekMovement::Growth))
+                    .with_system(record_path.system().after(SnekMovement::Movement))
+                    .with_system(announce_tick.system().after(SnekMovement::Movement))
+                    .with_system(
+                        growth_chart::sample.system()
+                            .after(SnekMovement::Movement)
+                            .before(SnekMovement::GameOver)
+                    )
+                    .with_system(count_ticks.system().after(SnekMovement::Growth))
+                    .with_system(flee_from_head.system().after(SnekMovement::Growth))
+                    .with_system(
+                        hit_boss.system()
+                            .after(SnekMovement::Eating)
+                            .before(SnekMovement::Growth)
+                    )
+                    .with_system(spawn_boss.system().after(SnekMovement::Growth))
+            )
+            .add_system(
+                game_over.system()
+                    .label(SnekMovement::GameOver)
+                    .after(SnekMovement::Movement)
+                    .before(SnekMovement::Eating)
+            )
+            .add_system(
+                food_script::restart.system()
+                    .after(SnekMovement::Movement)
+                    .before(SnekMovement::GameOver)
+            )
+            .add_system(
+                drill::progress.system()
+                    .after(SnekMovement::Movement)
+                    .before(SnekMovement::GameOver)
+            )
+            .add_system(speed_freeze_expiry.system())
+            .add_system(direction_lock_expiry.system().before(SnekMovement::Movement))
+            .add_system(clear_path_history.system())
+            .add_system(clear_rivals.system().after(SnekMovement::GameOver))
+            .add_system(clear_boss.system().after(SnekMovement::GameOver))
+            .add_system(
+                time_limit.system()
+                    .after(SnekMovement::Movement)
+                    .before(SnekMovement::GameOver)
+            )
+            .add_system(
+                growth_chart::end_game.system()
+                    .after(SnekMovement::Movement)
+                    .before(SnekMovement::GameOver)
+            )
+            .add_system(
+                keys::win.system()
+                    .after(SnekMovement::Movement)
+                    .before(SnekMovement::GameOver)
+            )
+            .add_system(
+                keys::fit.system()
+                    .after(SnekMovement::GameOver)
+                    .before(SnekMovement::Eating)
+            )
+            .add_system(score_multiplier_expiry.system())
+            .add_system(cluster_window.system())
+            .add_system(
+                clear_pending_growth.system()
+                    .after(SnekMovement::GameOver)
+                    .before(SnekMovement::Growth)
+            )
+            .add_system(slow_decay.system())
+            .add_system(expire_food.system())
+            .add_system(invulnerability_expiry.system())
+            .add_system(
+                adapt_difficulty.system()
+                    .after(SnekMovement::Movement)
+                    .before(SnekMovement::GameOver)
+            )
+            .add_system(apply_difficulty.system())
+            .add_system(fit_obstacles.system().before(SnekMovement::Obstacles))
+            .add_system(fit_rooms.system().before(SnekMovement::Movement))
+            .add_system_set(
+                SystemSet::new()
+                    // food should only spawn every second.
+                    .with_run_criteria(food_spawn_timestep.system())
+                    // both use GameRng, a fixed order keeps seeded games reproducible
+                    .with_system(food_spawner.system().after(SnekMovement::Growth))
+            )
+            .add_event::<GrowthEvent>()
+            .add_event::<BossBumpEvent>()
+            .add_event::<TimeUpEvent>()
+            .add_event::<keys::GameWonEvent>()
+            .add_event::<ReverseEvent>()
+            .add_event::<WarpEvent>()
+            .add_event::<SplitEvent>()
+            .add_event::<GameOverEvent>()
+            .add_event::<FoodEatenEvent>()
+            .add_event::<MovementTickEvent>()
+            .add_event::<NearMissEvent>();
+    }
+}
+
 fn main() {
-    let movement: f64 = 1.0 / MOVEMENT_RATE;
-    let fruits: f64 = 1.0 / SPAWN_RATE;
+    let args = Args::parse();
+    if let Some(ticks) = args.bench {
+        bench::run(&args, ticks);
+        return;
+    }
+    if let Some(length) = args.bench_shift {
+        bench::run_shift(length);
+        return;
+    }
+    if let Some(path) = &args.editor {
+        editor::run(path);
+        return;
+    }
+
+    let mut app = App::build();
+    // the daily challenge: everyone playing on the same day gets the same fruit
+    let today = if args.daily { Some(leaderboard::Date::today()) } else { None };
+    let mut seed = args.seed.unwrap_or_else(|| today.map_or_else(rand::random, |day| day.seed()));
+    if let Some(path) = &args.replay {
+        match replay::load(path) {
+            Ok((recorded_seed, replay)) => {
+                seed = recorded_seed;
+                app.insert_resource(replay);
+            }
+            Err(e) => Args::fail(&format!("could not load {}: {}", path.display(), e)),
+        }
+    }
+    if let Some(path) = &args.food_script {
+        match food_script::load(path, args.food_script_end) {
+            Ok(script) => {
+                app.insert_resource(script);
+            }
+            Err(e) => Args::fail(&format!("could not load {}: {}", path.display(), e)),
+        }
+    }
+    if args.drill {
+        let drill = drill::Drill::new(GridDimensions::default());
+        app.insert_resource(drill.script());
+        app.insert_resource(drill);
+    }
+    if let Some(path) = &args.record {
+        match replay::Recorder::create(path, seed) {
+            Ok(recorder) => {
+                app.insert_resource(recorder);
+            }
+            Err(e) => Args::fail(&format!("could not record to {}: {}", path.display(), e)),
+        }
+    }
+    // replays and headless runs don't count towards the leaderboard or the stats
+    if args.replay.is_none() && !args.no_render {
+        match leaderboard::Leaderboard::load(today) {
+            Ok(leaderboard) => {
+                app.insert_resource(leaderboard);
+            }
+            Err(e) => eprintln!("snek: could not load the leaderboard: {}", e),
+        }
+        match stats::Stats::load() {
+            Ok(stats) => {
+                app.insert_resource(stats);
+            }
+            Err(e) => eprintln!("snek: could not load the stats: {}", e),
+        }
+        if let Some(tutorial) = tutorial::Tutorial::load(args.tutorial) {
+            app.insert_resource(tutorial);
+        }
+    }
+    if let Some(port) = args.broadcast {
+        match broadcast::start(port) {
+            Ok(broadcast) => {
+                app.insert_resource(broadcast);
+            }
+            Err(e) => eprintln!("snek: could not broadcast on port {}: {}", port, e),
+        }
+    }
+    if let Some(port) = args.http {
+        match http::start(port) {
+            Ok(http) => {
+                app.insert_resource(http);
+            }
+            Err(e) => eprintln!("snek: could not serve the state on port {}: {}", port, e),
+        }
+    }
+    if args.no_render {
+        headless(app, &args, seed).run();
+        return;
+    }
+    if args.death_anim == DeathAnimation::Dissolve {
+        app.insert_resource(DeathAnim::default());
+    }
 
-    App::build()
+    app
         .insert_resource(WindowDescriptor {
-            title: "Snek".to_string(),
+            title: TITLE.to_string(),
             width: 1000.0,
             height: 1000.0,
             ..Default::default()
         })
-        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
-        .insert_resource(SnekSegments::default())
-        .insert_resource(LastTailPosition::default())
+        .insert_resource(ClearColor(BACKGROUND_COLORS[0]))
+        .insert_resource(GameRng(StdRng::seed_from_u64(seed)))
+        .insert_resource(Seed(seed))
+        .insert_resource(ShowPath(false))
+        .insert_resource(ShowTailHint(false))
+        .insert_resource(ShowHitboxes(false))
+        .insert_resource(Combo::default())
+        .insert_resource(BorderFlash::default())
+        .insert_resource(CapacityWarning::default())
+        .insert_resource(QuitConfirm::default())
+        .insert_resource(Transition::default())
+        .insert_resource(Paused(false))
+        .insert_resource(GridResize::default())
+        .insert_resource(ShowStats(false))
+        .insert_resource(instant_replay::InstantReplay::default())
+        .insert_resource(SlowMotion::default())
+        .insert_resource(Textured(args.textured))
+        .insert_resource(PixelArt(args.pixel_art))
+        // read once when the render pipelines are built, so it has to be in place before the plugins
+        .insert_resource(Msaa { samples: if args.pixel_art { 1 } else { 4 } })
+        .insert_resource(ShowTickIndicator(args.tick_indicator))
+        .insert_resource(ShowSpawnGauge(args.spawn_gauge))
+        .insert_resource(DebugKeys(args.debug))
+        .insert_resource(BorderWalls(args.border_walls))
+        .insert_resource(Checkerboard(args.checkerboard))
+        .insert_resource(ShowBorderWarning(args.border_warning))
+        .insert_resource(StretchFactor(args.stretch))
+        .insert_resource(BatchSprites(args.batch_sprites))
+        .insert_resource(FocusPause(!args.no_focus_pause))
+        .insert_resource(PixelSnap(!args.no_pixel_snap))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SnekPlugin)
         .add_startup_system(setup.system())
-        // we need a new stage here, since the material used here is created in the setup system.
-        // "single" means that there's only one system called in this stage. there are other options like "serial" or "parallel"
-        .add_startup_stage("game_setup", SystemStage::single(spawn_snek.system()))
+        // exclusive systems like the time system run first, so Time is already up to date here
+        .add_system_to_stage(CoreStage::First, frame_delta.system())
         .add_system(
             snek_movement_input.system()
                 .label(SnekMovement::Input)
                 .before(SnekMovement::Movement) // we make sure that we get the input before moving the snek
+                .before(SnekMovement::Recording)
         )
-        .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(movement))
-                .with_system(snek_movement.system().label(SnekMovement::Movement)) // we label this system "movement"
-                .with_system(
-                    snek_eating.system()
-                        .label(SnekMovement::Eating)
-                        .after(SnekMovement::Movement)
-                )
-                .with_system(
-                    snek_growth.system()
-                        .label(SnekMovement::Growth)
-                        .after(SnekMovement::Eating)
-                )
+        .add_system(food_shuffle.system())
+        .add_system(panic_button.system())
+        .add_system(panic_charges_text.system())
+        .add_system(toggle_pause.system())
+        .add_system(pause_on_focus_loss.system())
+        .add_system(paused_text.system())
+        .add_system(grid_resize_menu.system())
+        .add_system(stats_screen.system())
+        .add_system(stats::track_play.system())
+        .add_system(tutorial::progress.system())
+        .add_system(tutorial::hint_text.system())
+        .add_system(main_camera.system())
+        .add_system(toggle_path.system())
+        .add_system(toggle_tail_hint.system())
+        .add_system(fit_border_walls.system())
+        .add_system(fit_checkerboard.system())
+        .add_system(fit_border_warning.system())
+        .add_system(fit_fog.system())
+        .add_system(fog_of_war.system())
+        .add_system(teleport_to_mouse.system().before(SnekMovement::Movement))
+        .add_system(grow_on_key.system())
+        .add_system(toggle_hitboxes.system())
+        .add_system(tail_hint.system())
+        .add_system(growth_preview.system())
+        .add_system(target_food_indicator.system())
+        .add_system(quit.system())
+        .add_system(fade_transition.system())
+        .add_system(
+            fade_in_after_game_over.system()
+                .after(SnekMovement::Movement)
+                .before(SnekMovement::GameOver)
+        )
+        .add_system(spawn_floating_scores.system())
+        .add_system(floating_scores.system())
+        .add_system(count_combo.system())
+        .add_system(combo_flame.system())
+        .add_system(invulnerability_flash.system())
+        .add_system(dissolve.system())
+        .add_system(capacity_warning.system())
+        .add_system(border_flash.system())
+        .add_system(lives_text.system())
+        .add_system(time_limit_text.system())
+        .add_system(drill::drill_text.system())
+        .add_system(keys::keys_text.system())
+        .add_system(seed_text.system())
+        .add_system(cycle_difficulty.system())
+        .add_system(toggle_segment_style.system())
+        .add_system(difficulty_text.system())
+        .add_system(shuffle_charges_text.system())
+        .add_system(score_multiplier_display.system())
+        .add_system(spawn_gauge.system())
+        .add_system(background_system.system())
+        .add_system(window_title.system())
+        .add_system(texture_filtering.system())
+        .add_system(tick_indicator.system())
+        .add_system(
+            leaderboard::record_final_score.system()
+                .after(SnekMovement::Movement)
+                .before(SnekMovement::GameOver)
+        )
+        .add_system(
+            stats::record_game.system()
+                .after(SnekMovement::Movement)
+                .before(SnekMovement::GameOver)
+        )
+        .add_system(
+            instant_replay::record.system()
+                .after(SnekMovement::Movement)
+                .before(SnekMovement::GameOver)
         )
-        .add_system(game_over.system().after(SnekMovement::Movement))
+        .add_system(instant_replay::play.system())
+        .add_system(growth_chart::draw.system())
         .add_system_set_to_stage(
             CoreStage::PostUpdate,
             SystemSet::new()
                 .with_system(position_translation.system())
-                .with_system(size_scaling.system()),
-        )
-        .add_system_set(
-            SystemSet::new()
-                // food should only spawn every second.
-                .with_run_criteria(FixedTimestep::step(fruits))
-                .with_system(food_spawner.system())
-        )
-        .add_event::<GrowthEvent>()
-        .add_event::<GameOverEvent>()
-        .add_plugins(DefaultPlugins)
-        .run();
+                .with_system(size_scaling.system())
+                .with_system(segment_textures.system())
+                .with_system(draw_path.system())
+                .with_system(draw_hitboxes.system())
+                .with_system(corner_fills.system()),
+        );
+    args.insert_settings(&mut app);
+    // inserted after the plugin, which starts out without lockstep.
+    // the daily challenge and --seed use it too, so the fruit only depends on the seed and the turns taken.
+    app.insert_resource(Lockstep(args.lockstep()));
+    app.run();
 }
 
-fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+// --no-render: just the game, without a window, input or anything drawn. nobody steers, so it is
+// meant for replays and for watching the game through --broadcast or --http.
+fn headless(mut app: AppBuilder, args: &Args, seed: u64) -> App {
+    app
+        // without a window nothing paces the frames, so they are spaced out like the screen would
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / 60.0)))
+        .insert_resource(GameRng(StdRng::seed_from_u64(seed)))
+        // the default (empty) handles, like the benchmark
+        .init_resource::<Materials>()
+        // frame_delta reads these, they just never change without the systems that go with them
+        .insert_resource(Paused(false))
+        .insert_resource(Transition::default())
+        .insert_resource(instant_replay::InstantReplay::default())
+        .insert_resource(SlowMotion::default())
+        .add_plugins(MinimalPlugins)
+        .add_plugin(SnekPlugin)
+        .add_system_to_stage(CoreStage::First, frame_delta.system());
+    args.insert_settings(&mut app);
+    app.insert_resource(Lockstep(args.lockstep()));
+    app.app
+}
+
+fn setup(mut commands: Commands,
+         mut materials: ResMut<Assets<ColorMaterial>>,
+         asset_server: Res<AssetServer>,
+         textured: Res<Textured>,
+         show_tick_indicator: Res<ShowTickIndicator>,
+         show_spawn_gauge: Res<ShowSpawnGauge>,
+         segment_style: Res<SegmentStyle>,
+) {
     // A new 2d camera is created.
     // We can use a camera bundle for this, which spawns a new camera entity
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
+    // text is drawn by a separate ui camera
+    commands.spawn_bundle(UiCameraBundle::default());
+    let font: Handle<Font> = asset_server.load("fonts/DejaVuSans.ttf");
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Px(5.0), left: Val::Px(10.0), ..Default::default() },
+        Color::hex("B2B2B2").unwrap(),
+    ))
+        .insert(ShuffleChargesText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Px(65.0), right: Val::Px(10.0), ..Default::default() },
+        Color::hex("B2B2B2").unwrap(),
+    ))
+        .insert(PanicChargesText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Px(35.0), left: Val::Px(10.0), ..Default::default() },
+        Color::hex("FF9F40").unwrap(),
+    ))
+        .insert(MultiplierText);
+    commands.spawn_bundle(hud_bar(
+        Rect { top: Val::Px(65.0), left: Val::Px(10.0), ..Default::default() },
+        materials.add(Color::hex("FF9F40").unwrap().into()),
+    ))
+        .insert(MultiplierBar);
+    if show_spawn_gauge.0 {
+        commands.spawn_bundle(hud_text(
+            font.clone(),
+            Rect { bottom: Val::Px(45.0), right: Val::Px(10.0), ..Default::default() },
+            Color::hex("ABFF8B").unwrap(),
+        ))
+            .insert(SpawnGaugeText);
+        commands.spawn_bundle(hud_bar(
+            Rect { bottom: Val::Px(35.0), right: Val::Px(10.0), ..Default::default() },
+            materials.add(Color::hex("ABFF8B").unwrap().into()),
+        ))
+            .insert(SpawnGaugeBar);
+    }
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Px(5.0), right: Val::Px(10.0), ..Default::default() },
+        Color::hex("B2B2B2").unwrap(),
+    ))
+        .insert(LivesText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Px(35.0), right: Val::Px(10.0), ..Default::default() },
+        Color::hex("B2B2B2").unwrap(),
+    ))
+        .insert(DifficultyText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Px(5.0), left: Val::Percent(45.0), ..Default::default() },
+        Color::hex("EFEFEF").unwrap(),
+    ))
+        .insert(TimeLimitText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Px(95.0), right: Val::Px(10.0), ..Default::default() },
+        Color::hex("B2B2B2").unwrap(),
+    ))
+        .insert(DrillText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Px(125.0), right: Val::Px(10.0), ..Default::default() },
+        Color::hex("B2B2B2").unwrap(),
+    ))
+        .insert(KeysText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { bottom: Val::Px(65.0), left: Val::Px(10.0), ..Default::default() },
+        Color::hex("B2B2B2").unwrap(),
+    ))
+        .insert(SeedText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { bottom: Val::Px(5.0), left: Val::Px(10.0), ..Default::default() },
+        Color::hex("EFEFEF").unwrap(),
+    ))
+        .insert(QuitPromptText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { bottom: Val::Px(35.0), left: Val::Px(10.0), ..Default::default() },
+        Color::hex("EFEFEF").unwrap(),
+    ))
+        .insert(PausedText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Percent(30.0), left: Val::Percent(30.0), ..Default::default() },
+        Color::hex("EFEFEF").unwrap(),
+    ))
+        .insert(StatsText);
+    commands.spawn_bundle(hud_text(
+        font.clone(),
+        Rect { top: Val::Percent(12.0), left: Val::Percent(30.0), ..Default::default() },
+        Color::hex("FFD24D").unwrap(),
+    ))
+        .insert(TutorialText);
+    commands.spawn_bundle(hud_text(
+        font,
+        Rect { top: Val::Percent(5.0), left: Val::Percent(35.0), ..Default::default() },
+        Color::hex("EFEFEF").unwrap(),
+    ))
+        .insert(InstantReplayText);
+    if show_tick_indicator.0 {
+        commands.spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { bottom: Val::Px(12.0), right: Val::Px(12.0), ..Default::default() },
+                size: bevy::math::Size::new(Val::Px(16.0), Val::Px(16.0)),
+                ..Default::default()
+            },
+            // every indicator gets its own material, since tick_indicator fades it
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+            .insert(TickIndicator { timer: Timer::from_seconds(0.0, false) });
+    }
+    commands.spawn_bundle(SpriteBundle {
+        material: materials.add(Color::rgba(0.5, 1.0, 0.6, 0.2).into()),
+        visible: Visible {
+            is_visible: false,
+            is_transparent: true,
+        },
+        ..Default::default()
+    })
+        .insert(Position { x: 0, y: 0 })
+        .insert(Size::square(1.0))
+        .insert(TailHint);
+    commands.spawn_bundle(SpriteBundle {
+        material: materials.add(Color::rgba(0.7, 0.7, 0.7, 0.35).into()),
+        visible: Visible {
+            is_visible: false,
+            is_transparent: true,
+        },
+        ..Default::default()
+    })
+        .insert(Position { x: 0, y: 0 })
+        .insert(segment_style.segment_size())
+        .insert(GrowthPreview);
+    // four thin strips along the edges of the window, sharing one material that border_flash fades
+    let border_material = materials.add(Color::NONE.into());
+    let (full, width) = (Val::Percent(100.0), Val::Px(BORDER_FLASH_WIDTH));
+    let strips = [
+        (Rect { top: Val::Px(0.0), left: Val::Px(0.0), ..Default::default() }, full, width),
+        (Rect { bottom: Val::Px(0.0), left: Val::Px(0.0), ..Default::default() }, full, width),
+        (Rect { top: Val::Px(0.0), left: Val::Px(0.0), ..Default::default() }, width, full),
+        (Rect { top: Val::Px(0.0), right: Val::Px(0.0), ..Default::default() }, width, full),
+    ];
+    for (position, strip_width, strip_height) in strips.iter() {
+        commands.spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: *position,
+                size: bevy::math::Size::new(*strip_width, *strip_height),
+                ..Default::default()
+            },
+            material: border_material.clone(),
+            ..Default::default()
+        })
+            .insert(BorderFlashStrip);
+    }
+    // spawned last so it covers the board and the rest of the HUD
+    commands.spawn_bundle(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            size: bevy::math::Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            ..Default::default()
+        },
+        material: materials.add(Color::NONE.into()),
+        ..Default::default()
+    })
+        .insert(FadeOverlay);
+    if textured.0 {
+        let images = [
+            asset_server.load("textures/snek_head.png"),
+            asset_server.load("textures/snek_body.png"),
+            asset_server.load("textures/snek_tail.png"),
+        ];
+        commands.insert_resource(SnekTextures {
+            head: materials.add(images[0].clone().into()),
+            body: materials.add(images[1].clone().into()),
+            tail: materials.add(images[2].clone().into()),
+            images,
+        });
+    }
     commands.insert_resource(Materials {
         // create a new materials struct. add() - method returns a handle like it is defined in the mat. struct.
         head_material: materials.add(Color::hex("EFEFEF").unwrap().into()),
         food_material: materials.add(Color::hex("ABFF8B").unwrap().into()),
+        freeze_food_material: materials.add(Color::hex("8BD9FF").unwrap().into()),
+        reverse_food_material: materials.add(Color::hex("FF8BD9").unwrap().into()),
+        warp_food_material: materials.add(Color::hex("B58BFF").unwrap().into()),
+        split_food_material: materials.add(Color::hex("7DFFE0").unwrap().into()),
+        fleeing_food_material: materials.add(Color::hex("D9FF4D").unwrap().into()),
+        momentum_food_material: materials.add(Color::hex("FF8C1A").unwrap().into()),
+        rival_material: materials.add(Color::hex("B24B4B").unwrap().into()),
+        boss_materials: [
+            materials.add(Color::hex("F0C9FF").unwrap().into()),
+            materials.add(Color::hex("D88BFF").unwrap().into()),
+            materials.add(Color::hex("B84DFF").unwrap().into()),
+        ],
+        target_food_materials: [
+            materials.add(Color::hex("4DFF88").unwrap().into()),
+            materials.add(Color::hex("FFD24D").unwrap().into()),
+            materials.add(Color::hex("FF7A4D").unwrap().into()),
+        ],
+        target_ring_material: materials.add(Color::rgba(1.0, 0.82, 0.3, 0.25).into()),
+        double_score_food_material: materials.add(Color::hex("FF9F40").unwrap().into()),
+        cluster_food_material: materials.add(Color::hex("6BE06B").unwrap().into()),
         segment_material: materials.add(Color::hex("B2B2B2").unwrap().into()),
+        obstacle_material: materials.add(Color::hex("FF5C5C").unwrap().into()),
+        // see-through, so the background still changes color underneath
+        checker_materials: [
+            materials.add(Color::rgba(1.0, 1.0, 1.0, 0.02).into()),
+            materials.add(Color::rgba(1.0, 1.0, 1.0, 0.05).into()),
+        ],
+        fog_material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.92).into()),
+        border_warning_material: materials.add(Color::rgba(1.0, 0.36, 0.36, 0.12).into()),
+        key_material: materials.add(Color::hex("FFD700").unwrap().into()),
+        door_material: materials.add(Color::hex("A0522D").unwrap().into()),
+        goal_material: materials.add(Color::hex("3CB371").unwrap().into()),
+        hitbox_material: materials.add(Color::hex("39FF14").unwrap().into()),
+        // newest first, fading out towards the oldest part of the path
+        path_materials: (0..PATH_HISTORY_LENGTH)
+            .map(|age| {
+                let alpha = 0.4 * (1.0 - age as f32 / PATH_HISTORY_LENGTH as f32);
+                materials.add(Color::rgba(1.0, 0.9, 0.5, alpha).into())
+            })
+            .collect(),
     })
 }
 
+// an empty line of text pinned to the given spot of the window. systems fill in the text later.
+fn hud_text(font: Handle<Font>, position: Rect<Val>, color: Color) -> TextBundle {
+    TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position,
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 24.0,
+                color,
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    }
+}
+
+// an empty bar pinned to the given spot of the window. systems set its width later.
+fn hud_bar(position: Rect<Val>, material: Handle<ColorMaterial>) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position,
+            size: bevy::math::Size::new(Val::Px(0.0), Val::Px(6.0)),
+            ..Default::default()
+        },
+        material,
+        ..Default::default()
+    }
+}
+
 // this system looks for a resource of struct "Materials" which we created
-fn spawn_snek(mut commands: Commands, materials: Res<Materials>, mut segments: ResMut<SnekSegments>) {
+#[allow(clippy::too_many_arguments)]
+fn spawn_snek(mut commands: Commands,
+              materials: Res<Materials>,
+              mut segments: ResMut<SnekSegments>,
+              mut occupied: ResMut<Occupied>,
+              center_start: Res<CenterStart>,
+              segment_style: Res<SegmentStyle>,
+              start_length: Res<StartLength>,
+              (grid, thickness): (Res<GridDimensions>, Res<Thickness>),
+) {
+    // a board made smaller in the pause menu might not fit the whole snek anymore
+    let k = thickness.0 as i32;
+    let length = start_length.0.min((grid.height / thickness.0).max(1) as usize);
+    // the body is as long in tiles as that many thin segments plus the blocks above the bottom one
+    let head_position = start_position(center_start.0, (length - 1) * k as usize + 1, *grid);
+    // the body trails straight down from the head
+    let body_positions = (1..length as i32)
+        .map(|i| Position {
+            x: head_position.x,
+            y: head_position.y - i * k,
+        })
+        .collect::<Vec<Position>>();
+    occupied.0 = body_positions.iter().copied().collect();
+    occupied.0.insert(head_position);
     // we spawn a sprite
     println!("{:?}", segments.0);
     segments.0 = vec![
@@ -110,243 +1287,6004 @@ fn spawn_snek(mut commands: Commands, materials: Res<Materials>, mut segments: R
                 direction: Direction::Up,
                 next_direction: Direction::Up
             }) // we insert SnekHead as a component into this new snek-entity
-            .insert(Position {
-                x: 3,
-                y: 3,
-            })
+            .insert(head_position)
             .insert(Size::square(0.8))
             .insert(SnekSegment)
             .id(),
-        spawn_segment(commands,
-                      &materials.segment_material,
-                      Position {
-                          x: 3,
-                          y: 2,
-                      }),
     ];
+    for position in body_positions {
+        segments.0.push(spawn_segment(&mut commands, &materials.segment_material, position, *segment_style));
+    }
 }
 
-fn size_scaling(windows: Res<Windows>, mut query: Query<(&Size, &mut Sprite)>) {
-    let window = windows.get_primary().unwrap();
-    for (sprite_size, mut sprite) in query.iter_mut() {
-        sprite.size = Vec2::new(
-            sprite_size.width / WIDTH as f32 * window.width() as f32,
-            sprite_size.height / HEIGHT as f32 * window.height() as f32,
-        )
-    }
+fn spawn_obstacle(mut commands: Commands, materials: Res<Materials>, grid: Res<GridDimensions>) {
+    let obstacle = Obstacle {
+        path: obstacle_path(*grid),
+        index: 0,
+    };
+    commands.spawn_bundle(SpriteBundle {
+        material: materials.obstacle_material.clone(),
+        ..Default::default()
+    })
+        .insert(obstacle.position())
+        .insert(obstacle)
+        .insert(Size::square(0.9));
+}
+
+// there and back again along its row, without standing still at either end
+fn obstacle_path(grid: GridDimensions) -> Vec<Position> {
+    let (from, to) = (OBSTACLE_MARGIN, grid.width as i32 - 1 - OBSTACLE_MARGIN);
+    let y = grid.height as i32 - 1 - OBSTACLE_ROW_FROM_TOP;
+    (from..=to)
+        .chain((from + 1..to).rev())
+        .map(|x| Position { x, y })
+        .collect()
 }
 
-fn position_translation(windows: Res<Windows>, mut q: Query<(&Position, &mut Transform)>) {
-    fn convert(pos: f32, bound_window: f32, bound_game: f32) -> f32 {
-        let tile_size = bound_window / bound_game;
-        pos / bound_game * bound_window  // translate the tile position to pixel position
-            - (bound_window / 2.0)  // coordinate 0:0 is right in the middle of the screen. so we subtract half the screen
-            + (tile_size / 2.0) // then add half a tile because the tile also has 0:0 in the center
+// --rooms: the walls between the rooms go up again for a resized board. also runs at startup.
+fn fit_rooms(
+    mut commands: Commands,
+    rooms: Res<Rooms>,
+    grid: Res<GridDimensions>,
+    materials: Res<Materials>,
+    mut room_walls: ResMut<RoomWalls>,
+    walls: Query<Entity, With<RoomWall>>,
+) {
+    if !rooms.is_changed() && !grid.is_changed() {
+        return;
+    }
+    for wall in walls.iter() {
+        commands.entity(wall).despawn();
+    }
+    room_walls.0 = if rooms.0 { room_layout(*grid) } else { HashSet::new() };
+    for position in room_walls.0.iter() {
+        commands.spawn_bundle(SpriteBundle {
+            material: materials.obstacle_material.clone(),
+            ..Default::default()
+        })
+            .insert(RoomWall)
+            .insert(*position)
+            .insert(Size::square(1.0));
     }
+}
 
-    let window = windows.get_primary().unwrap();
-    for (pos, mut transform) in q.iter_mut() {
-        transform.translation = Vec3::new(
-            convert(pos.x as f32, window.width(), WIDTH as f32),
-            convert(pos.y as f32, window.height(), HEIGHT as f32),
-            0.0,
-        )
+// four rooms, one in each quarter of the board. the walls between them run through the middle,
+// with a doorway halfway along the wall between every two rooms that are next to each other.
+fn room_layout(grid: GridDimensions) -> HashSet<Position> {
+    if grid.width < MIN_ROOMS_BOARD || grid.height < MIN_ROOMS_BOARD {
+        return HashSet::new();
     }
+    let (width, height) = (grid.width as i32, grid.height as i32);
+    let (middle_x, middle_y) = (width / 2, height / 2);
+    let in_door = |along: i32, length: i32| {
+        let doors = [length / 4, length * 3 / 4];
+        doors.iter().any(|door| along > door - DOOR_WIDTH && along <= *door)
+    };
+    let column = (0..height).filter(|y| *y == middle_y || !in_door(*y, height)).map(|y| Position { x: middle_x, y });
+    let row = (0..width).filter(|x| *x == middle_x || !in_door(*x, width)).map(|x| Position { x, y: middle_y });
+    column.chain(row).collect()
 }
 
-fn snek_movement(segments: ResMut<SnekSegments>,
-                 mut heads: Query<(Entity, &mut SnekHead)>,
-                 mut positions: Query<&mut Position>,
-                 mut last_tail_position: ResMut<LastTailPosition>,
-                 mut game_over_writer: EventWriter<GameOverEvent>,
+// the border walls are put back up around a resized board
+fn fit_border_walls(
+    mut commands: Commands,
+    border_walls: Res<BorderWalls>,
+    grid: Res<GridDimensions>,
+    materials: Res<Materials>,
+    walls: Query<Entity, With<Wall>>,
 ) {
-    if let Some((head_entity, mut head)) = heads.iter_mut().next() {
-        let segment_positions = segments.0.iter()
-            .map(|e| *positions.get_mut(*e).unwrap())
-            .collect::<Vec<Position>>();
+    if !border_walls.is_changed() && !grid.is_changed() {
+        return;
+    }
+    for wall in walls.iter() {
+        commands.entity(wall).despawn();
+    }
+    if !border_walls.0 {
+        return;
+    }
+    let (width, height) = (grid.width as i32, grid.height as i32);
+    let ring = (-1..=width)
+        .flat_map(|x| vec![Position { x, y: -1 }, Position { x, y: height }])
+        .chain((0..height).flat_map(|y| vec![Position { x: -1, y }, Position { x: width, y }]));
+    for position in ring {
+        commands.spawn_bundle(SpriteBundle {
+            material: materials.obstacle_material.clone(),
+            ..Default::default()
+        })
+            .insert(Wall)
+            .insert(position)
+            .insert(Size::square(1.0));
+    }
+}
 
-        let mut head_pos = positions.get_mut(head_entity).unwrap();
-        head.direction = head.next_direction;
-        match &head.direction {
-            Direction::Left => head_pos.x -= 1,
-            Direction::Right => head_pos.x += 1,
-            Direction::Up => head_pos.y += 1,
-            Direction::Down => head_pos.y -= 1,
-        }
-        if head_pos.x < 0
-            || head_pos.x as u32 >= WIDTH
-            || head_pos.y < 0
-            || head_pos.y as u32 >= HEIGHT {
-            game_over_writer.send(GameOverEvent);
-        }
+// --checkerboard: every tile of the board gets a background square, in alternating shades.
+// they are drawn behind everything else and laid out again when the board is resized.
+fn fit_checkerboard(
+    mut commands: Commands,
+    checkerboard: Res<Checkerboard>,
+    grid: Res<GridDimensions>,
+    materials: Res<Materials>,
+    tiles: Query<Entity, With<CheckerTile>>,
+) {
+    if !checkerboard.is_changed() && !grid.is_changed() {
+        return;
+    }
+    for tile in tiles.iter() {
+        commands.entity(tile).despawn();
+    }
+    if !checkerboard.0 {
+        return;
+    }
+    for position in grid.tiles() {
+        commands.spawn_bundle(SpriteBundle {
+            material: materials.checker_materials[checker_shade(position)].clone(),
+            // position_translation keeps the depth, only x and y follow the tile
+            transform: Transform::from_xyz(0.0, 0.0, -1.0),
+            ..Default::default()
+        })
+            .insert(CheckerTile)
+            .insert(position)
+            .insert(Size::square(1.0));
+    }
+}
 
-        if segment_positions.contains(&head_pos) {
-            game_over_writer.send(GameOverEvent);
-        }
+fn checker_shade(pos: Position) -> usize {
+    ((pos.x + pos.y) % 2) as usize
+}
 
-        segment_positions.iter()
-            .zip(segments.0.iter().skip(1))
-            .for_each(|(segpos, segment)| {
-                *positions.get_mut(*segment).unwrap() = *segpos;
-            });
-        last_tail_position.0 = Some(*segment_positions.last().unwrap());
+// --border-warning: the tiles along the edge of the board get a faint red tint, so it is easy to see
+// where the board ends. only while the edge is deadly, bouncing off or wrapping around it is safe.
+fn fit_border_warning(
+    mut commands: Commands,
+    show: Res<ShowBorderWarning>,
+    wall_mode: Res<WallMode>,
+    grid: Res<GridDimensions>,
+    materials: Res<Materials>,
+    tiles: Query<Entity, With<BorderWarningTile>>,
+) {
+    if !show.is_changed() && !wall_mode.is_changed() && !grid.is_changed() {
+        return;
+    }
+    for tile in tiles.iter() {
+        commands.entity(tile).despawn();
+    }
+    if !show.0 || *wall_mode != WallMode::Die {
+        return;
+    }
+    for position in edge_tiles(*grid) {
+        commands.spawn_bundle(SpriteBundle {
+            material: materials.border_warning_material.clone(),
+            // over the checkerboard, behind everything on the board
+            transform: Transform::from_xyz(0.0, 0.0, -0.5),
+            ..Default::default()
+        })
+            .insert(BorderWarningTile)
+            .insert(position)
+            .insert(Size::square(1.0));
     }
 }
 
-fn snek_movement_input(keyboard_input: Res<Input<KeyCode>>, mut heads: Query<&mut SnekHead>) {
-    if let Some(mut head) = heads.iter_mut().next() {
-        let direction: Direction = if keyboard_input.pressed(KeyCode::A) {
-            Direction::Left
-        } else if keyboard_input.pressed(KeyCode::D) {
-            Direction::Right
-        } else if keyboard_input.pressed(KeyCode::W) {
-            Direction::Up
-        } else if keyboard_input.pressed(KeyCode::S) {
-            Direction::Down
-        } else {
-            head.direction
-        };
+// the outermost ring of tiles on the board
+fn edge_tiles(grid: GridDimensions) -> impl Iterator<Item = Position> {
+    let (right, top) = (grid.width as i32 - 1, grid.height as i32 - 1);
+    grid.tiles().filter(move |pos| pos.x == 0 || pos.y == 0 || pos.x == right || pos.y == top)
+}
 
-        if direction != head.direction.opposite() && direction != head.direction {
-            head.next_direction = direction;
-        }
+// --vision: a dark tile over every tile of the board, see fog_of_war
+fn fit_fog(
+    mut commands: Commands,
+    vision: Res<VisionRadius>,
+    grid: Res<GridDimensions>,
+    materials: Res<Materials>,
+    tiles: Query<Entity, With<FogTile>>,
+) {
+    if !vision.is_changed() && !grid.is_changed() {
+        return;
+    }
+    for tile in tiles.iter() {
+        commands.entity(tile).despawn();
+    }
+    if vision.0.is_none() {
+        return;
+    }
+    for position in grid.tiles() {
+        commands.spawn_bundle(SpriteBundle {
+            material: materials.fog_material.clone(),
+            // over the board, but under the instant replay
+            transform: Transform::from_xyz(0.0, 0.0, 1.0),
+            ..Default::default()
+        })
+            .insert(FogTile)
+            .insert(position)
+            .insert(Size::square(1.0));
     }
 }
 
-fn food_spawner(mut commands: Commands, materials: Res<Materials>, positions: Query<&Position, With<SnekSegment>>) {
-    let gen = || {
-        Position{
-            x: (random::<f32>() * WIDTH as f32) as i32,
-            y: (random::<f32>() * HEIGHT as f32) as i32,
-        }
+// only the fog near the head lifts. the food under it is still there, it just can't be seen.
+fn fog_of_war(
+    vision: Res<VisionRadius>,
+    heads: Query<&Position, With<SnekHead>>,
+    mut tiles: Query<(&Position, &mut Visible), With<FogTile>>,
+) {
+    let radius = match vision.0 {
+        Some(radius) => radius,
+        None => return,
     };
-
-    let positions_vec: Vec<&Position> = positions.iter().collect();
-    let mut pos = gen();
-    while positions_vec.contains(&&pos) {
-        pos = gen();
+    let head = match heads.iter().next() {
+        Some(head) => *head,
+        None => return,
+    };
+    for (pos, mut visible) in tiles.iter_mut() {
+        let foggy = !in_sight(*pos, head, radius);
+        if visible.is_visible != foggy {
+            visible.is_visible = foggy;
+        }
     }
-
-
-    commands.spawn_bundle(SpriteBundle {
-        material: materials.food_material.clone(),
-        ..Default::default()
-    })
-        .insert(Food)
-        .insert(pos)
-        .insert(Size::square(0.7));
 }
 
-fn spawn_segment(mut commands: Commands, material: &Handle<ColorMaterial>, position: Position) -> Entity {
-    commands.spawn_bundle(SpriteBundle {
-        material: material.clone(),
-        ..Default::default()
-    })
-        .insert(SnekSegment)
-        .insert(position)
-        .insert(Size::square(0.5))
-        .id()
+// a round window around the head
+fn in_sight(pos: Position, head: Position, radius: u32) -> bool {
+    let (dx, dy) = ((pos.x - head.x) as i64, (pos.y - head.y) as i64);
+    dx * dx + dy * dy <= radius as i64 * radius as i64
 }
 
-fn snek_eating(mut commands: Commands,
-               mut growth_writer: EventWriter<GrowthEvent>,
-               food_positions: Query<(Entity, &Position), With<Food>>,
-               head_positions: Query<&Position, With<SnekHead>>,
-) {
-    for head_pos in head_positions.iter() {
-        for (ent, food_pos) in food_positions.iter() {
-            if food_pos == head_pos {
-                commands.entity(ent).despawn();
-                growth_writer.send(GrowthEvent);
-            }
+// a resized board gets a new patrol. also runs once at startup, with the path it already has.
+fn fit_obstacles(grid: Res<GridDimensions>, mut obstacles: Query<(&mut Obstacle, &mut Position)>) {
+    if grid.is_changed() {
+        for (mut obstacle, mut position) in obstacles.iter_mut() {
+            obstacle.path = obstacle_path(*grid);
+            obstacle.index = 0;
+            *position = obstacle.position();
         }
     }
 }
 
-fn snek_growth(
-    commands: Commands,
-    last_tail_position: Res<LastTailPosition>,
-    mut segments: ResMut<SnekSegments>,
-    mut growth_reader: EventReader<GrowthEvent>,
-    materials: Res<Materials>,
-) {
-    if growth_reader.iter().next().is_some() {
-        segments.0.push(spawn_segment(
-            commands,
-            &materials.segment_material,
-            last_tail_position.0.unwrap(),
-        ))
+fn step_obstacles(mut obstacles: Query<(&mut Obstacle, &mut Position)>) {
+    for (mut obstacle, mut position) in obstacles.iter_mut() {
+        obstacle.index = (obstacle.index + 1) % obstacle.path.len();
+        *position = obstacle.position();
     }
 }
 
-fn game_over(
-    mut game_over_reader: EventReader<GameOverEvent>,
-    mut commands: Commands,
-    materials: Res<Materials>,
-    segments_res: ResMut<SnekSegments>,
-    food: Query<Entity, With<Food>>,
-    segments: Query<Entity, With<SnekSegment>>,
-) {
-    if game_over_reader.iter().next().is_some() {
-        for ent in food.iter().chain(segments.iter()) {
-            commands.entity(ent).despawn();
+// the snek starts heading up with its body trailing below the head
+fn start_position(center_start: bool, length: usize, grid: GridDimensions) -> Position {
+    // keep room below the head for the rest of the body
+    let min_y = length as i32 - 1;
+    if center_start {
+        Position {
+            x: (grid.width / 2) as i32,
+            y: ((grid.height / 2) as i32).max(min_y),
+        }
+    } else {
+        Position {
+            x: 3,
+            y: 3.max(min_y),
         }
-
-        spawn_snek(commands, materials, segments_res);
     }
 }
 
-struct SnekHead {
-    direction: Direction,
-    next_direction: Direction
+// a minimized window can report a size of zero. scaling against that would produce NaN transforms,
+// so the rendering systems skip the frame instead.
+fn usable_window(windows: &Windows) -> Option<&Window> {
+    windows.get_primary()
+        .filter(|window| window.width() > 0.0 && window.height() > 0.0)
 }
 
-struct Food;
+// a sprite with a SizeTween is drawn somewhere between its old and its new size until the tween is done
+#[allow(clippy::too_many_arguments)]
+fn size_scaling(
+    mut commands: Commands,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    grid: Res<GridDimensions>,
+    border_walls: Res<BorderWalls>,
+    (batch, pixel_snap, mut resized): (Res<BatchSprites>, Res<PixelSnap>, EventReader<WindowResized>),
+    mut query: Query<(Entity, &Size, &mut Sprite, Option<&mut SizeTween>)>,
+    changed: Query<Entity, Or<(Changed<Size>, With<SizeTween>)>>,
+    (thickness, blocks): (Res<Thickness>, Query<Entity, Or<(With<SnekSegment>, With<Food>)>>),
+) {
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    let (board, _) = border_walls.drawn(*grid);
+    let scale_factor = Some(window.scale_factor() as f32).filter(|_| pixel_snap.0);
+    let everything = !batch.0 || resized.iter().count() > 0 || grid.is_changed() || border_walls.is_changed();
+    let sized = if everything {
+        query.iter_mut().map(|(ent, ..)| ent).collect::<Vec<Entity>>()
+    } else {
+        changed.iter().collect()
+    };
+    for ent in sized {
+        let (ent, sprite_size, mut sprite, tween) = match query.get_mut(ent) {
+            Ok(sized) => sized,
+            Err(_) => continue,
+        };
+        let sprite_size = match tween {
+            Some(mut tween) => {
+                if tween.timer.tick(time.delta()).finished() {
+                    commands.entity(ent).remove::<SizeTween>();
+                }
+                tween.current(sprite_size)
+            }
+            None => *sprite_size,
+        };
+        // segments and fruit are drawn over their whole block
+        let scale = if blocks.get(ent).is_ok() { thickness.0 as f32 } else { 1.0 };
+        sprite.size = Vec2::new(
+            pixel_size(sprite_size.width * scale / board.width as f32 * window.width(), scale_factor),
+            pixel_size(sprite_size.height * scale / board.height as f32 * window.height(), scale_factor),
+        )
+    }
+}
 
-// This struct is used like a tag, so we can query for it later.
-struct Materials {
-    // This struct will be a resource which stores materials for various components.
-    head_material: Handle<ColorMaterial>,
-    food_material: Handle<ColorMaterial>,
-    segment_material: Handle<ColorMaterial>,
+// rounded up to whole physical pixels, so a sprite as big as its tile covers it all wherever
+// tile_line put its edges
+fn pixel_size(size: f32, scale_factor: Option<f32>) -> f32 {
+    match scale_factor {
+        Some(scale) => (size * scale).ceil() / scale,
+        None => size,
+    }
 }
 
-#[derive(PartialEq, Copy, Clone, Debug)]
-enum Direction {
-    Left,
-    Right,
-    Up,
-    Down,
+// B switches between the segment styles while playing. the body grows or shrinks to the new size
+// instead of snapping to it, see SizeTween.
+fn toggle_segment_style(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    lockstep: Res<Lockstep>,
+    mut segment_style: ResMut<SegmentStyle>,
+    mut sized: Query<
+        (Entity, &mut Size, Option<&SizeTween>),
+        (Or<(With<SnekSegment>, With<GrowthPreview>)>, Without<SnekHead>),
+    >,
+) {
+    // recordings only contain turns
+    if lockstep.0 || !keyboard_input.just_pressed(KeyCode::B) {
+        return;
+    }
+    *segment_style = match *segment_style {
+        SegmentStyle::Beaded => SegmentStyle::Solid,
+        SegmentStyle::Solid => SegmentStyle::Beaded,
+    };
+    for (ent, mut size, tween) in sized.iter_mut() {
+        // switching back halfway starts from wherever the last switch got to
+        let from = tween.map_or(*size, |tween| tween.current(&size));
+        *size = segment_style.segment_size();
+        commands.entity(ent).insert(SizeTween {
+            from,
+            timer: Timer::from_seconds(SIZE_TWEEN_SECONDS, false),
+        });
+    }
 }
 
-impl Direction {
-    fn opposite(self) -> Self {
-        match self {
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-            Self::Up => Self::Down,
-            Self::Down => Self::Up,
+fn convert(pos: f32, bound_window: f32, bound_game: f32) -> f32 {
+    let tile_size = bound_window / bound_game;
+    pos / bound_game * bound_window  // translate the tile position to pixel position
+        - (bound_window / 2.0)  // coordinate 0:0 is right in the middle of the screen. so we subtract half the screen
+        + (tile_size / 2.0) // then add half a tile because the tile also has 0:0 in the center
+}
+
+// where the line `line` tiles from the left (or bottom) of the board is drawn, from the middle of the
+// window. with a scale factor it is rounded to a whole physical pixel. two tiles next to each other
+// get their shared edge from the same line, so they always meet without a gap.
+fn tile_line(line: f32, bound_window: f32, bound_game: f32, scale_factor: Option<f32>) -> f32 {
+    let at = line / bound_game * bound_window - bound_window / 2.0;
+    match scale_factor {
+        Some(scale) => (at * scale).round() / scale,
+        None => at,
+    }
+}
+
+// with YAxisDown, row 0 is drawn at the top of the window instead of the bottom
+fn convert_y(y: f32, bound_window: f32, grid: GridDimensions, y_axis_down: YAxisDown) -> f32 {
+    let y = if y_axis_down.0 { grid.height as f32 - 1.0 - y } else { y };
+    convert(y, bound_window, grid.height as f32)
+}
+
+// with --stretch the body isn't put right on its tiles, see stretch_towards. only the drawing lags,
+// the game itself still only knows the tiles.
+#[allow(clippy::too_many_arguments)]
+fn position_translation(
+    windows: Res<Windows>,
+    time: Res<Time>,
+    y_axis_down: Res<YAxisDown>,
+    grid: Res<GridDimensions>,
+    border_walls: Res<BorderWalls>,
+    stretch: Res<StretchFactor>,
+    segments: Res<SnekSegments>,
+    (batch, pixel_snap, mut resized): (Res<BatchSprites>, Res<PixelSnap>, EventReader<WindowResized>),
+    mut q: Query<(Entity, &Position, &mut Transform)>,
+    moved: Query<Entity, Changed<Position>>,
+    (thickness, blocks): (Res<Thickness>, Query<Entity, Or<(With<SnekSegment>, With<Food>)>>),
+    (wall_mode, mut wrap_slides): (Res<WallMode>, Local<WrapSlides>),
+) {
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    let (board, inset) = border_walls.drawn(*grid);
+    let scale_factor = Some(window.scale_factor() as f32).filter(|_| pixel_snap.0);
+    // in the middle between the lines around the tile, or around the whole block for a thick snek
+    let on_tile = |entity: Entity, at: Vec2| {
+        let span = if blocks.get(entity).is_ok() { thickness.0 as f32 } else { 1.0 };
+        let (low_x, low_y) = (at.x + inset, at.y + inset);
+        let low_y = if y_axis_down.0 { board.height as f32 - low_y - span } else { low_y };
+        let between = |low: f32, bound_window: f32, bound_game: u32| {
+            let line = |line: f32| tile_line(line, bound_window, bound_game as f32, scale_factor);
+            (line(low) + line(low + span)) / 2.0
+        };
+        Vec2::new(between(low_x, window.width(), board.width), between(low_y, window.height(), board.height))
+    };
+    let tile_of = |pos: &Position| Vec2::new(pos.x as f32, pos.y as f32);
+    if *wall_mode == WallMode::Wrap && stretch.0 <= 0.0 {
+        let segment_positions = segments.0.iter()
+            .filter_map(|e| q.get_mut(*e).ok().map(|(e, pos, _)| (e, *pos)))
+            .collect::<Vec<(Entity, Position)>>();
+        wrap_slides.update(&segment_positions, *grid, time.delta());
+    }
+    let stretched = if stretch.0 > 0.0 { segments.0.iter().copied().collect() } else { HashSet::new() };
+    // anything that changes where all the tiles are drawn moves every sprite, even in batch mode
+    let everything = !batch.0
+        || resized.iter().count() > 0
+        || grid.is_changed()
+        || border_walls.is_changed()
+        || y_axis_down.is_changed();
+    let mut placed = if everything {
+        q.iter_mut().map(|(entity, ..)| entity).collect::<Vec<Entity>>()
+    } else {
+        moved.iter().collect()
+    };
+    // sliding segments move every frame, and once more to land on their tile
+    placed.extend(wrap_slides.sliding.keys().chain(wrap_slides.landed.iter()));
+    for entity in placed.into_iter().filter(|entity| !stretched.contains(entity)) {
+        if let Ok((_, pos, mut transform)) = q.get_mut(entity) {
+            let at = wrap_slides.sliding.get(&entity).map_or_else(|| tile_of(pos), |slide| slide.at(*pos));
+            transform.translation = on_tile(entity, at).extend(transform.translation.z);
+        }
+    }
+    if stretched.is_empty() {
+        return;
+    }
+
+    let tile = Vec2::new(window.width() / board.width as f32, window.height() / board.height as f32);
+    let mut ahead = None;
+    for (i, segment) in segments.0.iter().enumerate() {
+        if let Ok((_, pos, mut transform)) = q.get_mut(*segment) {
+            let target = on_tile(*segment, tile_of(pos));
+            let drawn = match ahead {
+                Some(ahead) => {
+                    let rate = STRETCH_CATCH_UP / (1.0 + stretch.0 * i as f32);
+                    let catch_up = 1.0 - (-rate * time.delta_seconds()).exp();
+                    stretch_towards(transform.translation.truncate(), target, ahead, catch_up, tile)
+                }
+                None => target,
+            };
+            transform.translation = drawn.extend(transform.translation.z);
+            ahead = Some(drawn);
         }
     }
 }
 
-#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
-enum SnekMovement {
-    Input,
-    Movement,
-    Eating,
-    Growth,
+// moves a drawn segment the catch_up fraction of the way to its tile, but keeps it close enough to
+// the segment in front of it that the body stays in one piece
+fn stretch_towards(drawn: Vec2, target: Vec2, ahead: Vec2, catch_up: f32, tile: Vec2) -> Vec2 {
+    if ((target - drawn) / tile).length() > STRETCH_SNAP {
+        return target;
+    }
+    let moved = drawn + (target - drawn) * catch_up;
+    let gap = (moved - ahead) / tile;
+    if gap.length() > STRETCH_MAX_GAP {
+        ahead + gap.normalize() * STRETCH_MAX_GAP * tile
+    } else {
+        moved
+    }
 }
 
-struct SnekSegment;
+// what else is in the way on the board, besides the snek itself and the obstacle
+#[derive(SystemParam)]
+struct Blockers<'a> {
+    rivals: Res<'a, RivalTiles>,
+    boss: Res<'a, BossTiles>,
+    room_walls: Res<'a, RoomWalls>,
+    objective: Res<'a, keys::Objective>,
+}
 
-#[derive(Default)]
-struct SnekSegments(Vec<Entity>);
+impl<'a> Blockers<'a> {
+    // every tile a fruit can't go on
+    fn tiles(&self) -> impl Iterator<Item = &Position> + '_ {
+        self.rivals.0.iter()
+            .chain(self.boss.0.iter())
+            .chain(self.room_walls.0.iter())
+            .chain(self.objective.taken.iter())
+    }
+}
 
-struct GrowthEvent;
+// what came of a step of the snek, for the systems after it
+#[derive(SystemParam)]
+struct MovementEvents<'a> {
+    game_over: EventWriter<'a, GameOverEvent>,
+    reverse: EventWriter<'a, ReverseEvent>,
+    boss_bump: EventWriter<'a, BossBumpEvent>,
+    won: EventWriter<'a, keys::GameWonEvent>,
+}
 
-#[derive(Default)]
-struct LastTailPosition(Option<Position>);
+#[allow(clippy::too_many_arguments)]
+fn snek_movement(mut commands: Commands,
+                 mut segments: ResMut<SnekSegments>,
+                 mut occupied: ResMut<Occupied>,
+                 mut heads: Query<(Entity, &mut SnekHead)>,
+                 mut positions: Query<&mut Position>,
+                 mut last_tail_position: ResMut<LastTailPosition>,
+                 mut events: MovementEvents,
+                 (tail_collision_mode, wall_mode, grid, thickness):
+                     (Res<TailCollisionMode>, Res<WallMode>, Res<GridDimensions>, Res<Thickness>),
+                 (mut movement_timer, speed_freeze, direction_lock):
+                     (ResMut<MovementTimer>, Res<SpeedFreeze>, Res<DirectionLock>),
+                 (difficulty, adaptive): (Res<Difficulty>, Res<AdaptiveDifficulty>),
+                 (invulnerability, require_input, steer_key_held):
+                     (Res<Invulnerability>, Res<RequireInput>, Res<SteerKeyHeld>),
+                 mut co_op: CoOpTurns,
+                 obstacles: Query<&Obstacle>,
+                 blockers: Blockers,
+                 pending_growth: Res<PendingGrowth>,
+                 mut segment_positions: Local<Vec<Position>>,
+) {
+    // --require-input: the snek stands still, and keeps its tick, until a key is held again
+    if require_input.0 && !steer_key_held.0 {
+        return;
+    }
+    if let Some((head_entity, mut head)) = heads.iter_mut().next() {
+        let old_head_pos = *positions.get_mut(head_entity).unwrap();
+        // a vote can be from before the snek started over, heading somewhere else
+        if let Some(turn) = co_op.count() {
+            if let Some(turn) = resolve_turn(head.direction, turn) {
+                head.next_direction = turn;
+            }
+        }
+        if direction_lock.timer.is_none() {
+            head.direction = head.next_direction;
+        }
+        if *wall_mode == WallMode::Bounce {
+            if let Some(bounced) = wall_bounce(old_head_pos, head.direction, *grid, *thickness) {
+                // the head stays at the wall for this tick. the body turns around with it,
+                // otherwise the head would run straight into its own neck.
+                head.direction = bounced;
+                head.next_direction = bounced;
+                if segments.0.len() > 1 {
+                    events.reverse.send(ReverseEvent);
+                }
+                return;
+            }
+        }
+        let mut head_pos = thickness.step(old_head_pos, head.direction);
+        if *wall_mode == WallMode::Wrap {
+            head_pos = wrapped(head_pos, *grid);
+        }
+        // a boss can't be walked into. the head stays in front of it for this tick, see hit_boss.
+        if let Some(bumped) = thickness.footprint(head_pos).find(|pos| blockers.boss.0.contains(pos)) {
+            events.boss_bump.send(BossBumpEvent(bumped));
+            return;
+        }
+        if !thickness.fits(head_pos, *grid)
+            || obstacles.iter().any(|o| o.hits(old_head_pos, head_pos, *thickness))
+            || thickness.footprint(head_pos)
+                .any(|pos| blockers.rivals.0.contains(&pos) || blockers.room_walls.0.contains(&pos))
+        {
+            events.game_over.send(GameOverEvent);
+        } else if blockers.objective.goal.is_some_and(|goal| thickness.footprint(head_pos).any(|pos| pos == goal)) {
+            events.won.send(keys::GameWonEvent);
+        }
+        shift_segments(&segments.0, head_pos, &mut positions, &mut segment_positions);
 
-struct GameOverEvent;
+        // the head counts as a segment, so a snek without a body leaves the tile its head was on
+        let old_tail_pos = segment_positions.last().copied().unwrap_or(old_head_pos);
+        // the tail moves out of the way in the same step, unless a new segment grows in its place
+        let chasing_tail = head_pos == old_tail_pos && !pending_growth.due_next_tick();
+
+        // the set answers "did we hit ourselves" right away, finding which segment we hit is rarely needed
+        let bitten = if occupied.0.contains(&head_pos) && invulnerability.timer.is_none() && !chasing_tail {
+            segment_positions.iter().position(|pos| *pos == head_pos)
+        } else {
+            None
+        };
+        occupied.advance(old_tail_pos, head_pos);
+
+        if let Some(index) = bitten {
+            match *tail_collision_mode {
+                TailCollisionMode::Die => events.game_over.send(GameOverEvent),
+                TailCollisionMode::Truncate => {
+                    // after moving, segment index + 1 sits on the tile the head just entered.
+                    // it and everything behind it is bitten off.
+                    for segment in segments.0.drain(index + 1..) {
+                        commands.entity(segment).despawn();
+                    }
+                    occupied.0 = segment_positions[..index].iter().copied().collect();
+                    occupied.0.insert(head_pos);
+                    if speed_freeze.timer.is_none() {
+                        movement_timer.0.set_duration(movement_interval(segments.0.len(), *difficulty, adaptive.factor));
+                    }
+                }
+            }
+        }
+
+        last_tail_position.0 = Some(old_tail_pos);
+    }
+}
+
+// moves the head to new_head and every other segment onto the tile of the one in front of it.
+// one pass with a single lookup per segment, which matters for long sneks (see `--bench-shift`).
+// old_positions is filled with where each segment was, head first. it is kept between ticks
+// so its allocation is reused.
+fn shift_segments(
+    segments: &[Entity],
+    new_head: Position,
+    positions: &mut Query<&mut Position>,
+    old_positions: &mut Vec<Position>,
+) {
+    old_positions.clear();
+    let mut next = new_head;
+    for segment in segments {
+        let mut pos = positions.get_mut(*segment).unwrap();
+        next = std::mem::replace(&mut *pos, next);
+        old_positions.push(next);
+    }
+}
+
+// the tile on the other side of the board, for a step over the edge
+fn wrapped(pos: Position, grid: GridDimensions) -> Position {
+    Position {
+        x: pos.x.rem_euclid(grid.width as i32),
+        y: pos.y.rem_euclid(grid.height as i32),
+    }
+}
+
+// the direction of a step from one tile to the other that went over the edge of the board
+fn wrap_direction(from: Position, to: Position, grid: GridDimensions) -> Option<Direction> {
+    [Direction::Up, Direction::Down, Direction::Left, Direction::Right].iter().copied()
+        .find(|direction| {
+            let step = from.neighbour(*direction);
+            !step.in_bounds(grid) && wrapped(step, grid) == to
+        })
+}
+
+// the reflected direction if the next step would leave the board
+fn wall_bounce(head: Position, direction: Direction, grid: GridDimensions, thickness: Thickness) -> Option<Direction> {
+    if thickness.fits(thickness.step(head, direction), grid) {
+        None
+    } else {
+        Some(direction.opposite())
+    }
+}
+
+// the snek gets faster the longer it is, up to MAX_MOVEMENT_RATE. the difficulty and the adaptive
+// factor scale the whole curve.
+fn movement_interval(length: usize, difficulty: Difficulty, adaptive: f64) -> Duration {
+    let extra_segments = length.saturating_sub(START_LENGTH) as f64;
+    let rate = (MOVEMENT_RATE + extra_segments * MOVEMENT_RATE_PER_SEGMENT).min(MAX_MOVEMENT_RATE);
+    Duration::from_secs_f64(1.0 / (rate * difficulty.speed_factor() * adaptive))
+}
+
+// also runs once at startup, since a freshly inserted resource counts as changed
+fn apply_difficulty(
+    difficulty: Res<Difficulty>,
+    adaptive: Res<AdaptiveDifficulty>,
+    segments: Res<SnekSegments>,
+    speed_freeze: Res<SpeedFreeze>,
+    mut movement_timer: ResMut<MovementTimer>,
+    mut spawn_timer: ResMut<SpawnTimer>,
+) {
+    if (difficulty.is_changed() || adaptive.is_changed()) && speed_freeze.timer.is_none() {
+        movement_timer.0.set_duration(movement_interval(segments.0.len().max(START_LENGTH), *difficulty, adaptive.factor));
+    }
+    if difficulty.is_changed() {
+        spawn_timer.0.set_duration(Duration::from_secs_f32(1.0 / difficulty.spawn_rate()));
+    }
+}
+
+// with --adaptive, surviving and keeping combos going speeds the snek up, dying slows it back down.
+// runs before game_over, so a death is seen before the game starts over.
+fn adapt_difficulty(
+    mode: Res<AdaptiveMode>,
+    delta: Res<FrameDelta>,
+    mut eaten_reader: EventReader<FoodEatenEvent>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut adaptive: ResMut<AdaptiveDifficulty>,
+    mut combo: Local<Combo>,
+) {
+    if !mode.0 {
+        return;
+    }
+    let mut factor = adaptive.factor + delta.0.as_secs_f64() * ADAPTIVE_GAIN_PER_SECOND;
+    combo.tick(delta.0);
+    for _ in eaten_reader.iter() {
+        combo.eat();
+        // the first fruit doesn't count, only keeping the combo going does
+        factor += ADAPTIVE_COMBO_GAIN * combo.count.saturating_sub(1).min(COMBO_FLAME_MAX) as f64;
+    }
+    if game_over_reader.iter().next().is_some() {
+        *combo = Combo::default();
+        factor -= ADAPTIVE_DEATH_PENALTY;
+    }
+    let factor = factor.clamp(ADAPTIVE_LIMITS.0, ADAPTIVE_LIMITS.1);
+    // left alone while paused, so apply_difficulty doesn't reset the timer every frame
+    if factor != adaptive.factor {
+        adaptive.factor = factor;
+    }
+}
+
+fn cycle_difficulty(keyboard_input: Res<Input<KeyCode>>, lockstep: Res<Lockstep>, mut difficulty: ResMut<Difficulty>) {
+    // recordings only contain turns
+    if !lockstep.0 && keyboard_input.just_pressed(KeyCode::C) {
+        *difficulty = difficulty.next();
+    }
+}
+
+fn difficulty_text(
+    difficulty: Res<Difficulty>,
+    mode: Res<AdaptiveMode>,
+    adaptive: Res<AdaptiveDifficulty>,
+    mut texts: Query<&mut Text, With<DifficultyText>>,
+) {
+    if difficulty.is_changed() || adaptive.is_changed() {
+        let value = if mode.0 {
+            format!("{:?} x{:.2}", *difficulty, adaptive.factor)
+        } else {
+            format!("{:?}", *difficulty)
+        };
+        for mut text in texts.iter_mut() {
+            // the factor creeps up every frame, the text only changes every hundredth
+            if text.sections[0].value != value {
+                text.sections[0].value = value.clone();
+            }
+        }
+    }
+}
+
+fn record_path(heads: Query<&Position, With<SnekHead>>, mut path: ResMut<PathHistory>) {
+    if let Some(head) = heads.iter().next() {
+        path.0.push_front(*head);
+        path.0.truncate(PATH_HISTORY_LENGTH);
+    }
+}
+
+fn clear_path_history(mut game_over_reader: EventReader<GameOverEvent>, mut path: ResMut<PathHistory>) {
+    if game_over_reader.iter().next().is_some() {
+        path.0.clear();
+    }
+}
+
+fn clear_pending_growth(mut game_over_reader: EventReader<GameOverEvent>, mut pending: ResMut<PendingGrowth>) {
+    if game_over_reader.iter().next().is_some() {
+        pending.0.clear();
+    }
+}
+
+fn toggle_path(keyboard_input: Res<Input<KeyCode>>, mut show_path: ResMut<ShowPath>) {
+    if keyboard_input.just_pressed(KeyCode::H) {
+        show_path.0 = !show_path.0;
+    }
+}
+
+fn toggle_tail_hint(keyboard_input: Res<Input<KeyCode>>, mut show_tail_hint: ResMut<ShowTailHint>) {
+    if keyboard_input.just_pressed(KeyCode::T) {
+        show_tail_hint.0 = !show_tail_hint.0;
+    }
+}
+
+// with --debug, holding M moves the snek so its head is on the tile under the mouse. the body keeps
+// its shape, and the snek stays put if that would push part of it off the board.
+#[allow(clippy::too_many_arguments)]
+fn teleport_to_mouse(
+    debug_keys: Res<DebugKeys>,
+    keyboard_input: Res<Input<KeyCode>>,
+    lockstep: Res<Lockstep>,
+    windows: Res<Windows>,
+    (grid, y_axis_down, border_walls): (Res<GridDimensions>, Res<YAxisDown>, Res<BorderWalls>),
+    cameras: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    segments: Res<SnekSegments>,
+    mut occupied: ResMut<Occupied>,
+    mut last_tail_position: ResMut<LastTailPosition>,
+    mut positions: Query<&mut Position>,
+) {
+    // recordings only contain turns
+    if !debug_keys.0 || lockstep.0 || !keyboard_input.pressed(KeyCode::M) {
+        return;
+    }
+    let (window, (camera, projection)) = match (usable_window(&windows), cameras.iter().next()) {
+        (Some(window), Some(camera)) => (window, camera),
+        _ => return,
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+    let camera = (camera.translation.truncate(), projection.scale);
+    let (head, target) = match (
+        segments.0.first().and_then(|head| positions.get_mut(*head).ok()),
+        window.cursor_position().and_then(|cursor| cursor_tile(cursor, window_size, camera, border_walls.drawn(*grid), *y_axis_down))
+            .filter(|target| target.in_bounds(*grid)),
+    ) {
+        (Some(head), Some(target)) => (*head, target),
+        _ => return,
+    };
+    let shift = |pos: Position| Position { x: pos.x + target.x - head.x, y: pos.y + target.y - head.y };
+    let fits = segments.0.iter().all(|segment| shift(*positions.get_mut(*segment).unwrap()).in_bounds(*grid));
+    if target == head || !fits {
+        return;
+    }
+    occupied.0.clear();
+    for segment in &segments.0 {
+        let mut pos = positions.get_mut(*segment).unwrap();
+        *pos = shift(*pos);
+        occupied.0.insert(*pos);
+    }
+    // pending growth comes in behind the tail, wherever that is now
+    last_tail_position.0 = last_tail_position.0.map(shift).filter(|pos| pos.in_bounds(*grid));
+}
+
+// with --debug, G grows the snek by one segment, the same way eating a fruit does
+fn grow_on_key(
+    debug_keys: Res<DebugKeys>,
+    keyboard_input: Res<Input<KeyCode>>,
+    lockstep: Res<Lockstep>,
+    mut growth_writer: EventWriter<GrowthEvent>,
+) {
+    // recordings only contain turns
+    if debug_keys.0 && !lockstep.0 && keyboard_input.just_pressed(KeyCode::G) {
+        growth_writer.send(GrowthEvent);
+    }
+}
+
+// with --debug, O outlines every tile something is on
+fn toggle_hitboxes(
+    debug_keys: Res<DebugKeys>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut show_hitboxes: ResMut<ShowHitboxes>,
+) {
+    if debug_keys.0 && keyboard_input.just_pressed(KeyCode::O) {
+        show_hitboxes.0 = !show_hitboxes.0;
+    }
+}
+
+// the outlines go straight from Position to the screen, without anything position_translation
+// does on the way, so a sprite that is drawn off its tile sticks out of its outline.
+// rebuilt every frame while they are shown, they are only for debugging.
+#[allow(clippy::too_many_arguments)]
+fn draw_hitboxes(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    show_hitboxes: Res<ShowHitboxes>,
+    materials: Res<Materials>,
+    (grid, border_walls, y_axis_down, thickness): (Res<GridDimensions>, Res<BorderWalls>, Res<YAxisDown>, Res<Thickness>),
+    taken: Query<
+        (&Position, Option<&SnekSegment>),
+        Or<(
+            With<SnekSegment>,
+            With<Food>,
+            With<Obstacle>,
+            With<Wall>,
+            With<RoomWall>,
+            With<BossTile>,
+            With<RivalSegment>,
+            With<keys::DoorWall>,
+            With<keys::Key>,
+        )>,
+    >,
+    edges: Query<Entity, With<HitboxEdge>>,
+) {
+    if !show_hitboxes.0 && !show_hitboxes.is_changed() {
+        return;
+    }
+    for edge in edges.iter() {
+        commands.entity(edge).despawn();
+    }
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    if !show_hitboxes.0 {
+        return;
+    }
+
+    let (board, inset) = border_walls.drawn(*grid);
+    let tile = Vec2::new(window.width() / board.width as f32, window.height() / board.height as f32);
+    // a thick snek takes up its whole block
+    let tiles = taken.iter().flat_map(|(pos, segment)| match segment {
+        Some(_) => thickness.footprint(*pos).collect::<Vec<Position>>(),
+        None => vec![*pos],
+    });
+    for pos in tiles {
+        let center = Vec2::new(
+            convert(pos.x as f32 + inset, window.width(), board.width as f32),
+            convert_y(pos.y as f32 + inset, window.height(), board, *y_axis_down),
+        );
+        for (offset, size) in hitbox_edges(tile) {
+            commands.spawn_bundle(SpriteBundle {
+                material: materials.hitbox_material.clone(),
+                sprite: Sprite::new(size),
+                // above the board, the fog and the instant replay
+                transform: Transform::from_xyz(center.x + offset.x, center.y + offset.y, 7.0),
+                ..Default::default()
+            })
+                .insert(HitboxEdge);
+        }
+    }
+}
+
+// the four sides of the outline of a tile, as offsets from its middle and sizes. they are drawn
+// inside the tile, so the outlines of two neighbours don't overlap.
+fn hitbox_edges(tile: Vec2) -> [(Vec2, Vec2); 4] {
+    let line = tile.min_element() * HITBOX_LINE_WIDTH;
+    let (across, up) = ((tile.x - line) / 2.0, (tile.y - line) / 2.0);
+    [
+        (Vec2::new(0.0, up), Vec2::new(tile.x, line)),
+        (Vec2::new(0.0, -up), Vec2::new(tile.x, line)),
+        (Vec2::new(-across, 0.0), Vec2::new(line, tile.y)),
+        (Vec2::new(across, 0.0), Vec2::new(line, tile.y)),
+    ]
+}
+
+// the tile under a cursor position (in pixels from the bottom left of the window), the inverse of
+// position_translation. camera is the main camera's position and zoom, board is what
+// BorderWalls::drawn returns. the border walls are off the board, so they can come back too.
+fn cursor_tile(
+    cursor: Vec2,
+    window_size: Vec2,
+    (camera, scale): (Vec2, f32),
+    (board, inset): (GridDimensions, f32),
+    y_axis_down: YAxisDown,
+) -> Option<Position> {
+    // from the bottom left of the window, in tiles
+    let tiles = ((cursor - window_size / 2.0) * scale + camera + window_size / 2.0) / window_size
+        * Vec2::new(board.width as f32, board.height as f32);
+    let (x, y) = (tiles.x.floor() as i32, tiles.y.floor() as i32);
+    let y = if y_axis_down.0 { board.height as i32 - 1 - y } else { y };
+    Some(Position { x, y }).filter(|pos| pos.in_bounds(board))
+        .map(|pos| Position { x: pos.x - inset as i32, y: pos.y - inset as i32 })
+}
+
+// marks the tile the tail frees up with the next move, which is safe to enter.
+// follows SnekSegments every frame, so after a game over it is on the new snek right away.
+fn tail_hint(
+    show_tail_hint: Res<ShowTailHint>,
+    segments: Res<SnekSegments>,
+    pending_growth: Res<PendingGrowth>,
+    positions: Query<&Position, Without<TailHint>>,
+    mut hints: Query<(&mut Position, &mut Visible), With<TailHint>>,
+) {
+    // a head on its own never comes back to the tile it leaves
+    let tail = segments.0.last()
+        .filter(|_| segments.0.len() > 1)
+        .and_then(|tail| positions.get(*tail).ok());
+    for (mut hint_pos, mut visible) in hints.iter_mut() {
+        // a segment that is about to grow keeps the tile taken
+        let shown = show_tail_hint.0 && tail.is_some() && !pending_growth.due_next_tick();
+        if let Some(tail) = tail {
+            if *hint_pos != *tail {
+                *hint_pos = *tail;
+            }
+        }
+        if visible.is_visible != shown {
+            visible.is_visible = shown;
+        }
+    }
+}
+
+// a faint segment on the tile the tail just left, which is where new segments grow in,
+// while an eaten fruit is still on its way to becoming one. game over clears PendingGrowth, which hides it again.
+fn growth_preview(
+    pending_growth: Res<PendingGrowth>,
+    last_tail_position: Res<LastTailPosition>,
+    mut ghosts: Query<(&mut Position, &mut Visible), With<GrowthPreview>>,
+) {
+    let shown = last_tail_position.0.filter(|_| !pending_growth.0.is_empty());
+    for (mut ghost_pos, mut visible) in ghosts.iter_mut() {
+        if let Some(position) = shown {
+            if *ghost_pos != position {
+                *ghost_pos = position;
+            }
+        }
+        if visible.is_visible != shown.is_some() {
+            visible.is_visible = shown.is_some();
+        }
+    }
+}
+
+// draws a thin line between the centers of each pair of tiles the head went through, rebuilt whenever the path changes
+#[allow(clippy::too_many_arguments)]
+fn draw_path(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    show_path: Res<ShowPath>,
+    path: Res<PathHistory>,
+    materials: Res<Materials>,
+    y_axis_down: Res<YAxisDown>,
+    (grid, border_walls): (Res<GridDimensions>, Res<BorderWalls>),
+    lines: Query<Entity, With<PathLine>>,
+) {
+    if !show_path.is_changed() && !path.is_changed() {
+        return;
+    }
+    for line in lines.iter() {
+        commands.entity(line).despawn();
+    }
+
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    if !show_path.0 {
+        return;
+    }
+
+    let (board, inset) = border_walls.drawn(*grid);
+    let tile = Vec2::new(window.width() / board.width as f32, window.height() / board.height as f32);
+    for (age, (newer, older)) in path.0.iter().zip(path.0.iter().skip(1)).enumerate() {
+        let size = match direction_between(*older, *newer) {
+            Some(Direction::Left) | Some(Direction::Right) => Vec2::new(tile.x, tile.y * 0.15),
+            Some(Direction::Up) | Some(Direction::Down) => Vec2::new(tile.x * 0.15, tile.y),
+            // not neighbours, e.g. right after a reset. nothing sensible to connect.
+            None => continue,
+        };
+        commands.spawn_bundle(SpriteBundle {
+            material: materials.path_materials[age].clone(),
+            sprite: Sprite::new(size),
+            transform: Transform::from_xyz(
+                convert((newer.x + older.x) as f32 / 2.0 + inset, window.width(), board.width as f32),
+                convert_y((newer.y + older.y) as f32 / 2.0 + inset, window.height(), board, *y_axis_down),
+                1.0,
+            ),
+            ..Default::default()
+        })
+            .insert(PathLine);
+    }
+}
+
+// beads leave a notch on the inside of every bend. a filler bead goes on the inner corner of each
+// one, between the segments before and after it. the fillers are kept around and moved from frame to
+// frame, only as many as there are bends.
+#[allow(clippy::too_many_arguments)]
+fn corner_fills(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    materials: Res<Materials>,
+    (segment_style, stretch, thickness): (Res<SegmentStyle>, Res<StretchFactor>, Res<Thickness>),
+    y_axis_down: Res<YAxisDown>,
+    (grid, border_walls): (Res<GridDimensions>, Res<BorderWalls>),
+    segments: Res<SnekSegments>,
+    positions: Query<&Position, With<SnekSegment>>,
+    mut fills: Query<(Entity, &mut Sprite, &mut Transform), With<CornerFill>>,
+) {
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    // solid segments leave no notch, and a stretched body isn't on its tiles
+    let fill_size = 1.0 - segment_style.segment_size().width;
+    let corners = if fill_size > 0.0 && stretch.0 <= 0.0 {
+        let segment_positions = segments.0.iter()
+            .filter_map(|e| positions.get(*e).ok().copied())
+            .collect::<Vec<Position>>();
+        inner_corners(&segment_positions, *thickness)
+    } else {
+        Vec::new()
+    };
+
+    let (board, inset) = border_walls.drawn(*grid);
+    let tile = Vec2::new(window.width() / board.width as f32, window.height() / board.height as f32);
+    let size = tile * fill_size * thickness.0 as f32;
+    let centre = inset + (thickness.0 - 1) as f32 / 2.0;
+    let mut fills = fills.iter_mut();
+    for corner in corners {
+        let translation = Vec3::new(
+            convert(corner.x + centre, window.width(), board.width as f32),
+            convert_y(corner.y + centre, window.height(), board, *y_axis_down),
+            // under the segments
+            -0.5,
+        );
+        match fills.next() {
+            Some((_, mut sprite, mut transform)) => {
+                if sprite.size != size {
+                    sprite.size = size;
+                }
+                if transform.translation != translation {
+                    transform.translation = translation;
+                }
+            }
+            None => {
+                commands.spawn_bundle(SpriteBundle {
+                    material: materials.segment_material.clone(),
+                    sprite: Sprite::new(size),
+                    transform: Transform::from_translation(translation),
+                    ..Default::default()
+                })
+                    .insert(CornerFill);
+            }
+        }
+    }
+    // the snek straightened out or got shorter
+    for (stale, ..) in fills {
+        commands.entity(stale).despawn();
+    }
+}
+
+// the inner corner of every bend in the body, halfway between the segments before and after it.
+// segments that aren't next to each other (right after a warp) don't make a bend.
+fn inner_corners(segments: &[Position], thickness: Thickness) -> Vec<Vec2> {
+    let k = thickness.0 as i32;
+    let next_to = |a: Position, b: Position| (a.x - b.x).abs() + (a.y - b.y).abs() == k;
+    segments.windows(3)
+        .filter(|w| next_to(w[0], w[1]) && next_to(w[1], w[2]) && w[0].x != w[2].x && w[0].y != w[2].y)
+        .map(|w| Vec2::new((w[0].x + w[2].x) as f32 / 2.0, (w[0].y + w[2].y) as f32 / 2.0))
+        .collect()
+}
+
+// game time advances by FrameDelta instead of reading Time directly,
+// so the benchmark can step the game deterministically.
+#[allow(clippy::too_many_arguments)]
+fn frame_delta(
+    time: Res<Time>,
+    paused: Res<Paused>,
+    (transition, instant_replay): (Res<Transition>, Res<instant_replay::InstantReplay>),
+    lockstep: Res<Lockstep>,
+    movement_timer: Res<MovementTimer>,
+    mut slow_motion: ResMut<SlowMotion>,
+    mut saved_up: Local<Duration>,
+    mut delta: ResMut<FrameDelta>,
+) {
+    let slowed = match &mut slow_motion.timer {
+        Some(timer) => !timer.tick(time.delta()).finished(),
+        None => false,
+    };
+    if !slowed {
+        slow_motion.timer = None;
+    }
+
+    // while paused no game time passes, so every timer in the game stands still.
+    // the same goes for a fade, so the new game doesn't start moving while it is still dark,
+    // and for the instant replay of the game that just ended.
+    delta.0 = if paused.0 || transition.timer.is_some() || instant_replay.playing() {
+        Duration::ZERO
+    } else if lockstep.0 {
+        // game time only advances straight to the next movement tick, so how the frames happen to
+        // fall doesn't change what happens in the game
+        *saved_up += time.delta();
+        let to_next_tick = movement_timer.0.duration().saturating_sub(movement_timer.0.elapsed());
+        if *saved_up >= to_next_tick {
+            *saved_up -= to_next_tick;
+            to_next_tick
+        } else {
+            Duration::ZERO
+        }
+    } else if slowed {
+        time.delta().mul_f32(PANIC_TIME_SCALE)
+    } else {
+        time.delta()
+    };
+}
+
+// P or Escape. a turn pressed before pausing stays queued and is taken on the first tick after it.
+fn toggle_pause(keyboard_input: Res<Input<KeyCode>>, mut paused: ResMut<Paused>) {
+    if keyboard_input.just_pressed(KeyCode::P) || keyboard_input.just_pressed(KeyCode::Escape) {
+        paused.0 = !paused.0;
+    }
+}
+
+// switching to another window pauses the game, unless --no-focus-pause. coming back leaves it
+// paused, so the snek doesn't run off before there is a hand on the keys again.
+fn pause_on_focus_loss(
+    focus_pause: Res<FocusPause>,
+    mut focus_reader: EventReader<WindowFocused>,
+    mut paused: ResMut<Paused>,
+) {
+    let lost_focus = focus_reader.iter().any(|event| event.id == WindowId::primary() && !event.focused);
+    if focus_pause.0 && lost_focus && !paused.0 {
+        paused.0 = true;
+    }
+}
 
+fn paused_text(
+    paused: Res<Paused>,
+    grid: Res<GridDimensions>,
+    resize: Res<GridResize>,
+    mut texts: Query<&mut Text, With<PausedText>>,
+) {
+    if paused.is_changed() || grid.is_changed() || resize.is_changed() {
+        let board = match resize.0 {
+            Some(size) => format!("Board {} -> {}, Enter to start over on it", *grid, size),
+            None => format!("Board {}, arrow keys to resize", *grid),
+        };
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = if paused.0 {
+                format!("Paused - WASD to look around, +/- to zoom, I for stats\n{}", board)
+            } else {
+                String::new()
+            };
+        }
+    }
+}
+
+// I shows the lifetime stats while paused. unpausing hides them again.
+fn stats_screen(
+    keyboard_input: Res<Input<KeyCode>>,
+    paused: Res<Paused>,
+    stats: Option<Res<stats::Stats>>,
+    mut show_stats: ResMut<ShowStats>,
+    mut texts: Query<&mut Text, With<StatsText>>,
+) {
+    if !paused.0 {
+        if show_stats.0 {
+            show_stats.0 = false;
+        }
+    } else if keyboard_input.just_pressed(KeyCode::I) {
+        show_stats.0 = !show_stats.0;
+    }
+
+    let stats_changed = stats.as_ref().is_some_and(|stats| stats.is_changed());
+    if !show_stats.is_changed() && !stats_changed {
+        return;
+    }
+    let summary = match (&stats, show_stats.0) {
+        (Some(stats), true) => stats.summary(),
+        (None, true) => "No stats for replays".to_string(),
+        (_, false) => String::new(),
+    };
+    for mut text in texts.iter_mut() {
+        // the stats change every frame of play, while the screen is empty
+        if text.sections[0].value != summary {
+            text.sections[0].value = summary.clone();
+        }
+    }
+}
+
+// while paused, the arrow keys pick a new board size. Enter ends the game and starts over on it,
+// since the snek and the fruit wouldn't fit the new board. the size picked is dropped on unpausing.
+fn grid_resize_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    paused: Res<Paused>,
+    lockstep: Res<Lockstep>,
+    mut grid: ResMut<GridDimensions>,
+    mut resize: ResMut<GridResize>,
+    mut lives: ResMut<Lives>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+) {
+    // recordings only contain turns
+    if !paused.0 || lockstep.0 {
+        if resize.0.is_some() {
+            resize.0 = None;
+        }
+        return;
+    }
+
+    let mut size = resize.0.unwrap_or(*grid);
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        size.width -= 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        size.width += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        size.height -= 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        size.height += 1;
+    }
+    let size = size.clamped();
+
+    let applied = keyboard_input.just_pressed(KeyCode::Return) && size != *grid;
+    if applied {
+        *grid = size;
+        // the last life, so game_over starts a whole new game on the new board
+        lives.0 = 1;
+        game_over_writer.send(GameOverEvent);
+    }
+    let pending = if applied || size == *grid { None } else { Some(size) };
+    if resize.0 != pending {
+        resize.0 = pending;
+    }
+}
+
+// while paused, WASD pans and +/- zooms the game camera. unpausing puts it back where it was.
+// only the camera moves, so position_translation never notices.
+// the board is laid out in window pixels (see position_translation and size_scaling) and the camera
+// scale zooms the whole picture, so the board stays put relative to itself at any zoom.
+// the scroll wheel zooms at any time. while the game runs a zoomed in camera follows the head,
+// while paused it can be moved around freely.
+#[allow(clippy::too_many_arguments)]
+fn main_camera(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut scroll_reader: EventReader<MouseWheel>,
+    paused: Res<Paused>,
+    rooms: Res<Rooms>,
+    windows: Res<Windows>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection, &mut Camera), With<MainCamera>>,
+    heads: Query<&Transform, (With<SnekHead>, Without<MainCamera>)>,
+) {
+    let notches = scroll_reader.iter()
+        .map(|scroll| match scroll.unit {
+            MouseScrollUnit::Line => scroll.y,
+            MouseScrollUnit::Pixel => scroll.y / SCROLL_PIXELS_PER_NOTCH,
+        })
+        .sum::<f32>();
+
+    for (mut transform, mut projection, mut camera) in cameras.iter_mut() {
+        // scrolling up zooms in
+        let mut scale = projection.scale / SCROLL_ZOOM_STEP.powf(notches);
+        if paused.0 {
+            let mut pan = Vec2::ZERO;
+            if keyboard_input.pressed(KeyCode::A) {
+                pan.x -= 1.0;
+            }
+            if keyboard_input.pressed(KeyCode::D) {
+                pan.x += 1.0;
+            }
+            if keyboard_input.pressed(KeyCode::W) {
+                pan.y += 1.0;
+            }
+            if keyboard_input.pressed(KeyCode::S) {
+                pan.y -= 1.0;
+            }
+            // zoomed out, the same key press covers more of the board
+            let pan = pan * SPECTATE_PAN_SPEED * scale * time.delta_seconds();
+            transform.translation.x += pan.x;
+            transform.translation.y += pan.y;
+
+            let zoom = SPECTATE_ZOOM_SPEED.powf(time.delta_seconds());
+            let any_pressed = |keys: &[KeyCode]| keys.iter().any(|key| keyboard_input.pressed(*key));
+            if any_pressed(&[KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd]) {
+                scale /= zoom;
+            }
+            if any_pressed(&[KeyCode::Minus, KeyCode::NumpadSubtract]) {
+                scale *= zoom;
+            }
+        }
+        scale = scale.max(ZOOM_LIMITS.0).min(ZOOM_LIMITS.1);
+
+        // with --rooms only the room the head is in is shown, see room_view
+        if rooms.0 && !paused.0 {
+            scale = ROOM_ZOOM;
+        }
+        if !paused.0 {
+            let follow = match (heads.iter().next(), usable_window(&windows)) {
+                (Some(head), Some(window)) if rooms.0 => room_view(head.translation, window.width(), window.height()),
+                (Some(head), Some(window)) => follow_head(head.translation, window.width(), window.height(), scale),
+                _ => Vec2::ZERO,
+            };
+            transform.translation.x = follow.x;
+            transform.translation.y = follow.y;
+        }
+
+        if scale != projection.scale {
+            // the camera only rebuilds its matrix on window resizes, so we do it here
+            projection.scale = scale;
+            camera.projection_matrix = projection.get_projection_matrix();
+        }
+    }
+}
+
+// the middle of the quarter of the window the head is in. the walls between the rooms run through
+// the middle of the board, so that is the head's room, and the camera snaps over as soon as the head
+// is through a door.
+fn room_view(head: Vec3, window_width: f32, window_height: f32) -> Vec2 {
+    Vec2::new(window_width / 4.0 * head.x.signum(), window_height / 4.0 * head.y.signum())
+}
+
+// centers on the head, but never shows anything past the edge of the board.
+// zoomed out, the whole board fits and the camera stays in the middle.
+fn follow_head(head: Vec3, window_width: f32, window_height: f32, scale: f32) -> Vec2 {
+    let slack = (1.0 - scale).max(0.0) / 2.0;
+    let (max_x, max_y) = (window_width * slack, window_height * slack);
+    Vec2::new(head.x.max(-max_x).min(max_x), head.y.max(-max_y).min(max_y))
+}
+
+// run criteria for the movement set. unlike FixedTimestep, the step can change while the game runs.
+fn movement_timestep(
+    delta: Res<FrameDelta>,
+    mut timer: ResMut<MovementTimer>,
+    instant_turn: Res<InstantTurn>,
+    heads: Query<&SnekHead>,
+) -> ShouldRun {
+    if timer.0.tick(delta.0).just_finished() {
+        return ShouldRun::Yes;
+    }
+
+    // a turn that is waiting for the next tick gets it right away,
+    // as long as that doesn't let the snek move faster than it should.
+    let turn_pending = heads.iter().any(|head| head.next_direction != head.direction);
+    if instant_turn.0 && turn_pending && timer.0.percent() >= INSTANT_TURN_MIN_SPACING {
+        timer.0.reset();
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+// in on-demand and continuous mode the clock is ignored. snek_eating spawns the replacements, this only
+// tops the board up when it is short (at the start, after a game over or an expired target fruit).
+fn food_spawn_timestep(
+    delta: Res<FrameDelta>,
+    mut timer: ResMut<SpawnTimer>,
+    spawn_mode: Res<SpawnMode>,
+    max_food: Res<MaxFood>,
+    food: Query<(), With<Food>>,
+) -> ShouldRun {
+    if let Some(kept) = spawn_mode.kept_on_board(&max_food) {
+        if food.iter().count() < kept {
+            ShouldRun::Yes
+        } else {
+            ShouldRun::No
+        }
+    } else if timer.0.tick(delta.0).just_finished() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn snek_movement_input(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    paused: Res<Paused>,
+    y_axis_down: Res<YAxisDown>,
+    steering: Res<SteeringMode>,
+    max_turns: Res<MaxTurnsPerSecond>,
+    replay: Option<Res<replay::Replay>>,
+    mut heads: Query<&mut SnekHead>,
+    mut cooldown: Local<TurnCooldown>,
+    mut steer_key_held: ResMut<SteerKeyHeld>,
+    mut co_op: CoOpTurns,
+) {
+    cooldown.tick(time.delta());
+    let players: &[[KeyCode; 4]] = if co_op.on() { &[WASD, ARROW_KEYS] } else { &[WASD] };
+    let held = players.iter().flatten().any(|key| keyboard_input.pressed(*key));
+    if steer_key_held.0 != held {
+        steer_key_held.0 = held;
+    }
+    // while paused the same keys move the camera instead. a replay brings its own turns.
+    if paused.0 || replay.is_some() {
+        return;
+    }
+    if let Some(mut head) = heads.iter_mut().next() {
+        // both players only vote, snek_movement counts the votes on the next tick
+        if co_op.on() {
+            for (player, keys) in players.iter().enumerate() {
+                let turn = absolute_input(&keyboard_input, *keys, *y_axis_down)
+                    .and_then(|direction| resolve_turn(head.direction, direction));
+                if let Some(turn) = turn.filter(|turn| co_op.votes.votes[player] != Some(*turn)) {
+                    if cooldown.allow(max_turns.0) {
+                        co_op.votes.vote(player, turn);
+                    }
+                }
+            }
+            return;
+        }
+        let turn = if *steering == SteeringMode::Relative {
+            relative_input(&keyboard_input, *y_axis_down).map(|clockwise| relative_turn(head.direction, clockwise))
+        } else {
+            absolute_input(&keyboard_input, WASD, *y_axis_down)
+                .and_then(|direction| resolve_turn(head.direction, direction))
+        };
+        // holding a key asks for the same turn every frame, only a new one counts towards the cap
+        if let Some(turn) = turn.filter(|turn| *turn != head.next_direction) {
+            if cooldown.allow(max_turns.0) {
+                head.next_direction = turn;
+            }
+        }
+    }
+}
+
+// D turns clockwise on the screen, A counterclockwise. with the y axis down the screen is mirrored,
+// so the turns are too.
+fn relative_input(keyboard_input: &Input<KeyCode>, y_axis_down: YAxisDown) -> Option<bool> {
+    if keyboard_input.just_pressed(KeyCode::D) {
+        Some(!y_axis_down.0)
+    } else if keyboard_input.just_pressed(KeyCode::A) {
+        Some(y_axis_down.0)
+    } else {
+        None
+    }
+}
+
+// the keys for up, left, down and right
+const WASD: [KeyCode; 4] = [KeyCode::W, KeyCode::A, KeyCode::S, KeyCode::D];
+// the second player's, with --co-op
+const ARROW_KEYS: [KeyCode; 4] = [KeyCode::Up, KeyCode::Left, KeyCode::Down, KeyCode::Right];
+
+fn absolute_input(keyboard_input: &Input<KeyCode>, keys: [KeyCode; 4], y_axis_down: YAxisDown) -> Option<Direction> {
+    let [up, left, down, right] = keys;
+    let direction: Direction = if keyboard_input.pressed(left) {
+        Direction::Left
+    } else if keyboard_input.pressed(right) {
+        Direction::Right
+    } else if keyboard_input.pressed(up) {
+        Direction::Up
+    } else if keyboard_input.pressed(down) {
+        Direction::Down
+    } else {
+        return None;
+    };
+    // W always means up on the screen
+    Some(if y_axis_down.0 { direction.flipped_vertically() } else { direction })
+}
+
+// --rooms: the board is split into four rooms with doorways between them, and the camera only shows
+// the one the head is in
+struct Rooms(bool);
+
+// the walls between the rooms, see room_layout
+#[derive(Default)]
+struct RoomWalls(HashSet<Position>);
+
+struct RoomWall;
+
+// --require-input: the snek only moves while one of the steering keys is held
+struct RequireInput(bool);
+
+// whether one of the steering keys is held down right now, kept by snek_movement_input
+#[derive(Default)]
+struct SteerKeyHeld(bool);
+
+// --max-turns-per-second: turns pressed faster than this are dropped, so a slow tick can't be
+// filled up with a burst of turns
+struct MaxTurnsPerSecond(f32);
+
+// the time left until snek_movement_input takes another turn, see MaxTurnsPerSecond
+#[derive(Default)]
+struct TurnCooldown(Duration);
+
+impl TurnCooldown {
+    fn tick(&mut self, delta: Duration) {
+        self.0 = self.0.saturating_sub(delta);
+    }
+
+    // true if a turn may be taken now, which starts the cooldown over
+    fn allow(&mut self, max_per_second: f32) -> bool {
+        if self.0 > Duration::ZERO {
+            return false;
+        }
+        self.0 = Duration::from_secs_f32(1.0 / max_per_second);
+        true
+    }
+}
+
+// always a quarter turn from where the snek is heading now, so it can never reverse into itself.
+// pressing twice before the next tick doesn't add up, the second press just turns the same way again.
+fn relative_turn(current: Direction, clockwise: bool) -> Direction {
+    if clockwise { current.clockwise() } else { current.clockwise().opposite() }
+}
+
+// a turn is only accepted if it actually changes the direction and doesn't reverse the snek into itself
+fn resolve_turn(current: Direction, pressed: Direction) -> Option<Direction> {
+    if pressed != current.opposite() && pressed != current {
+        Some(pressed)
+    } else {
+        None
+    }
+}
+
+// the anchors a fruit can't go on: anything under the snek and the fruit in `taken`, the obstacle,
+// the blockers, and with --thickness anywhere it would overlap them or hang off the board
+fn food_blocked<'a>(
+    taken: impl Iterator<Item = &'a Position>,
+    obstacles: impl Iterator<Item = &'a Position>,
+    blockers: &Blockers,
+    thickness: Thickness,
+    grid: GridDimensions,
+) -> HashSet<Position> {
+    let mut covered = thickness.covered(taken);
+    covered.extend(obstacles);
+    covered.extend(blockers.tiles());
+    thickness.blocked_anchors(covered, grid)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn food_spawner(mut commands: Commands,
+                materials: Res<Materials>,
+                mut rng: ResMut<GameRng>,
+                spawn_pattern: Res<SpawnPattern>,
+                snek: Res<Occupied>,
+                food: Query<&Position, With<Food>>,
+                obstacles: Query<&Position, With<Obstacle>>,
+                max_food: Res<MaxFood>,
+                (wild, fleeing, momentum): (Res<WildMode>, Res<FleeingFood>, Res<MomentumFood>),
+                blockers: Blockers,
+                style: Res<GameStyle>,
+                grid: Res<GridDimensions>,
+                mut script: Option<ResMut<food_script::FoodScript>>,
+                spawn_mode: Res<SpawnMode>,
+                (cluster_chance, mut clusters_spawned): (Res<ClusterChance>, Local<u32>),
+                (spawn_bias, heads, thickness): (Res<SpawnBias>, Query<(&Position, &SnekHead)>, Res<Thickness>),
+) {
+    let food_on_board = food.iter().count();
+    if max_food.reached(food_on_board) {
+        return;
+    }
+    let mut occupied = food_blocked(snek.0.iter().chain(food.iter()), obstacles.iter(), &blockers, *thickness, *grid);
+    // one per tick of the timer, or as many as it takes to top the board up
+    let mut wanted = spawn_mode.kept_on_board(&max_food).map_or(1, |kept| kept.saturating_sub(food_on_board));
+    // a cluster takes the place of one fruit, as long as all of it fits under --max-food.
+    // the chance is only rolled with clusters on, so seeded games stay the same without them.
+    let cluster_allowed = script.is_none() && *style != GameStyle::SnakeII
+        && !max_food.reached(food_on_board + CLUSTER_SIZE - 1);
+    if wanted > 0 && cluster_allowed && cluster_chance.0 > 0.0 && rng.0.gen::<f32>() < cluster_chance.0 {
+        *clusters_spawned += 1;
+        if spawn_food_cluster(&mut commands, &materials, &mut rng.0, &mut occupied, *grid, *clusters_spawned) {
+            wanted -= 1;
+        }
+    }
+    let forward = spawn_bias.forward(heads.iter());
+    for _ in 0..wanted {
+        spawn_random_food(
+            &mut commands,
+            &materials,
+            &mut rng.0,
+            &mut occupied,
+            *spawn_pattern,
+            forward,
+            (*wild, *fleeing, *momentum),
+            *style,
+            *grid,
+            script.as_deref_mut(),
+        );
+    }
+}
+
+// returns false if there was no room left, or nothing left in the food script.
+// with a food script (--food-script) the fruit goes where the script says, only its kind is random.
+#[allow(clippy::too_many_arguments)]
+fn spawn_random_food(
+    commands: &mut Commands,
+    materials: &Materials,
+    rng: &mut StdRng,
+    occupied: &mut HashSet<Position>,
+    pattern: SpawnPattern,
+    forward: Option<(Position, Direction)>,
+    (wild, fleeing, momentum): (WildMode, FleeingFood, MomentumFood),
+    style: GameStyle,
+    grid: GridDimensions,
+    script: Option<&mut food_script::FoodScript>,
+) -> bool {
+    let position = match script {
+        Some(script) => script.next(occupied, grid),
+        // with nowhere free ahead of the head the fruit can go anywhere after all
+        None => forward
+            .and_then(|head| random_free_position(rng, &with_behind(occupied, head, grid), pattern, grid))
+            .or_else(|| random_free_position(rng, occupied, pattern, grid)),
+    };
+    match position {
+        Some(pos) => {
+            occupied.insert(pos);
+            let kind = random_food_kind(rng, (wild, fleeing, momentum), style);
+            spawn_food(commands, materials, kind, pos);
+            true
+        }
+        None => false,
+    }
+}
+
+// four regular fruits on a free 2x2 block, all tagged with the same cluster.
+// returns false if there is no such block left.
+fn spawn_food_cluster(
+    commands: &mut Commands,
+    materials: &Materials,
+    rng: &mut StdRng,
+    occupied: &mut HashSet<Position>,
+    grid: GridDimensions,
+    cluster: u32,
+) -> bool {
+    // the block a bottom left corner stands for
+    let block = |pos: Position| [
+        pos,
+        Position { x: pos.x + 1, ..pos },
+        Position { y: pos.y + 1, ..pos },
+        Position { x: pos.x + 1, y: pos.y + 1 },
+    ];
+    let blocked = grid.tiles()
+        .filter(|pos| block(*pos).iter().any(|tile| !tile.in_bounds(grid) || occupied.contains(tile)))
+        .collect::<HashSet<Position>>();
+    match random_free_position(rng, &blocked, SpawnPattern::Uniform, grid) {
+        Some(corner) => {
+            for tile in block(corner).iter() {
+                occupied.insert(*tile);
+                let fruit = spawn_food(commands, materials, FoodKind::Regular, *tile);
+                commands.entity(fruit)
+                    .insert(FoodCluster(cluster))
+                    .insert(materials.cluster_food_material.clone());
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+// snake II has no special fruit, its bonus comes on a fixed cadence instead (see snek_eating)
+fn random_food_kind(
+    rng: &mut StdRng,
+    (wild, fleeing, momentum): (WildMode, FleeingFood, MomentumFood),
+    style: GameStyle,
+) -> FoodKind {
+    let mut roll = rng.gen::<f32>();
+    if style == GameStyle::SnakeII {
+        return FoodKind::Regular;
+    }
+    for (kind, chance) in SPECIAL_FOOD_CHANCES.iter() {
+        if roll < *chance {
+            return match kind {
+                FoodKind::Split if !wild.0 => FoodKind::Regular,
+                FoodKind::Fleeing if !fleeing.0 => FoodKind::Regular,
+                FoodKind::Momentum if !momentum.0 => FoodKind::Regular,
+                _ => *kind,
+            };
+        }
+        roll -= chance;
+    }
+    FoodKind::Regular
+}
+
+// --spawn-bias forward: the occupied tiles plus every tile that isn't ahead of the head, so the fruit
+// shows up on the side of the board the snek is heading for
+fn with_behind(occupied: &HashSet<Position>, (head, direction): (Position, Direction), grid: GridDimensions) -> HashSet<Position> {
+    let ahead = |pos: &Position| match direction {
+        Direction::Up => pos.y > head.y,
+        Direction::Down => pos.y < head.y,
+        Direction::Right => pos.x > head.x,
+        Direction::Left => pos.x < head.x,
+    };
+    let mut blocked = occupied.clone();
+    blocked.extend(grid.tiles().filter(|pos| !ahead(pos)));
+    blocked
+}
+
+// picks among the tiles that are still free, so a full board returns None instead of looping forever
+fn random_free_position(
+    rng: &mut StdRng,
+    occupied: &HashSet<Position>,
+    pattern: SpawnPattern,
+    grid: GridDimensions,
+) -> Option<Position> {
+    let free = grid.tiles()
+        .filter(|pos| !occupied.contains(pos))
+        .filter(|pos| pattern != SpawnPattern::EdgesOnly || is_edge(*pos, grid))
+        .collect::<Vec<Position>>();
+
+    if free.is_empty() {
+        return None;
+    }
+
+    match pattern {
+        SpawnPattern::Uniform | SpawnPattern::EdgesOnly => Some(free[rng.gen_range(0..free.len())]),
+        SpawnPattern::CenterBias => {
+            // tiles get a weight that falls off with their distance from the middle of the grid
+            let weight = |pos: &Position| {
+                let dx = (pos.x as f32 - (grid.width as f32 - 1.0) / 2.0).abs();
+                let dy = (pos.y as f32 - (grid.height as f32 - 1.0) / 2.0).abs();
+                1.0 / (1.0 + dx + dy)
+            };
+            let mut pick = rng.gen::<f32>() * free.iter().map(weight).sum::<f32>();
+            for pos in free.iter() {
+                pick -= weight(pos);
+                if pick <= 0.0 {
+                    return Some(*pos);
+                }
+            }
+            free.last().copied()
+        }
+    }
+}
+
+// --wall-hug-penalty: points lost every tick the head spends right next to a wall, either a room wall
+// or a locked door, or the edge of the board when running into it is deadly.
+// the score only has whole points, so fractions are owed until they add up to one.
+fn wall_hug_penalty(
+    penalty: Res<WallHugPenalty>,
+    (grid, wall_mode, thickness): (Res<GridDimensions>, Res<WallMode>, Res<Thickness>),
+    blockers: Blockers,
+    heads: Query<&Position, With<SnekHead>>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut score: ResMut<Score>,
+    mut owed: Local<f32>,
+) {
+    // a new game doesn't start out owing anything
+    if game_over_reader.iter().next().is_some() {
+        *owed = 0.0;
+    }
+    if penalty.0 == 0.0 {
+        return;
+    }
+    let walled = |pos: Position| {
+        if pos.in_bounds(*grid) {
+            blockers.room_walls.0.contains(&pos)
+        } else {
+            *wall_mode == WallMode::Die
+        }
+    };
+    let directions = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+    // a head that left the board is a game over, not a wall hug
+    let hugging = heads.iter().any(|head| {
+        head.in_bounds(*grid)
+            && directions.iter().any(|direction| thickness.footprint(head.neighbour(*direction)).any(&walled))
+    });
+    if hugging {
+        *owed += penalty.0;
+    }
+    let whole = owed.floor();
+    if whole >= 1.0 {
+        *owed -= whole;
+        score.0 = score.0.saturating_sub(whole as u32);
+    }
+}
+
+// on the outermost ring of tiles, so one of the neighbours is the wall
+fn is_edge(pos: Position, grid: GridDimensions) -> bool {
+    pos.x == 0 || pos.y == 0 || pos.x == grid.width as i32 - 1 || pos.y == grid.height as i32 - 1
+}
+
+// in feast mode every eaten fruit is replaced by FEAST_REPLACEMENTS new ones, until FEAST_FOOD_CAP is reached
+fn feast_replacements(feast: bool, food_on_board: usize) -> usize {
+    if feast {
+        FEAST_REPLACEMENTS.min(FEAST_FOOD_CAP.saturating_sub(food_on_board))
+    } else {
+        0
+    }
+}
+
+fn spawn_food(commands: &mut Commands, materials: &Materials, kind: FoodKind, position: Position) -> Entity {
+    let mut food = commands.spawn_bundle(SpriteBundle {
+        material: materials.food_material(kind),
+        ..Default::default()
+    });
+    food
+        .insert(Food)
+        .insert(kind)
+        .insert(position)
+        .insert(Size::square(0.7));
+
+    if kind == FoodKind::Target {
+        food
+            .insert(TargetFood {
+                timer: Timer::from_seconds(TARGET_FOOD_SECONDS, false),
+            })
+            .with_children(|parent| {
+                // drawn just behind the fruit
+                parent.spawn_bundle(SpriteBundle {
+                    material: materials.target_ring_material.clone(),
+                    transform: Transform::from_xyz(0.0, 0.0, -0.1),
+                    ..Default::default()
+                })
+                    .insert(TargetRing)
+                    .insert(Size::square(TARGET_RING_SIZE));
+            });
+    }
+    food.id()
+}
+
+// --slow-decay: a new target fruit gets more time the longer the snek is when it shows up. it is
+// already on the board by then, so its timer is only stretched, not started over.
+fn slow_decay(
+    slow_decay: Res<SlowDecay>,
+    segments: Res<SnekSegments>,
+    mut targets: Query<&mut TargetFood, Added<TargetFood>>,
+) {
+    if !slow_decay.0 {
+        return;
+    }
+    for mut target in targets.iter_mut() {
+        let seconds = TARGET_FOOD_SECONDS * decay_factor(segments.0.len());
+        target.timer.set_duration(Duration::from_secs_f32(seconds));
+    }
+}
+
+fn decay_factor(length: usize) -> f32 {
+    let grown = length.saturating_sub(START_LENGTH) as f32;
+    (1.0 + SLOW_DECAY_PER_SEGMENT * grown).min(MAX_SLOW_DECAY)
+}
+
+// target fruit that wasn't reached in time just disappears. until then it goes from green over
+// yellow to red as its value runs out.
+fn expire_food(
+    mut commands: Commands,
+    delta: Res<FrameDelta>,
+    materials: Res<Materials>,
+    mut targets: Query<(Entity, &mut TargetFood, &mut Handle<ColorMaterial>)>,
+) {
+    for (ent, mut target, mut material) in targets.iter_mut() {
+        if target.timer.tick(delta.0).finished() {
+            commands.entity(ent).despawn_recursive();
+            continue;
+        }
+        let tiered = &materials.target_food_materials[value_tier(&target.timer)];
+        if *material != *tiered {
+            *material = tiered.clone();
+        }
+    }
+}
+
+// which third of its value a target fruit is down to, 0 while it's fresh. in the snake II style that
+// is its points counting down, otherwise it is the time left before all of them are gone.
+fn value_tier(timer: &Timer) -> usize {
+    let tiers = 3;
+    let spent = 1.0 - timer.percent_left();
+    ((spent * tiers as f32) as usize).min(tiers - 1)
+}
+
+// target fruit blinks, and its ring shrinks with the time that's left
+fn target_food_indicator(
+    mut targets: Query<(&TargetFood, &mut Visible, &Children)>,
+    mut rings: Query<&mut Size, With<TargetRing>>,
+) {
+    for (target, mut visible, children) in targets.iter_mut() {
+        visible.is_visible = (target.timer.elapsed_secs() * 2.0).fract() < 0.5;
+        for child in children.iter() {
+            if let Ok(mut size) = rings.get_mut(*child) {
+                *size = Size::square(0.7 + (TARGET_RING_SIZE - 0.7) * target.timer.percent_left());
+            }
+        }
+    }
+}
+
+// Tab moves every fruit on the board somewhere else, as long as there are charges left this round.
+// only the position changes, so a target fruit keeps its countdown and a cluster stays a cluster.
+#[allow(clippy::too_many_arguments)]
+fn food_shuffle(
+    keyboard_input: Res<Input<KeyCode>>,
+    spawn_pattern: Res<SpawnPattern>,
+    mut rng: ResMut<GameRng>,
+    mut charges: ResMut<ShuffleCharges>,
+    mut food: Query<&mut Position, With<Food>>,
+    obstacles: Query<&Position, (With<Obstacle>, Without<Food>)>,
+    snek: Res<Occupied>,
+    blockers: Blockers,
+    (thickness, grid): (Res<Thickness>, Res<GridDimensions>),
+    paused: Res<Paused>,
+    lockstep: Res<Lockstep>,
+) {
+    // recordings only contain turns
+    if paused.0 || lockstep.0 || charges.0 == 0 || !keyboard_input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    charges.0 -= 1;
+
+    // every fruit moves, so the tiles they are on now are free
+    let mut occupied = food_blocked(snek.0.iter(), obstacles.iter(), &blockers, *thickness, *grid);
+    for mut pos in food.iter_mut() {
+        // with nowhere to go a fruit stays where it is
+        let moved = random_free_position(&mut rng.0, &occupied, *spawn_pattern, *grid).unwrap_or(*pos);
+        occupied.insert(moved);
+        *pos = moved;
+    }
+}
+
+// clears the board and slows the game down for a moment, so the player can get out of a tight spot
+fn panic_button(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    paused: Res<Paused>,
+    lockstep: Res<Lockstep>,
+    mut charges: ResMut<PanicCharges>,
+    mut slow_motion: ResMut<SlowMotion>,
+    food: Query<Entity, With<Food>>,
+) {
+    // recordings only contain turns
+    if paused.0 || lockstep.0 || charges.0 == 0 || !keyboard_input.just_pressed(KeyCode::Space) {
+        return;
+    }
+    charges.0 -= 1;
+
+    for ent in food.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+    slow_motion.timer = Some(Timer::from_seconds(PANIC_SLOW_SECONDS, false));
+}
+
+fn panic_charges_text(charges: Res<PanicCharges>, mut texts: Query<&mut Text, With<PanicChargesText>>) {
+    if charges.is_changed() {
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = format!("Panics: {}", charges.0);
+        }
+    }
+}
+
+// score goes back to 0 on game over, which also resets the background
+fn background_system(score: Res<Score>, warning: Res<CapacityWarning>, mut clear_color: ResMut<ClearColor>) {
+    if score.is_changed() || warning.is_changed() {
+        let shift = warning.0 * CAPACITY_BACKGROUND_SHIFT;
+        clear_color.0 = mix_colors(background_color(score.0), CAPACITY_WARNING_COLOR, shift);
+    }
+}
+
+fn background_color(score: u32) -> Color {
+    let last = BACKGROUND_COLORS.len() - 1;
+    let progress = (score as f32 / BACKGROUND_SCORE_STEP).min(last as f32);
+    let index = progress.floor() as usize;
+    let (from, to) = (BACKGROUND_COLORS[index], BACKGROUND_COLORS[(index + 1).min(last)]);
+    mix_colors(from, to, progress - index as f32)
+}
+
+fn mix_colors(from: Color, to: Color, t: f32) -> Color {
+    Color::rgb(
+        from.r() + (to.r() - from.r()) * t,
+        from.g() + (to.g() - from.g()) * t,
+        from.b() + (to.b() - from.b()) * t,
+    )
+}
+
+// textures are stretched to the tile size. pixel art keeps their pixels hard edged,
+// otherwise they are blended smoothly both ways.
+fn texture_filtering(
+    pixel_art: Res<PixelArt>,
+    mut texture_events: EventReader<AssetEvent<Texture>>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    let filter = if pixel_art.0 { FilterMode::Nearest } else { FilterMode::Linear };
+    for event in texture_events.iter() {
+        if let AssetEvent::Created { handle } = event {
+            if let Some(texture) = textures.get_mut(handle) {
+                texture.sampler.mag_filter = filter;
+                texture.sampler.min_filter = filter;
+            }
+        }
+    }
+}
+
+fn window_title(
+    segments: Res<SnekSegments>,
+    score: Res<Score>,
+    leaderboard: Option<Res<leaderboard::Leaderboard>>,
+    mut windows: ResMut<Windows>,
+) {
+    let best_changed = leaderboard.as_ref().is_some_and(|leaderboard| leaderboard.is_changed());
+    if segments.is_changed() || score.is_changed() || best_changed {
+        if let Some(window) = windows.get_primary_mut() {
+            let mut title = format!("{} — len {} — score {}", TITLE, segments.0.len(), score.0);
+            if let Some(leaderboard) = leaderboard {
+                title += &format!(" — best {}", leaderboard.best());
+            }
+            window.set_title(title);
+        }
+    }
+}
+
+// Q asks for confirmation, a second Q within QUIT_CONFIRM_SECONDS closes the game
+fn quit(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut quit_confirm: ResMut<QuitConfirm>,
+    mut transition: ResMut<Transition>,
+    mut texts: Query<&mut Text, With<QuitPromptText>>,
+) {
+    let pressed = keyboard_input.just_pressed(KeyCode::Q);
+    let waiting = match &mut quit_confirm.timer {
+        Some(timer) => !timer.tick(time.delta()).finished(),
+        None => false,
+    };
+
+    let prompt = if pressed && waiting {
+        // fade_transition closes the game once the screen is black
+        transition.start(Fade::ToBlack);
+        quit_confirm.timer = None;
+        None
+    } else if pressed || waiting {
+        if pressed {
+            quit_confirm.timer = Some(Timer::from_seconds(QUIT_CONFIRM_SECONDS, false));
+        }
+        Some("Press Q again to quit")
+    } else {
+        quit_confirm.timer = None;
+        None
+    };
+
+    let prompt = prompt.unwrap_or_default();
+    for mut text in texts.iter_mut() {
+        // only touch the text when it changes, otherwise it's laid out again every frame
+        if text.sections[0].value != prompt {
+            text.sections[0].value = prompt.to_string();
+        }
+    }
+}
+
+// once the last life is gone, the new game fades in from black.
+// runs before game_over, which resets the lives.
+fn fade_in_after_game_over(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    lives: Res<Lives>,
+    mut transition: ResMut<Transition>,
+) {
+    if game_over_reader.iter().next().is_some() && lives.0 <= 1 {
+        transition.start(Fade::FromBlack);
+    }
+}
+
+fn fade_transition(
+    time: Res<Time>,
+    mut transition: ResMut<Transition>,
+    mut exit: EventWriter<AppExit>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    overlays: Query<&Handle<ColorMaterial>, With<FadeOverlay>>,
+) {
+    let direction = transition.direction;
+    let alpha = match &mut transition.timer {
+        Some(timer) => {
+            timer.tick(time.delta());
+            match direction {
+                Fade::ToBlack => timer.percent(),
+                Fade::FromBlack => timer.percent_left(),
+            }
+        }
+        None => return,
+    };
+    for material in overlays.iter() {
+        if let Some(material) = materials.get_mut(material) {
+            material.color = Color::rgba(0.0, 0.0, 0.0, alpha);
+        }
+    }
+
+    if transition.timer.as_ref().is_some_and(|timer| timer.finished()) {
+        match direction {
+            // stays black until the window closes
+            Fade::ToBlack => exit.send(AppExit),
+            Fade::FromBlack => transition.timer = None,
+        }
+    }
+}
+
+fn count_ticks(mut tick: ResMut<TickCount>) {
+    tick.0 += 1;
+}
+
+// runs once per movement step, however long that step was
+fn announce_tick(mut tick_writer: EventWriter<MovementTickEvent>) {
+    tick_writer.send(MovementTickEvent);
+}
+
+// lights up on every movement step and fades out over the first half of the step
+fn tick_indicator(
+    time: Res<Time>,
+    mut tick_reader: EventReader<MovementTickEvent>,
+    movement_timer: Res<MovementTimer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut indicators: Query<(&mut TickIndicator, &Handle<ColorMaterial>)>,
+) {
+    let ticked = tick_reader.iter().count() > 0;
+    for (mut indicator, material) in indicators.iter_mut() {
+        if ticked {
+            indicator.timer.set_duration(movement_timer.0.duration() / 2);
+            indicator.timer.reset();
+        }
+        indicator.timer.tick(time.delta());
+        if let Some(material) = materials.get_mut(material) {
+            let alpha = if indicator.timer.finished() { 0.0 } else { indicator.timer.percent_left() };
+            material.color = Color::rgba(1.0, 1.0, 1.0, alpha);
+        }
+    }
+}
+
+fn spawn_floating_scores(
+    mut commands: Commands,
+    mut eaten_reader: EventReader<FoodEatenEvent>,
+    windows: Res<Windows>,
+    asset_server: Res<AssetServer>,
+    y_axis_down: Res<YAxisDown>,
+    grid: Res<GridDimensions>,
+    border_walls: Res<BorderWalls>,
+) {
+    let window = match usable_window(&windows) {
+        Some(window) => window,
+        None => return,
+    };
+    let (board, inset) = border_walls.drawn(*grid);
+    for eaten in eaten_reader.iter() {
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                format!("+{}", eaten.points),
+                TextStyle {
+                    font: asset_server.load("fonts/DejaVuSans.ttf"),
+                    font_size: 20.0,
+                    color: Color::hex("EFEFEF").unwrap(),
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            transform: Transform::from_xyz(
+                convert(eaten.position.x as f32 + inset, window.width(), board.width as f32),
+                convert_y(eaten.position.y as f32 + inset, window.height(), board, *y_axis_down),
+                2.0,
+            ),
+            ..Default::default()
+        })
+            .insert(FloatingScore {
+                timer: Timer::from_seconds(FLOATING_SCORE_SECONDS, false),
+                velocity: Vec2::new(0.0, FLOATING_SCORE_SPEED),
+            });
+    }
+}
+
+fn count_combo(
+    delta: Res<FrameDelta>,
+    mut eaten_reader: EventReader<FoodEatenEvent>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut combo: ResMut<Combo>,
+) {
+    if game_over_reader.iter().next().is_some() {
+        *combo = Combo::default();
+        return;
+    }
+    combo.tick(delta.0);
+    for _ in eaten_reader.iter() {
+        combo.eat();
+    }
+}
+
+// a glow behind the head that grows and turns hotter with the combo, and goes out when it ends
+fn combo_flame(
+    mut commands: Commands,
+    time: Res<Time>,
+    combo: Res<Combo>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    heads: Query<Entity, With<SnekHead>>,
+    mut flames: Query<(Entity, &Parent, &mut Transform, &Handle<ColorMaterial>), With<ComboFlame>>,
+) {
+    let head = heads.iter().next();
+    let level = combo.level();
+    let mut burning = false;
+    for (flame, parent, mut transform, material) in flames.iter_mut() {
+        // a reversed snek has a new head, the flame doesn't move along with it
+        if level == 0.0 || Some(parent.0) != head {
+            commands.entity(flame).despawn_recursive();
+            continue;
+        }
+        burning = true;
+        let flicker = 1.0 + 0.08 * (time.seconds_since_startup() as f32 * 20.0).sin();
+        transform.scale = Vec3::splat((1.3 + 0.9 * level) * flicker);
+        if let Some(material) = materials.get_mut(material) {
+            material.color = Color::rgba(1.0, 0.7 - 0.5 * level, 0.1, 0.3 + 0.4 * level);
+        }
+    }
+
+    if let (Some(head), false) = (head, burning) {
+        if level > 0.0 {
+            commands.entity(head).with_children(|parent| {
+                parent.spawn_bundle(SpriteBundle {
+                    // every flame gets its own material, since it changes color
+                    material: materials.add(Color::NONE.into()),
+                    // just behind the head
+                    transform: Transform::from_xyz(0.0, 0.0, -0.5),
+                    ..Default::default()
+                })
+                    .insert(Size::square(1.0))
+                    .insert(ComboFlame);
+            });
+        }
+    }
+}
+
+// moves the popups up while fading them out, and clears them all on game over
+fn floating_scores(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut popups: Query<(Entity, &mut FloatingScore, &mut Transform, &mut Text)>,
+) {
+    let game_over = game_over_reader.iter().next().is_some();
+    for (ent, mut popup, mut transform, mut text) in popups.iter_mut() {
+        if game_over || popup.timer.tick(time.delta()).finished() {
+            commands.entity(ent).despawn();
+            continue;
+        }
+        transform.translation += popup.velocity.extend(0.0) * time.delta_seconds();
+        text.sections[0].style.color.set_a(popup.timer.percent_left());
+    }
+}
+
+fn score_multiplier_expiry(
+    delta: Res<FrameDelta>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut multiplier: ResMut<ScoreMultiplier>,
+) {
+    if game_over_reader.iter().next().is_some() {
+        *multiplier = ScoreMultiplier::default();
+    } else if multiplier.factor != 1.0 && multiplier.timer.tick(delta.0).finished() {
+        multiplier.factor = 1.0;
+    }
+}
+
+fn cluster_window(
+    delta: Res<FrameDelta>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut progress: ResMut<ClusterProgress>,
+) {
+    if game_over_reader.iter().next().is_some() {
+        *progress = ClusterProgress::default();
+    } else if progress.cluster.is_some() {
+        progress.timer.tick(delta.0);
+    }
+}
+
+fn score_multiplier_display(
+    multiplier: Res<ScoreMultiplier>,
+    mut texts: Query<&mut Text, With<MultiplierText>>,
+    mut bars: Query<&mut Style, With<MultiplierBar>>,
+) {
+    if !multiplier.is_changed() {
+        return;
+    }
+    let active = multiplier.factor != 1.0;
+    for mut text in texts.iter_mut() {
+        let value = if active { format!("{}x score", multiplier.factor) } else { String::new() };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+    for mut style in bars.iter_mut() {
+        let width = if active { HUD_BAR_WIDTH * multiplier.timer.percent_left() } else { 0.0 };
+        style.size.width = Val::Px(width);
+    }
+}
+
+// fills up until the next fruit spawns
+fn spawn_gauge(
+    spawn_timer: Res<SpawnTimer>,
+    spawn_mode: Res<SpawnMode>,
+    max_food: Res<MaxFood>,
+    food: Query<(), With<Food>>,
+    mut texts: Query<&mut Text, With<SpawnGaugeText>>,
+    mut bars: Query<&mut Style, With<SpawnGaugeBar>>,
+) {
+    let (label, fill) = if spawn_mode.kept_on_board(&max_food).is_some() {
+        ("", 0.0)
+    } else if max_food.reached(food.iter().count()) {
+        ("board full", 1.0)
+    } else {
+        ("next fruit", spawn_timer.0.percent())
+    };
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.to_string();
+        }
+    }
+    for mut style in bars.iter_mut() {
+        style.size.width = Val::Px(HUD_BAR_WIDTH * fill);
+    }
+}
+
+fn shuffle_charges_text(charges: Res<ShuffleCharges>, mut texts: Query<&mut Text, With<ShuffleChargesText>>) {
+    if charges.is_changed() {
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = format!("Shuffles: {}", charges.0);
+        }
+    }
+}
+
+// gives the segments their head, body or tail texture, turned the way the snek is going there.
+// until all textures are loaded (or when one failed to load) the segments keep their solid colors.
+fn segment_textures(
+    textures: Option<Res<SnekTextures>>,
+    asset_server: Res<AssetServer>,
+    segments: Res<SnekSegments>,
+    y_axis_down: Res<YAxisDown>,
+    positions: Query<&Position>,
+    mut sprites: Query<(&mut Handle<ColorMaterial>, &mut Transform), With<SnekSegment>>,
+) {
+    let textures = match textures {
+        Some(textures) if asset_server.get_group_load_state(textures.images.iter().map(|h| h.id)) == LoadState::Loaded => {
+            textures
+        }
+        _ => return,
+    };
+
+    let segment_positions = segments.0.iter()
+        .filter_map(|e| positions.get(*e).ok().copied())
+        .collect::<Vec<Position>>();
+    let last = segments.0.len().saturating_sub(1);
+    for (i, segment) in segments.0.iter().enumerate() {
+        if let Ok((mut material, mut transform)) = sprites.get_mut(*segment) {
+            let wanted = if i == 0 {
+                &textures.head
+            } else if i == last {
+                &textures.tail
+            } else {
+                &textures.body
+            };
+            if *material != *wanted {
+                *material = wanted.clone();
+            }
+
+            // every segment faces the one in front of it, the head faces away from the one behind it
+            let facing = if i == 0 {
+                segment_positions.get(1).and_then(|behind| direction_between(*behind, segment_positions[0]))
+            } else {
+                segment_positions.get(i).and_then(|pos| direction_between(*pos, segment_positions[i - 1]))
+            };
+            if let Some(direction) = facing {
+                let on_screen = if y_axis_down.0 { direction.flipped_vertically() } else { direction };
+                transform.rotation = Quat::from_rotation_z(on_screen.angle());
+            }
+        }
+    }
+}
+
+fn spawn_segment(
+    commands: &mut Commands,
+    material: &Handle<ColorMaterial>,
+    position: Position,
+    segment_style: SegmentStyle,
+) -> Entity {
+    commands.spawn_bundle(SpriteBundle {
+        material: material.clone(),
+        ..Default::default()
+    })
+        .insert(SnekSegment)
+        .insert(position)
+        .insert(segment_style.segment_size())
+        .id()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn snek_eating(mut commands: Commands,
+               (mut growth_writer, mut reverse_writer, mut warp_writer, mut split_writer): (
+                   EventWriter<GrowthEvent>,
+                   EventWriter<ReverseEvent>,
+                   EventWriter<WarpEvent>,
+                   EventWriter<SplitEvent>,
+               ),
+               mut eaten_writer: EventWriter<FoodEatenEvent>,
+               (mut speed_freeze, mut direction_lock): (ResMut<SpeedFreeze>, ResMut<DirectionLock>),
+               mut score: ResMut<Score>,
+               (feast_mode, eat_range, mut cluster_progress): (Res<FeastMode>, Res<EatRange>, ResMut<ClusterProgress>),
+               (spawn_pattern, spawn_bias, grid, thickness):
+                   (Res<SpawnPattern>, Res<SpawnBias>, Res<GridDimensions>, Res<Thickness>),
+               (spawn_mode, max_food): (Res<SpawnMode>, Res<MaxFood>),
+               mut rng: ResMut<GameRng>,
+               materials: Res<Materials>,
+               food_positions: Query<(Entity, &Position, &FoodKind, Option<&TargetFood>, Option<&FoodCluster>), With<Food>>,
+               heads: Query<(&Position, &SnekHead)>,
+               (snek, obstacles): (Res<Occupied>, Query<&Position, With<Obstacle>>),
+               mut multiplier: ResMut<ScoreMultiplier>,
+               (wild, fleeing, momentum, style, mut fruits_eaten, mut script): (
+                   Res<WildMode>,
+                   Res<FleeingFood>,
+                   Res<MomentumFood>,
+                   Res<GameStyle>,
+                   Local<u32>,
+                   Option<ResMut<food_script::FoodScript>>,
+               ),
+               (rival_tiles, boss_tiles, mut room_walls, mut objective, keys, doors): (
+                   Res<RivalTiles>,
+                   Res<BossTiles>,
+                   ResMut<RoomWalls>,
+                   ResMut<keys::Objective>,
+                   Query<(Entity, &Position, &keys::Key)>,
+                   Query<(Entity, &Position, &keys::DoorWall)>,
+               ),
+) {
+    let mut food_on_board = food_positions.iter().count();
+    let mut occupied = thickness.covered(snek.0.iter().chain(food_positions.iter().map(|(_, pos, _, _, _)| pos)));
+    occupied.extend(obstacles.iter());
+    occupied.extend(rival_tiles.0.iter());
+    occupied.extend(boss_tiles.0.iter());
+    occupied.extend(room_walls.0.iter());
+    occupied.extend(objective.taken.iter());
+    let mut occupied = thickness.blocked_anchors(occupied, *grid);
+
+    for (head_pos, _) in heads.iter() {
+        // --keys: a key is picked up like a fruit, and opens its door
+        for (ent, key_pos, key) in keys.iter() {
+            if thickness.footprint(*head_pos).any(|pos| pos == *key_pos) {
+                commands.entity(ent).despawn();
+                objective.taken.remove(key_pos);
+                keys::open(&mut commands, key.door, &doors, &mut room_walls);
+                growth_writer.send(GrowthEvent);
+            }
+        }
+        let in_reach = fruit_in_reach(
+            *head_pos,
+            food_positions.iter().map(|(ent, pos, _, _, _)| (ent, *pos)),
+            eat_range.0,
+            *thickness,
+        );
+        for (ent, food_pos, kind, target, cluster) in food_positions.iter() {
+            if Some(ent) == in_reach {
+                commands.entity(ent).despawn_recursive();
+                food_on_board -= 1;
+                let points = match (*style, target) {
+                    (GameStyle::SnakeII, Some(target)) => countdown_points(&target.timer),
+                    _ => kind.points(),
+                };
+                let mut points = multiplier.apply(points);
+                if cluster.is_some_and(|cluster| cluster_progress.eat(cluster.0)) {
+                    points += multiplier.apply(CLUSTER_BONUS_POINTS);
+                }
+                score.0 += points;
+                eaten_writer.send(FoodEatenEvent {
+                    position: *food_pos,
+                    points,
+                });
+                match kind {
+                    FoodKind::Regular | FoodKind::Target | FoodKind::Fleeing => growth_writer.send(GrowthEvent),
+                    FoodKind::Freeze => {
+                        growth_writer.send(GrowthEvent);
+                        // eating another freeze food restarts the timer
+                        speed_freeze.timer = Some(Timer::from_seconds(SPEED_FREEZE_SECONDS, false));
+                    }
+                    FoodKind::Reverse => reverse_writer.send(ReverseEvent),
+                    FoodKind::Warp => warp_writer.send(WarpEvent),
+                    FoodKind::Split => split_writer.send(SplitEvent),
+                    FoodKind::Momentum => {
+                        growth_writer.send(GrowthEvent);
+                        // another one starts the lock over
+                        direction_lock.timer = Some(Timer::from_seconds(DIRECTION_LOCK_SECONDS, false));
+                    }
+                    FoodKind::DoubleScore => {
+                        growth_writer.send(GrowthEvent);
+                        // another one refreshes the time, the factor doesn't stack
+                        multiplier.factor = DOUBLE_SCORE_FACTOR;
+                        multiplier.timer = Timer::from_seconds(DOUBLE_SCORE_SECONDS, false);
+                    }
+                }
+
+                let mut replacements = feast_replacements(feast_mode.0, food_on_board);
+                if let Some(kept) = spawn_mode.kept_on_board(&max_food) {
+                    replacements = replacements.max(kept.saturating_sub(food_on_board));
+                }
+                for _ in 0..replacements {
+                    let spawned = spawn_random_food(
+                        &mut commands,
+                        &materials,
+                        &mut rng.0,
+                        &mut occupied,
+                        *spawn_pattern,
+                        spawn_bias.forward(heads.iter()),
+                        (*wild, *fleeing, *momentum),
+                        *style,
+                        *grid,
+                        script.as_deref_mut(),
+                    );
+                    if spawned {
+                        food_on_board += 1;
+                    }
+                }
+
+                if *style == GameStyle::SnakeII && *kind == FoodKind::Regular {
+                    *fruits_eaten += 1;
+                    if fruits_eaten.is_multiple_of(SNAKE_II_BONUS_EVERY) {
+                        if let Some(pos) = random_free_position(&mut rng.0, &occupied, *spawn_pattern, *grid) {
+                            occupied.insert(pos);
+                            spawn_food(&mut commands, &materials, FoodKind::Target, pos);
+                            food_on_board += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the fruit the head eats: the closest one at most `range` tiles away, counted along the grid.
+// with the default range of 0 that is only a fruit right under the head.
+fn fruit_in_reach(
+    head: Position,
+    food: impl Iterator<Item = (Entity, Position)>,
+    range: u32,
+    thickness: Thickness,
+) -> Option<Entity> {
+    food.map(|(ent, pos)| (ent, thickness.gap(head, pos)))
+        .filter(|(_, distance)| *distance <= range)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(ent, _)| ent)
+}
+
+// the head jumps, the body stays where it is. every segment takes the place of the one in front of it
+// as usual, so over the next ticks the body follows the head through the warp, one segment per tick.
+#[allow(clippy::too_many_arguments)]
+fn snek_warp(
+    mut warp_reader: EventReader<WarpEvent>,
+    mut rng: ResMut<GameRng>,
+    mut occupied: ResMut<Occupied>,
+    spawn_pattern: Res<SpawnPattern>,
+    mut heads: Query<(&SnekHead, &mut Position)>,
+    food: Query<&Position, (With<Food>, Without<SnekHead>)>,
+    obstacles: Query<&Position, (With<Obstacle>, Without<SnekHead>)>,
+    rival_tiles: Res<RivalTiles>,
+    grid: Res<GridDimensions>,
+) {
+    if warp_reader.iter().next().is_none() {
+        return;
+    }
+    if let Some((head, mut head_pos)) = heads.iter_mut().next() {
+        let mut blocked = occupied.0.clone();
+        blocked.extend(food.iter());
+        blocked.extend(obstacles.iter());
+        blocked.extend(rival_tiles.0.iter());
+        let blocked = warp_blocked(&blocked, head.direction, *grid);
+        let destination = random_free_position(&mut rng.0, &blocked, *spawn_pattern, *grid);
+        if let Some(destination) = destination {
+            occupied.0.remove(&head_pos);
+            occupied.0.insert(destination);
+            *head_pos = destination;
+        }
+    }
+}
+
+// tiles the head can't be warped to: taken ones, and ones where the very next step would be fatal
+fn warp_blocked(taken: &HashSet<Position>, direction: Direction, grid: GridDimensions) -> HashSet<Position> {
+    grid.tiles()
+        .filter(|pos| {
+            let next = pos.neighbour(direction);
+            taken.contains(pos) || !next.in_bounds(grid) || taken.contains(&next)
+        })
+        .collect()
+}
+
+// the back half of the snek breaks off and becomes a rival that moves on its own
+#[allow(clippy::too_many_arguments)]
+fn snek_split(
+    mut commands: Commands,
+    mut split_reader: EventReader<SplitEvent>,
+    mut segments: ResMut<SnekSegments>,
+    mut occupied: ResMut<Occupied>,
+    mut rival_tiles: ResMut<RivalTiles>,
+    mut last_tail_position: ResMut<LastTailPosition>,
+    mut movement_timer: ResMut<MovementTimer>,
+    speed_freeze: Res<SpeedFreeze>,
+    (difficulty, adaptive): (Res<Difficulty>, Res<AdaptiveDifficulty>),
+    materials: Res<Materials>,
+    positions: Query<&Position>,
+) {
+    if split_reader.iter().next().is_none() || segments.0.len() < SPLIT_MIN_LENGTH {
+        return;
+    }
+
+    let keep = segments.0.len().div_ceil(2);
+    let back = segments.0.split_off(keep);
+    let back_positions = back.iter()
+        .map(|e| *positions.get(*e).unwrap())
+        .collect::<Vec<Position>>();
+    for pos in back_positions.iter() {
+        occupied.0.remove(pos);
+        rival_tiles.0.insert(*pos);
+    }
+    for segment in back.iter() {
+        commands.entity(*segment)
+            .remove::<SnekSegment>()
+            .insert(RivalSegment)
+            .insert(materials.rival_material.clone());
+    }
+    // the new head sits where the split happened, so it first points towards the front half.
+    // rival_movement steers it away before it moves.
+    let direction = direction_between(back_positions[1], back_positions[0]).unwrap_or(Direction::Up);
+    commands.entity(back[0]).insert(RivalSnek {
+        segments: back,
+        direction,
+    });
+
+    last_tail_position.0 = None;
+    if speed_freeze.timer.is_none() {
+        movement_timer.0.set_duration(movement_interval(segments.0.len(), *difficulty, adaptive.factor));
+    }
+}
+
+// rivals wander towards the fruit like the benchmark autopilot, without eating it.
+// a rival that has nowhere left to go falls apart.
+#[allow(clippy::too_many_arguments)]
+fn rival_movement(
+    mut commands: Commands,
+    mut rival_tiles: ResMut<RivalTiles>,
+    snek: Res<Occupied>,
+    grid: Res<GridDimensions>,
+    obstacles: Query<&Obstacle>,
+    food: Query<&Position, (With<Food>, Without<RivalSegment>)>,
+    mut rivals: Query<(Entity, &mut RivalSnek)>,
+    mut positions: Query<&mut Position, With<RivalSegment>>,
+) {
+    let food = food.iter().copied().collect::<Vec<Position>>();
+    for (rival_entity, mut rival) in rivals.iter_mut() {
+        let mut blocked = snek.0.clone();
+        blocked.extend(rival_tiles.0.iter());
+        blocked.extend(obstacles.iter().map(|o| o.position()));
+
+        let segment_positions = rival.segments.iter()
+            .map(|e| *positions.get_mut(*e).unwrap())
+            .collect::<Vec<Position>>();
+        let direction = match steer(segment_positions[0], rival.direction, &blocked, &food, *grid) {
+            Some(direction) => direction,
+            None => {
+                for segment in rival.segments.iter() {
+                    commands.entity(*segment).despawn_recursive();
+                }
+                commands.entity(rival_entity).remove::<RivalSnek>();
+                for pos in segment_positions.iter() {
+                    rival_tiles.0.remove(pos);
+                }
+                continue;
+            }
+        };
+
+        rival.direction = direction;
+        let new_head = segment_positions[0].neighbour(direction);
+        rival_tiles.0.remove(segment_positions.last().unwrap());
+        rival_tiles.0.insert(new_head);
+        *positions.get_mut(rival.segments[0]).unwrap() = new_head;
+        for (pos, segment) in segment_positions.iter().zip(rival.segments.iter().skip(1)) {
+            *positions.get_mut(*segment).unwrap() = *pos;
+        }
+    }
+}
+
+// one greedy step towards the closest fruit, never into a blocked tile and never straight back
+fn steer(
+    head: Position,
+    direction: Direction,
+    blocked: &HashSet<Position>,
+    food: &[Position],
+    grid: GridDimensions,
+) -> Option<Direction> {
+    let distance_to_food = |pos: Position| {
+        food.iter()
+            .map(|f| (f.x - pos.x).abs() + (f.y - pos.y).abs())
+            .min()
+            .unwrap_or(0)
+    };
+
+    [Direction::Left, Direction::Right, Direction::Up, Direction::Down].iter()
+        .filter(|candidate| **candidate != direction.opposite())
+        .map(|candidate| (*candidate, head.neighbour(*candidate)))
+        .filter(|(_, next)| next.in_bounds(grid) && !blocked.contains(next))
+        .min_by_key(|(_, next)| distance_to_food(*next))
+        .map(|(candidate, _)| candidate)
+}
+
+// --fleeing-food: every tick each fleeing fruit takes a step away from the head. it only ever
+// stays as close or gets further away, so it can only be caught once it has nowhere left to go.
+fn flee_from_head(
+    fleeing: Res<FleeingFood>,
+    grid: Res<GridDimensions>,
+    snek: Res<Occupied>,
+    rival_tiles: Res<RivalTiles>,
+    heads: Query<&Position, (With<SnekHead>, Without<Food>)>,
+    obstacles: Query<&Obstacle>,
+    mut food: Query<(&mut Position, &FoodKind), With<Food>>,
+) {
+    if !fleeing.0 {
+        return;
+    }
+    let head = match heads.iter().next() {
+        Some(head) => *head,
+        None => return,
+    };
+    let mut blocked = snek.0.clone();
+    blocked.extend(rival_tiles.0.iter());
+    blocked.extend(obstacles.iter().map(|obstacle| obstacle.position()));
+    blocked.extend(food.iter_mut().map(|(pos, _)| *pos));
+
+    for (mut pos, kind) in food.iter_mut() {
+        if *kind != FoodKind::Fleeing {
+            continue;
+        }
+        if let Some(next) = flee_step(*pos, head, &blocked, *grid) {
+            blocked.remove(&*pos);
+            blocked.insert(next);
+            *pos = next;
+        }
+    }
+}
+
+// the free neighbour furthest from the head, if there is one further away than the fruit is now
+fn flee_step(food: Position, head: Position, blocked: &HashSet<Position>, grid: GridDimensions) -> Option<Position> {
+    let distance = |pos: Position| (pos.x - head.x).abs() + (pos.y - head.y).abs();
+    [Direction::Left, Direction::Right, Direction::Up, Direction::Down].iter()
+        .map(|direction| food.neighbour(*direction))
+        .filter(|next| next.in_bounds(grid) && !blocked.contains(next))
+        .filter(|next| distance(*next) > distance(food))
+        .max_by_key(|next| distance(*next))
+}
+
+// --boss-fruit: puts a boss down once enough fruit was eaten since the last one. it goes as far from
+// the head as it fits, which needs no randomness, so seeded games don't change.
+#[allow(clippy::too_many_arguments)]
+fn spawn_boss(
+    mut commands: Commands,
+    boss_fruits: Res<BossFruits>,
+    materials: Res<Materials>,
+    mut eaten_reader: EventReader<FoodEatenEvent>,
+    (snek, rival_tiles, grid): (Res<Occupied>, Res<RivalTiles>, Res<GridDimensions>),
+    mut boss_tiles: ResMut<BossTiles>,
+    heads: Query<&Position, With<SnekHead>>,
+    food: Query<&Position, With<Food>>,
+    obstacles: Query<&Obstacle>,
+    mut fruits_eaten: Local<u32>,
+) {
+    if !boss_fruits.0 {
+        return;
+    }
+    // eating the boss counts too, the next one is just as far off
+    *fruits_eaten += eaten_reader.iter().count() as u32;
+    if *fruits_eaten < BOSS_EVERY_FRUITS || !boss_tiles.0.is_empty() {
+        return;
+    }
+    let head = match heads.iter().next() {
+        Some(head) => *head,
+        None => return,
+    };
+    let mut blocked = snek.0.clone();
+    blocked.extend(rival_tiles.0.iter());
+    blocked.extend(food.iter());
+    // the obstacle's whole patrol, so it never walks into the boss
+    blocked.extend(obstacles.iter().flat_map(|obstacle| obstacle.path.iter()));
+    if let Some(corner) = boss_spot(head, &blocked, *grid) {
+        *fruits_eaten = 0;
+        commands.spawn().insert(BossFruit { hp: BOSS_HP });
+        for pos in boss_footprint(corner) {
+            boss_tiles.0.insert(pos);
+            commands.spawn_bundle(SpriteBundle {
+                material: materials.boss_materials[BOSS_HP as usize - 1].clone(),
+                ..Default::default()
+            })
+                .insert(BossTile)
+                .insert(pos)
+                .insert(Size::square(1.0));
+        }
+    }
+}
+
+// the four tiles of a boss, from its bottom left one
+fn boss_footprint(corner: Position) -> [Position; 4] {
+    let Position { x, y } = corner;
+    [corner, Position { x: x + 1, y }, Position { x, y: y + 1 }, Position { x: x + 1, y: y + 1 }]
+}
+
+// the bottom left corner of the free 2x2 spot furthest from the head
+fn boss_spot(head: Position, blocked: &HashSet<Position>, grid: GridDimensions) -> Option<Position> {
+    let distance = |pos: &Position| (pos.x - head.x).abs() + (pos.y - head.y).abs();
+    grid.tiles()
+        .filter(|corner| boss_footprint(*corner).iter().all(|pos| pos.in_bounds(grid) && !blocked.contains(pos)))
+        .max_by_key(distance)
+}
+
+// every tick the head pushes against a boss takes one hp off it, the last one eats it.
+// snek_eating is full up on params, so this is its own system.
+#[allow(clippy::too_many_arguments)]
+fn hit_boss(
+    mut commands: Commands,
+    materials: Res<Materials>,
+    mut bump_reader: EventReader<BossBumpEvent>,
+    mut boss_tiles: ResMut<BossTiles>,
+    mut bosses: Query<(Entity, &mut BossFruit)>,
+    mut tiles: Query<(Entity, &mut Handle<ColorMaterial>), With<BossTile>>,
+    (mut score, multiplier): (ResMut<Score>, Res<ScoreMultiplier>),
+    mut eaten_writer: EventWriter<FoodEatenEvent>,
+    mut growth_writer: EventWriter<GrowthEvent>,
+) {
+    let bumped = bump_reader.iter().next().map(|bump| bump.0);
+    let (bumped, (boss_entity, mut boss)) = match (bumped, bosses.iter_mut().next()) {
+        (Some(bumped), Some(boss)) => (bumped, boss),
+        _ => return,
+    };
+    boss.hp = boss.hp.saturating_sub(1);
+    if boss.hp > 0 {
+        // it pales with every hit
+        for (_, mut material) in tiles.iter_mut() {
+            *material = materials.boss_materials[boss.hp as usize - 1].clone();
+        }
+        return;
+    }
+    commands.entity(boss_entity).despawn();
+    for (tile, _) in tiles.iter_mut() {
+        commands.entity(tile).despawn();
+    }
+    boss_tiles.0.clear();
+    let points = multiplier.apply(BOSS_POINTS);
+    score.0 += points;
+    eaten_writer.send(FoodEatenEvent { position: bumped, points });
+    growth_writer.send(GrowthEvent);
+}
+
+fn clear_boss(
+    mut commands: Commands,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut boss_tiles: ResMut<BossTiles>,
+    bosses: Query<Entity, Or<(With<BossFruit>, With<BossTile>)>>,
+) {
+    if game_over_reader.iter().next().is_some() {
+        for ent in bosses.iter() {
+            commands.entity(ent).despawn();
+        }
+        boss_tiles.0.clear();
+    }
+}
+
+fn clear_rivals(
+    mut commands: Commands,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut rival_tiles: ResMut<RivalTiles>,
+    rivals: Query<Entity, With<RivalSegment>>,
+) {
+    if game_over_reader.iter().next().is_some() {
+        for ent in rivals.iter() {
+            commands.entity(ent).despawn_recursive();
+        }
+        rival_tiles.0.clear();
+    }
+}
+
+// swaps head and tail. the new head keeps moving away from the body.
+fn snek_reverse(
+    mut commands: Commands,
+    mut reverse_reader: EventReader<ReverseEvent>,
+    mut segments: ResMut<SnekSegments>,
+    mut heads: Query<&mut SnekHead>,
+    positions: Query<&Position>,
+    materials: Res<Materials>,
+    segment_style: Res<SegmentStyle>,
+) {
+    if reverse_reader.iter().next().is_none() || segments.0.is_empty() {
+        return;
+    }
+
+    let old_head = segments.0[0];
+    segments.0.reverse();
+    let new_head = segments.0[0];
+
+    if let Ok(mut head) = heads.get_mut(old_head) {
+        let direction = match segments.0.get(1) {
+            Some(neck) => direction_between(*positions.get(*neck).unwrap(), *positions.get(new_head).unwrap())
+                .unwrap_or_else(|| head.direction.opposite()),
+            None => head.direction.opposite(),
+        };
+
+        if new_head == old_head {
+            // a snek without a body just turns around
+            head.direction = direction;
+            head.next_direction = direction;
+            return;
+        }
+
+        commands.entity(old_head)
+            .remove::<SnekHead>()
+            .insert(materials.segment_material.clone())
+            .insert(segment_style.segment_size());
+        commands.entity(new_head)
+            .insert(SnekHead {
+                direction,
+                next_direction: direction,
+            })
+            .insert(materials.head_material.clone())
+            .insert(Size::square(0.8));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn snek_growth(
+    mut commands: Commands,
+    (difficulty, adaptive): (Res<Difficulty>, Res<AdaptiveDifficulty>),
+    last_tail_position: Res<LastTailPosition>,
+    mut segments: ResMut<SnekSegments>,
+    mut occupied: ResMut<Occupied>,
+    mut growth_reader: EventReader<GrowthEvent>,
+    mut movement_timer: ResMut<MovementTimer>,
+    speed_freeze: Res<SpeedFreeze>,
+    materials: Res<Materials>,
+    grow_delay: Res<EatGrowDelay>,
+    segment_style: Res<SegmentStyle>,
+    mut pending: ResMut<PendingGrowth>,
+    style: Res<GameStyle>,
+    fixed_length: Res<FixedSnakeLength>,
+) {
+    for _ in growth_reader.iter() {
+        // at the cap the fruit still scores, the snek just doesn't get longer.
+        // growth that is already on its way counts, so the snek never ends up past the cap.
+        if fixed_length.reached(segments.0.len() + pending.0.len()) {
+            continue;
+        }
+        // snake II grows once the fruit has gone all the way down to the tail
+        let delay = match *style {
+            GameStyle::Classic => grow_delay.0,
+            GameStyle::SnakeII => segments.0.len().saturating_sub(1) as u32,
+        };
+        pending.eat(delay);
+    }
+    // right after a split there is no free tile behind the tail until the snek moves again
+    if let Some(position) = last_tail_position.0.filter(|_| pending.tick()) {
+        occupied.0.insert(position);
+        segments.0.push(spawn_segment(
+            &mut commands,
+            &materials.segment_material,
+            position,
+            *segment_style,
+        ));
+        // while frozen, the speed stays where it was. speed_freeze_expiry catches up afterwards.
+        if speed_freeze.timer.is_none() {
+            movement_timer.0.set_duration(movement_interval(segments.0.len(), *difficulty, adaptive.factor));
+        }
+    }
+}
+
+fn direction_lock_expiry(delta: Res<FrameDelta>, mut direction_lock: ResMut<DirectionLock>) {
+    if direction_lock.timer.as_mut().is_some_and(|timer| timer.tick(delta.0).finished()) {
+        direction_lock.timer = None;
+    }
+}
+
+fn speed_freeze_expiry(
+    delta: Res<FrameDelta>,
+    (difficulty, adaptive): (Res<Difficulty>, Res<AdaptiveDifficulty>),
+    segments: Res<SnekSegments>,
+    mut speed_freeze: ResMut<SpeedFreeze>,
+    mut movement_timer: ResMut<MovementTimer>,
+) {
+    let expired = match &mut speed_freeze.timer {
+        Some(timer) => timer.tick(delta.0).finished(),
+        None => false,
+    };
+    if expired {
+        speed_freeze.timer = None;
+        movement_timer.0.set_duration(movement_interval(segments.0.len(), *difficulty, adaptive.factor));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn game_over(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut commands: Commands,
+    materials: Res<Materials>,
+    segments_res: ResMut<SnekSegments>,
+    occupied: ResMut<Occupied>,
+    (center_start, start_length, grid, thickness):
+        (Res<CenterStart>, Res<StartLength>, Res<GridDimensions>, Res<Thickness>),
+    segment_style: Res<SegmentStyle>,
+    (difficulty, adaptive): (Res<Difficulty>, Res<AdaptiveDifficulty>),
+    mut movement_timer: ResMut<MovementTimer>,
+    (mut speed_freeze, mut direction_lock): (ResMut<SpeedFreeze>, ResMut<DirectionLock>),
+    mut score: ResMut<Score>,
+    (mut shuffle_charges, mut panic_charges): (ResMut<ShuffleCharges>, ResMut<PanicCharges>),
+    (mut lives, death_anim, mut stopwatch): (ResMut<Lives>, Option<ResMut<DeathAnim>>, ResMut<Stopwatch>),
+    mut invulnerability: ResMut<Invulnerability>,
+    food: Query<Entity, With<Food>>,
+    segments: Query<Entity, With<SnekSegment>>,
+) {
+    if game_over_reader.iter().next().is_some() {
+        match death_anim {
+            // the dead snek is only left for dissolve to take apart, the game goes on without it
+            Some(mut death_anim) => {
+                for ent in segments_res.0.iter() {
+                    commands.entity(*ent).remove::<SnekSegment>().remove::<SnekHead>().remove::<Position>();
+                }
+                death_anim.start(&mut commands, &segments_res.0);
+            }
+            None => {
+                for ent in segments.iter() {
+                    commands.entity(ent).despawn_recursive();
+                }
+            }
+        }
+
+        speed_freeze.timer = None;
+        direction_lock.timer = None;
+        movement_timer.0.set_duration(movement_interval(START_LENGTH, *difficulty, adaptive.factor));
+
+        if lives.0 > 1 {
+            // lose a life: the snek starts over, but the board and score stay
+            lives.0 -= 1;
+            invulnerability.timer = Some(Timer::from_seconds(INVULNERABLE_SECONDS, false));
+        } else {
+            for ent in food.iter() {
+                commands.entity(ent).despawn_recursive();
+            }
+            score.0 = 0;
+            shuffle_charges.0 = SHUFFLE_CHARGES;
+            panic_charges.0 = PANIC_CHARGES;
+            lives.0 = START_LIVES;
+            invulnerability.timer = None;
+            stopwatch.0 = Duration::ZERO;
+        }
+
+        spawn_snek(
+            commands,
+            materials,
+            segments_res,
+            occupied,
+            center_start,
+            segment_style,
+            start_length,
+            (grid, thickness),
+        );
+    }
+}
+
+fn invulnerability_expiry(delta: Res<FrameDelta>, mut invulnerability: ResMut<Invulnerability>) {
+    let expired = match &mut invulnerability.timer {
+        Some(timer) => timer.tick(delta.0).finished(),
+        None => false,
+    };
+    if expired {
+        invulnerability.timer = None;
+    }
+}
+
+// sends a NearMissEvent on the tick the head comes up right next to something that would end the game.
+// only on that tick, so running along a wall doesn't keep flashing.
+#[allow(clippy::too_many_arguments)]
+fn detect_near_miss(
+    grid: Res<GridDimensions>,
+    wall_mode: Res<WallMode>,
+    occupied: Res<Occupied>,
+    rival_tiles: Res<RivalTiles>,
+    heads: Query<(&SnekHead, &Position)>,
+    obstacles: Query<&Obstacle>,
+    mut near_miss_writer: EventWriter<NearMissEvent>,
+    mut was_near: Local<bool>,
+) {
+    let near = heads.iter().any(|(head, head_pos)| {
+        let mut blocked = occupied.0.clone();
+        blocked.extend(rival_tiles.0.iter());
+        blocked.extend(obstacles.iter().map(|o| o.position()));
+        next_to_danger(*head_pos, head.direction, &blocked, *grid, *wall_mode == WallMode::Die)
+    });
+    if near && !*was_near {
+        near_miss_writer.send(NearMissEvent);
+    }
+    *was_near = near;
+}
+
+// looks to the front and both sides of the head. behind it is the neck, which it can't turn into.
+fn next_to_danger(
+    head: Position,
+    direction: Direction,
+    blocked: &HashSet<Position>,
+    grid: GridDimensions,
+    walls_are_deadly: bool,
+) -> bool {
+    [Direction::Left, Direction::Right, Direction::Up, Direction::Down].iter()
+        .filter(|side| **side != direction.opposite())
+        .map(|side| head.neighbour(*side))
+        .any(|next| if next.in_bounds(grid) { blocked.contains(&next) } else { walls_are_deadly })
+}
+
+// how close the board is to being full: 0 with at least CAPACITY_WARNING_FREE of it free, up to 1
+// with no free tile left
+fn capacity_warning(snek: Res<Occupied>, grid: Res<GridDimensions>, mut warning: ResMut<CapacityWarning>) {
+    if !snek.is_changed() && !grid.is_changed() {
+        return;
+    }
+    let total = (grid.width * grid.height) as usize;
+    let level = capacity_level(total.saturating_sub(snek.0.len()), total);
+    // only touched when it changes, the background redraws on every change
+    if warning.0 != level {
+        warning.0 = level;
+    }
+}
+
+fn capacity_level(free: usize, total: usize) -> f32 {
+    let free_share = free as f32 / total as f32;
+    (1.0 - free_share / CAPACITY_WARNING_FREE).max(0.0)
+}
+
+// red when a life is lost, yellow on a near miss. a near miss doesn't cut a red flash short.
+// between flashes the border pulses while the board is nearly full, see capacity_warning.
+#[allow(clippy::too_many_arguments)]
+fn border_flash(
+    time: Res<Time>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut near_miss_reader: EventReader<NearMissEvent>,
+    mut flash: ResMut<BorderFlash>,
+    warning: Res<CapacityWarning>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    strips: Query<&Handle<ColorMaterial>, With<BorderFlashStrip>>,
+    mut pulse: Local<Option<f32>>,
+) {
+    let near_miss = near_miss_reader.iter().count() > 0;
+    if game_over_reader.iter().next().is_some() {
+        flash.start(DEATH_FLASH_COLOR);
+    } else if near_miss && (flash.timer.finished() || flash.color != DEATH_FLASH_COLOR) {
+        flash.start(NEAR_MISS_FLASH_COLOR);
+    }
+
+    // a flash takes over the border, the pulse picks up again once it has faded
+    let color = if flash.timer.finished() {
+        // once faded out and with no warning there's nothing to update until the next flash
+        if warning.0 == 0.0 && pulse.is_none() {
+            return;
+        }
+        let mut color = CAPACITY_WARNING_COLOR;
+        if warning.0 == 0.0 {
+            *pulse = None;
+            color.set_a(0.0);
+        } else {
+            // the phase carries on when the rate changes, so the pulse speeds up without jumping
+            let (slowest, fastest) = CAPACITY_PULSE_RATES;
+            let rate = slowest + (fastest - slowest) * warning.0;
+            let phase = (pulse.unwrap_or(0.0) + time.delta_seconds() * rate).fract();
+            *pulse = Some(phase);
+            let strength = 0.3 + 0.5 * warning.0;
+            color.set_a(strength * (0.5 - 0.5 * (phase * std::f32::consts::TAU).cos()));
+        }
+        color
+    } else {
+        flash.timer.tick(time.delta());
+        let mut color = flash.color;
+        color.set_a(if flash.timer.finished() { 0.0 } else { 0.8 * flash.timer.percent_left() });
+        color
+    };
+    for material in strips.iter() {
+        if let Some(material) = materials.get_mut(material) {
+            material.color = color;
+        }
+    }
+}
+
+// takes the snek that just died apart, from the tail to the head. in real time, since game time
+// stands still during the fade and the instant replay.
+fn dissolve(mut commands: Commands, time: Res<Time>, death_anim: Option<ResMut<DeathAnim>>) {
+    if let Some(mut death_anim) = death_anim {
+        for ent in death_anim.advance(time.delta()) {
+            commands.entity(*ent).despawn_recursive();
+        }
+    }
+}
+
+// the snek blinks while it's invulnerable
+fn invulnerability_flash(invulnerability: Res<Invulnerability>, mut segments: Query<&mut Visible, With<SnekSegment>>) {
+    if !invulnerability.is_changed() {
+        return;
+    }
+    let visible = match &invulnerability.timer {
+        Some(timer) => (timer.elapsed_secs() * 8.0).fract() < 0.5,
+        None => true,
+    };
+    for mut segment in segments.iter_mut() {
+        segment.is_visible = visible;
+    }
+}
+
+// K copies the seed, so the same game can be played again with --seed
+fn seed_text(
+    seed: Res<Seed>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut texts: Query<&mut Text, With<SeedText>>,
+    mut copied: Local<Option<bool>>,
+) {
+    let pressed = keyboard_input.just_pressed(KeyCode::K);
+    if pressed {
+        let copy = copy_to_clipboard(&seed.0.to_string());
+        if let Err(e) = &copy {
+            eprintln!("snek: could not copy the seed: {}", e);
+        }
+        *copied = Some(copy.is_ok());
+    }
+    // the caption starts out empty, so it is set on the first frame too
+    for mut text in texts.iter_mut() {
+        if pressed || text.sections[0].value.is_empty() {
+            text.sections[0].value = seed_caption(seed.0, *copied);
+        }
+    }
+}
+
+// hands the text to the desktop's own clipboard tool, the first one that is there
+fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let tools: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[("wl-copy", &[]), ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])]
+    };
+    let mut last_error = std::io::Error::new(std::io::ErrorKind::NotFound, "no clipboard tool");
+    for (program, args) in tools {
+        let mut child = match Command::new(program).args(*args).stdin(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                last_error = e;
+                continue;
+            }
+        };
+        child.stdin.take().expect("stdin is piped").write_all(text.as_bytes())?;
+        return match child.wait()? {
+            status if status.success() => Ok(()),
+            status => Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{} failed: {}", program, status))),
+        };
+    }
+    Err(last_error)
+}
+
+fn seed_caption(seed: u64, copied: Option<bool>) -> String {
+    match copied {
+        None => format!("Seed {} (K to copy)", seed),
+        Some(true) => format!("Seed {} (copied)", seed),
+        // no clipboard, e.g. without a desktop session. the seed can still be copied by hand.
+        Some(false) => format!("Seed {} (no clipboard)", seed),
+    }
+}
+
+// --time-limit: when the clock runs out the game is over, however many lives are left. it runs on
+// game time, so it stands still while paused, during fades and during the instant replay.
+fn time_limit(
+    limit: Res<TimeLimit>,
+    delta: Res<FrameDelta>,
+    score: Res<Score>,
+    mut lives: ResMut<Lives>,
+    mut stopwatch: ResMut<Stopwatch>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    mut time_up_writer: EventWriter<TimeUpEvent>,
+) {
+    stopwatch.0 += delta.0;
+    if limit.time_left(&stopwatch) == Some(Duration::ZERO) {
+        // the last life, so game_over ends the game instead of just starting the snek over.
+        // it also starts the stopwatch over.
+        lives.0 = 1;
+        game_over_writer.send(GameOverEvent);
+        time_up_writer.send(TimeUpEvent { score: score.0 });
+    }
+}
+
+// the clock while playing, the final score for a while once it ran out
+fn time_limit_text(
+    time: Res<Time>,
+    limit: Res<TimeLimit>,
+    stopwatch: Res<Stopwatch>,
+    mut time_up_reader: EventReader<TimeUpEvent>,
+    mut texts: Query<&mut Text, With<TimeLimitText>>,
+    mut final_score: Local<Option<(u32, Timer)>>,
+) {
+    if let Some(time_up) = time_up_reader.iter().last() {
+        *final_score = Some((time_up.score, Timer::from_seconds(TIME_UP_MESSAGE_SECONDS, false)));
+    }
+    if final_score.as_mut().is_some_and(|(_, timer)| timer.tick(time.delta()).finished()) {
+        *final_score = None;
+    }
+    let caption = match (&*final_score, limit.time_left(&stopwatch)) {
+        (Some((score, _)), _) => format!("Time's up! Final score: {}", score),
+        (None, Some(left)) => time_left_caption(left),
+        (None, None) => String::new(),
+    };
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != caption {
+            text.sections[0].value = caption.clone();
+        }
+    }
+}
+
+// whole seconds, rounded up, so the clock shows 0:00 only once the time is really up
+fn time_left_caption(left: Duration) -> String {
+    let seconds = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+    format!("Time {}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn lives_text(lives: Res<Lives>, mut texts: Query<&mut Text, With<LivesText>>) {
+    if lives.is_changed() {
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = format!("Lives: {}", lives.0);
+        }
+    }
+}
+
+// components are plain structs: bevy 0.5 treats every Send + Sync + 'static type as a component.
+// newer bevy versions need #[derive(Component)] on each of them (and on Position and Size).
+struct SnekHead {
+    direction: Direction,
+    next_direction: Direction
+}
+
+struct Food;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum FoodKind {
+    Regular,
+    // stops the snek from speeding up while it grows, for SPEED_FREEZE_SECONDS
+    Freeze,
+    // turns the snek around: the tail becomes the head. doesn't make the snek grow.
+    Reverse,
+    // worth TARGET_FOOD_POINTS, but only around for TARGET_FOOD_SECONDS
+    Target,
+    // every fruit is worth DOUBLE_SCORE_FACTOR times as much, for DOUBLE_SCORE_SECONDS
+    DoubleScore,
+    // moves the head to a random free tile, the body follows through one segment per tick.
+    // doesn't make the snek grow.
+    Warp,
+    // the back half of the snek breaks off and turns into a rival (see snek_split)
+    Split,
+    // steps away from the head every tick (see flee_from_head), worth FLEEING_FOOD_POINTS
+    Fleeing,
+    // the snek keeps going straight for DIRECTION_LOCK_SECONDS, see DirectionLock
+    Momentum,
+}
+
+impl FoodKind {
+    fn points(self) -> u32 {
+        match self {
+            FoodKind::Target => TARGET_FOOD_POINTS,
+            FoodKind::Fleeing => FLEEING_FOOD_POINTS,
+            _ => FOOD_POINTS,
+        }
+    }
+}
+
+// in the snake II style a bonus loses worth while it waits, from TARGET_FOOD_POINTS down to FOOD_POINTS
+fn countdown_points(timer: &Timer) -> u32 {
+    let bonus = (TARGET_FOOD_POINTS - FOOD_POINTS) as f32 * timer.percent_left();
+    FOOD_POINTS + bonus.round() as u32
+}
+
+struct TargetFood {
+    timer: Timer,
+}
+
+struct TargetRing;
+
+// This struct is used like a tag, so we can query for it later.
+// the headless benchmark uses the default (empty) handles.
+#[derive(Default)]
+struct Materials {
+    // This struct will be a resource which stores materials for various components.
+    head_material: Handle<ColorMaterial>,
+    food_material: Handle<ColorMaterial>,
+    freeze_food_material: Handle<ColorMaterial>,
+    reverse_food_material: Handle<ColorMaterial>,
+    warp_food_material: Handle<ColorMaterial>,
+    split_food_material: Handle<ColorMaterial>,
+    fleeing_food_material: Handle<ColorMaterial>,
+    momentum_food_material: Handle<ColorMaterial>,
+    rival_material: Handle<ColorMaterial>,
+    // by the boss's hp, the last one for a fresh boss
+    boss_materials: [Handle<ColorMaterial>; BOSS_HP as usize],
+    // from a fresh target fruit to one that is about to go, see value_tier
+    target_food_materials: [Handle<ColorMaterial>; 3],
+    target_ring_material: Handle<ColorMaterial>,
+    double_score_food_material: Handle<ColorMaterial>,
+    cluster_food_material: Handle<ColorMaterial>,
+    segment_material: Handle<ColorMaterial>,
+    obstacle_material: Handle<ColorMaterial>,
+    // the two shades of --checkerboard, see checker_shade
+    checker_materials: [Handle<ColorMaterial>; 2],
+    fog_material: Handle<ColorMaterial>,
+    border_warning_material: Handle<ColorMaterial>,
+    // --keys
+    key_material: Handle<ColorMaterial>,
+    door_material: Handle<ColorMaterial>,
+    goal_material: Handle<ColorMaterial>,
+    hitbox_material: Handle<ColorMaterial>,
+    path_materials: Vec<Handle<ColorMaterial>>,
+}
+
+impl Materials {
+    fn food_material(&self, kind: FoodKind) -> Handle<ColorMaterial> {
+        match kind {
+            FoodKind::Regular => self.food_material.clone(),
+            FoodKind::Freeze => self.freeze_food_material.clone(),
+            FoodKind::Reverse => self.reverse_food_material.clone(),
+            FoodKind::Warp => self.warp_food_material.clone(),
+            FoodKind::Split => self.split_food_material.clone(),
+            FoodKind::Fleeing => self.fleeing_food_material.clone(),
+            FoodKind::Momentum => self.momentum_food_material.clone(),
+            FoodKind::Target => self.target_food_materials[0].clone(),
+            FoodKind::DoubleScore => self.double_score_food_material.clone(),
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize)]
+enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// the direction of a single step from one tile to a neighbouring one
+fn direction_between(from: Position, to: Position) -> Option<Direction> {
+    match (to.x - from.x, to.y - from.y) {
+        (-1, 0) => Some(Direction::Left),
+        (1, 0) => Some(Direction::Right),
+        (0, 1) => Some(Direction::Up),
+        (0, -1) => Some(Direction::Down),
+        _ => None,
+    }
+}
+
+impl Direction {
+    fn opposite(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+        }
+    }
+
+    // a quarter turn clockwise, with up being up on the board
+    fn clockwise(self) -> Self {
+        match self {
+            Self::Up => Self::Right,
+            Self::Right => Self::Down,
+            Self::Down => Self::Left,
+            Self::Left => Self::Up,
+        }
+    }
+
+    fn flipped_vertically(self) -> Self {
+        match self {
+            Self::Up | Self::Down => self.opposite(),
+            _ => self,
+        }
+    }
+
+    // counterclockwise rotation of a sprite drawn facing up
+    fn angle(self) -> f32 {
+        match self {
+            Self::Up => 0.0,
+            Self::Left => std::f32::consts::FRAC_PI_2,
+            Self::Down => std::f32::consts::PI,
+            Self::Right => -std::f32::consts::FRAC_PI_2,
+        }
+    }
+}
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+enum SnekMovement {
+    Input,
+    Recording,
+    Obstacles,
+    Movement,
+    Eating,
+    Rivals,
+    Warp,
+    Split,
+    Reverse,
+    Growth,
+    GameOver,
+}
+
+struct SnekSegment;
+
+#[derive(Default)]
+struct SnekSegments(Vec<Entity>);
+
+// the size of the board in tiles. can be changed in the pause menu, see grid_resize_menu.
+#[derive(PartialEq, Copy, Clone, Debug)]
+struct GridDimensions {
+    width: u32,
+    height: u32,
+}
+
+impl Default for GridDimensions {
+    fn default() -> Self {
+        GridDimensions {
+            width: WIDTH,
+            height: HEIGHT,
+        }
+    }
+}
+
+impl GridDimensions {
+    fn tiles(self) -> impl Iterator<Item = Position> {
+        (0..self.width as i32).flat_map(move |x| (0..self.height as i32).map(move |y| Position { x, y }))
+    }
+
+    fn clamped(self) -> Self {
+        GridDimensions {
+            width: self.width.max(GRID_LIMITS.0).min(GRID_LIMITS.1),
+            height: self.height.max(GRID_LIMITS.0).min(GRID_LIMITS.1),
+        }
+    }
+}
+
+impl std::fmt::Display for GridDimensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+// the board size picked in the pause menu, until it is applied
+#[derive(Default)]
+struct GridResize(Option<GridDimensions>);
+
+struct GrowthEvent;
+
+// the boss tile the head was stopped by
+struct BossBumpEvent(Position);
+
+struct ReverseEvent;
+
+struct WarpEvent;
+
+struct SplitEvent;
+
+// a broken off half of the snek. sits on the rival's head and lists all of its segments, head first.
+struct RivalSnek {
+    segments: Vec<Entity>,
+    direction: Direction,
+}
+
+struct RivalSegment;
+
+// every tile covered by a rival, so the snek and the spawners can avoid them
+#[derive(Default)]
+struct RivalTiles(HashSet<Position>);
+
+// --boss-fruit lets bosses show up, see spawn_boss
+struct BossFruits(bool);
+
+// how many more bumps the boss takes. its tiles are separate entities, see BossTile.
+struct BossFruit {
+    hp: u32,
+}
+
+// one of the four sprites of the boss
+struct BossTile;
+
+// the tiles the boss covers, so the snek and the spawners can go around it
+#[derive(Default)]
+struct BossTiles(HashSet<Position>);
+
+// --wild lets split fruit spawn
+#[derive(Copy, Clone)]
+struct WildMode(bool);
+
+// --eat-range: how far from the head a fruit can be and still get eaten, see fruit_in_reach
+struct EatRange(u32);
+
+// --fleeing-food lets fleeing fruit spawn
+#[derive(Copy, Clone)]
+struct FleeingFood(bool);
+
+// --momentum-food lets momentum fruit spawn
+#[derive(Copy, Clone)]
+struct MomentumFood(bool);
+
+// see slow_decay
+struct SlowDecay(bool);
+
+// how many movement ticks after eating the new segment shows up
+struct EatGrowDelay(u32);
+
+// a set of rules, picked with --style. snake II, after the old phones: one fruit at a time,
+// no special fruit, a bonus every SNAKE_II_BONUS_EVERY fruits that is worth more the sooner
+// it's eaten, and the snek only grows once the fruit reaches its tail.
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum GameStyle {
+    #[default]
+    Classic,
+    SnakeII,
+}
+
+impl std::str::FromStr for GameStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(Self::Classic),
+            "snake2" => Ok(Self::SnakeII),
+            _ => Err(()),
+        }
+    }
+}
+
+// ticks left until each eaten fruit turns into a segment
+#[derive(Default)]
+struct PendingGrowth(Vec<u32>);
+
+impl PendingGrowth {
+    fn eat(&mut self, delay: u32) {
+        self.0.push(delay);
+    }
+
+    // true if the next tick grows a segment on the tile the tail leaves
+    fn due_next_tick(&self) -> bool {
+        self.0.contains(&0)
+    }
+
+    // one movement tick. returns true if a segment is due now.
+    // there is only one free tile behind the tail per tick, so at most one segment grows at a time.
+    fn tick(&mut self) -> bool {
+        let due = match self.0.iter().position(|ticks| *ticks == 0) {
+            Some(index) => {
+                self.0.remove(index);
+                true
+            }
+            None => false,
+        };
+        for ticks in self.0.iter_mut() {
+            *ticks = ticks.saturating_sub(1);
+        }
+        due
+    }
+}
+
+#[derive(Default)]
+struct LastTailPosition(Option<Position>);
+
+// every tile covered by the snek. kept up to date as the snek moves, so collision checks and
+// finding free tiles don't have to look at every segment.
+#[derive(Default)]
+struct Occupied(HashSet<Position>);
+
+impl Occupied {
+    // one movement step: the tail leaves its tile, the head enters a new one
+    fn advance(&mut self, old_tail: Position, new_head: Position) {
+        self.0.remove(&old_tail);
+        self.0.insert(new_head);
+    }
+}
+
+struct MovementTimer(Timer);
+
+struct SpawnTimer(Timer);
+
+// how much game time passed since the last frame
+#[derive(Default)]
+struct FrameDelta(Duration);
+
+// movement ticks since the game started
+#[derive(Default)]
+struct TickCount(u64);
+
+// on while recording or replaying: game time only advances in whole movement ticks (see frame_delta)
+struct Lockstep(bool);
+
+// all randomness in the game goes through here, so a seed reproduces a game
+struct GameRng(StdRng);
+
+// the seed GameRng started from, shown on screen
+struct Seed(u64);
+
+struct SeedText;
+
+#[derive(Default)]
+struct Score(u32);
+
+// how many times the food can still be shuffled this round
+struct ShuffleCharges(u32);
+
+struct ShuffleChargesText;
+
+// how many times the panic button (space) can still be used this round
+struct PanicCharges(u32);
+
+struct PanicChargesText;
+
+// game time runs at PANIC_TIME_SCALE until the timer (in real time) runs out
+#[derive(Default)]
+struct SlowMotion {
+    timer: Option<Timer>,
+}
+
+#[derive(Default)]
+struct QuitConfirm {
+    // running while the game waits for the second Q
+    timer: Option<Timer>,
+}
+
+struct QuitPromptText;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum Fade {
+    ToBlack,
+    FromBlack,
+}
+
+// a running fade. game time stands still until it is done.
+struct Transition {
+    timer: Option<Timer>,
+    direction: Fade,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Transition {
+            timer: None,
+            direction: Fade::FromBlack,
+        }
+    }
+}
+
+impl Transition {
+    fn start(&mut self, direction: Fade) {
+        self.timer = Some(Timer::from_seconds(FADE_SECONDS, false));
+        self.direction = direction;
+    }
+}
+
+// full screen black node, see fade_transition
+struct FadeOverlay;
+
+struct Lives(u32);
+
+struct LivesText;
+
+// --time-limit, in seconds of game time
+struct TimeLimit(Option<f32>);
+
+impl TimeLimit {
+    fn time_left(&self, stopwatch: &Stopwatch) -> Option<Duration> {
+        self.0.map(|limit| Duration::from_secs_f32(limit).saturating_sub(stopwatch.0))
+    }
+}
+
+// game time played since the game started
+#[derive(Default)]
+struct Stopwatch(Duration);
+
+// the clock of --time-limit ran out. score is the final one, from before game_over resets it.
+struct TimeUpEvent {
+    score: u32,
+}
+
+struct TimeLimitText;
+
+struct DrillText;
+
+struct KeysText;
+
+#[derive(Default)]
+struct Invulnerability {
+    timer: Option<Timer>,
+}
+
+// --death-anim: what happens to the snek when it dies
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum DeathAnimation {
+    // it dissolves segment by segment, see DeathAnim
+    #[default]
+    Dissolve,
+    // it is gone right away
+    Off,
+}
+
+impl std::str::FromStr for DeathAnimation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dissolve" => Ok(Self::Dissolve),
+            "off" => Ok(Self::Off),
+            _ => Err(()),
+        }
+    }
+}
+
+// the segments of the snek that died last, tail first. only there with --death-anim dissolve,
+// without it game_over despawns the snek right away.
+#[derive(Default)]
+struct DeathAnim {
+    segments: Vec<Entity>,
+    timer: Timer,
+    next_index: usize,
+}
+
+impl DeathAnim {
+    fn start(&mut self, commands: &mut Commands, head_first: &[Entity]) {
+        // a snek that died before the last one was gone doesn't have to wait for it
+        for ent in self.segments.drain(self.next_index..) {
+            commands.entity(ent).despawn_recursive();
+        }
+        self.segments = head_first.iter().rev().copied().collect();
+        self.next_index = 0;
+        let step = DISSOLVE_STEP_SECONDS.min(DISSOLVE_MAX_SECONDS / self.segments.len().max(1) as f32);
+        self.timer = Timer::from_seconds(step, true);
+    }
+
+    // the segments that are due to go, a long frame can take more than one
+    fn advance(&mut self, delta: Duration) -> &[Entity] {
+        let due = self.timer.tick(delta).times_finished() as usize;
+        let from = self.next_index;
+        self.next_index = (from + due).min(self.segments.len());
+        &self.segments[from..self.next_index]
+    }
+}
+
+// --max-food: the spawn timer doesn't add fruit while this many are on the board
+struct MaxFood(Option<usize>);
+
+impl MaxFood {
+    fn reached(&self, food_on_board: usize) -> bool {
+        self.0.is_some_and(|max| food_on_board >= max)
+    }
+}
+
+// --fixed-length: the snek stops growing once it is this long
+struct FixedSnakeLength(Option<u32>);
+
+impl FixedSnakeLength {
+    fn reached(&self, length: usize) -> bool {
+        self.0.is_some_and(|max| length >= max as usize)
+    }
+}
+
+// --spawn-gauge shows how long until the next fruit spawns
+struct ShowSpawnGauge(bool);
+
+struct SpawnGaugeText;
+
+struct SpawnGaugeBar;
+
+// --thickness: every segment and fruit covers a block of this many by this many tiles, with its
+// Position in the bottom left corner. the snek moves a whole block per step, so its segments stay
+// on one lattice and never overlap: two of them collide exactly when they share a Position, the same
+// as with a thickness of 1. walls, obstacles, rivals, the boss and fruit are checked against the
+// whole block.
+#[derive(Copy, Clone)]
+struct Thickness(u32);
+
+impl Thickness {
+    fn footprint(self, anchor: Position) -> impl Iterator<Item = Position> {
+        let k = self.0 as i32;
+        (0..k).flat_map(move |dx| (0..k).map(move |dy| Position { x: anchor.x + dx, y: anchor.y + dy }))
+    }
+
+    fn step(self, anchor: Position, direction: Direction) -> Position {
+        (0..self.0).fold(anchor, |pos, _| pos.neighbour(direction))
+    }
+
+    fn fits(self, anchor: Position, grid: GridDimensions) -> bool {
+        self.footprint(anchor).all(|pos| pos.in_bounds(grid))
+    }
+
+    // tiles between two blocks, counted along the grid. 0 when they overlap.
+    fn gap(self, a: Position, b: Position) -> u32 {
+        let apart = |d: i32| d.unsigned_abs().saturating_sub(self.0 - 1);
+        apart(b.x - a.x) + apart(b.y - a.y)
+    }
+
+    // every tile under the blocks at these anchors
+    fn covered<'a>(self, anchors: impl Iterator<Item = &'a Position>) -> HashSet<Position> {
+        anchors.flat_map(|anchor| self.footprint(*anchor)).collect()
+    }
+
+    // the anchors a new block can't go on without covering one of these tiles or hanging off the
+    // board. with a thickness of 1 that is just the tiles themselves.
+    fn blocked_anchors(self, covered: HashSet<Position>, grid: GridDimensions) -> HashSet<Position> {
+        if self.0 == 1 {
+            return covered;
+        }
+        let back = self.0 as i32 - 1;
+        let mut blocked = covered.iter()
+            .flat_map(|tile| self.footprint(Position { x: tile.x - back, y: tile.y - back }))
+            .collect::<HashSet<Position>>();
+        blocked.extend(grid.tiles().filter(|anchor| !self.fits(*anchor, grid)));
+        blocked
+    }
+}
+
+// --batch-sprites: position_translation and size_scaling only touch the sprites whose tile or size
+// changed, instead of all of them every frame. helps with very long sneks.
+struct BatchSprites(bool);
+
+// on unless --no-focus-pause, see pause_on_focus_loss
+struct FocusPause(bool);
+
+// on unless --no-pixel-snap: the lines between the tiles are put on whole physical pixels, see
+// tile_line. otherwise a window with a display scale like 1.5 or 2 leaves hairline gaps between tiles
+// that should touch.
+struct PixelSnap(bool);
+
+// --stretch: how much further back each body segment lags behind on turns, see position_translation
+struct StretchFactor(f32);
+
+// how the body segments are drawn, picked with --segment-style
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum SegmentStyle {
+    // small squares with gaps between them, so each segment can be told apart
+    #[default]
+    Beaded,
+    // every segment fills its whole tile, so the body is one solid shape
+    Solid,
+}
+
+impl SegmentStyle {
+    fn segment_size(self) -> Size {
+        match self {
+            Self::Beaded => Size::square(0.5),
+            Self::Solid => Size::square(1.0),
+        }
+    }
+}
+
+// --wall-wrap: a segment that went over the edge slides off the board there first, then comes
+// back in on the other side. only the drawing, the segment is already on its new tile.
+struct WrapSlide {
+    from: Position,
+    direction: Direction,
+    timer: Timer,
+}
+
+impl WrapSlide {
+    // in tiles, just off the board halfway through
+    fn at(&self, to: Position) -> Vec2 {
+        let next = self.from.neighbour(self.direction);
+        let step = Vec2::new((next.x - self.from.x) as f32, (next.y - self.from.y) as f32);
+        let t = self.timer.percent();
+        if t < 0.5 {
+            Vec2::new(self.from.x as f32, self.from.y as f32) + step * t * 2.0
+        } else {
+            Vec2::new(to.x as f32, to.y as f32) - step * (1.0 - t) * 2.0
+        }
+    }
+}
+
+// the slides position_translation is drawing, kept between frames
+#[derive(Default)]
+struct WrapSlides {
+    // where each segment was last frame, to notice the ones that just went over the edge
+    last: HashMap<Entity, Position>,
+    sliding: HashMap<Entity, WrapSlide>,
+    // slides that ended this frame. their segments still have to be put back onto their tile.
+    landed: Vec<Entity>,
+}
+
+impl WrapSlides {
+    fn update(&mut self, segments: &[(Entity, Position)], grid: GridDimensions, delta: Duration) {
+        for (entity, pos) in segments {
+            let from = match self.last.get(entity) {
+                Some(from) => *from,
+                None => continue,
+            };
+            if let Some(direction) = wrap_direction(from, *pos, grid) {
+                let timer = Timer::from_seconds(WRAP_SLIDE_SECONDS, false);
+                self.sliding.insert(*entity, WrapSlide { from, direction, timer });
+            }
+        }
+        let landed = &mut self.landed;
+        landed.clear();
+        self.sliding.retain(|entity, slide| {
+            let done = slide.timer.tick(delta).finished();
+            if done {
+                landed.push(*entity);
+            }
+            !done
+        });
+        self.last = segments.iter().copied().collect();
+    }
+}
+
+// the size a sprite is drawn at while it grows or shrinks towards its Size, see size_scaling
+struct SizeTween {
+    from: Size,
+    timer: Timer,
+}
+
+impl SizeTween {
+    fn current(&self, to: &Size) -> Size {
+        let t = self.timer.percent();
+        Size {
+            width: self.from.width + (to.width - self.from.width) * t,
+            height: self.from.height + (to.height - self.from.height) * t,
+        }
+    }
+}
+
+impl std::str::FromStr for SegmentStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beaded" => Ok(Self::Beaded),
+            "solid" => Ok(Self::Solid),
+            _ => Err(()),
+        }
+    }
+}
+
+// --co-op: two players steer the same snek, one with WASD and one with the arrow keys. this is how
+// their turns are put together on every movement tick.
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum InputAggregation {
+    // the turn that was asked for last
+    LastWins,
+    // only a turn both players asked for
+    Consensus,
+}
+
+impl std::str::FromStr for InputAggregation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last-wins" => Ok(Self::LastWins),
+            "consensus" => Ok(Self::Consensus),
+            _ => Err(()),
+        }
+    }
+}
+
+// None unless --co-op
+struct CoOp(Option<InputAggregation>);
+
+// --co-op: the turn each player asked for since the last movement tick
+#[derive(Default)]
+struct TurnVotes {
+    votes: [Option<Direction>; 2],
+    latest: Option<usize>,
+}
+
+impl TurnVotes {
+    fn vote(&mut self, player: usize, turn: Direction) {
+        self.votes[player] = Some(turn);
+        self.latest = Some(player);
+    }
+
+    // the turn the votes add up to. the next tick starts from scratch.
+    fn count(&mut self, aggregation: InputAggregation) -> Option<Direction> {
+        let turn = match aggregation {
+            InputAggregation::LastWins => self.latest.and_then(|player| self.votes[player]),
+            InputAggregation::Consensus if self.votes[0] == self.votes[1] => self.votes[0],
+            InputAggregation::Consensus => None,
+        };
+        *self = TurnVotes::default();
+        turn
+    }
+}
+
+// --co-op, for the system that collects the votes and the one that counts them
+#[derive(SystemParam)]
+struct CoOpTurns<'a> {
+    aggregation: Res<'a, CoOp>,
+    votes: ResMut<'a, TurnVotes>,
+}
+
+impl<'a> CoOpTurns<'a> {
+    fn on(&self) -> bool {
+        self.aggregation.0.is_some()
+    }
+
+    // the turn for this tick, None without --co-op
+    fn count(&mut self) -> Option<Direction> {
+        let aggregation = self.aggregation.0?;
+        self.votes.count(aggregation)
+    }
+}
+
+// --steering: absolute turns the snek towards the key's direction (WASD), relative turns it a quarter
+// turn from where it is heading, clockwise with D and counterclockwise with A
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum SteeringMode {
+    #[default]
+    Absolute,
+    Relative,
+}
+
+impl std::str::FromStr for SteeringMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "absolute" => Ok(Self::Absolute),
+            "relative" => Ok(Self::Relative),
+            _ => Err(()),
+        }
+    }
+}
+
+// points taken off the score per tick the head is next to a wall, see wall_hug_penalty
+struct WallHugPenalty(f32);
+
+// when on, eating a fruit spawns replacements right away (see feast_replacements)
+struct FeastMode(bool);
+
+// the last PATH_HISTORY_LENGTH head positions, newest first
+#[derive(Default)]
+struct PathHistory(VecDeque<Position>);
+
+// toggled with H
+struct ShowPath(bool);
+
+// toggled with T
+struct ShowTailHint(bool);
+
+// turned on with --debug, enables teleport_to_mouse, grow_on_key and toggle_hitboxes
+struct DebugKeys(bool);
+
+// toggled with O, with --debug
+struct ShowHitboxes(bool);
+
+// --border-walls: a ring of walls drawn right outside the board, so the deadly edge can be seen.
+// the board itself doesn't change, it is only drawn a bit smaller to make room for the walls.
+#[derive(Copy, Clone)]
+struct BorderWalls(bool);
+
+impl BorderWalls {
+    // the board as drawn, walls included, and how many tiles in from its edge row and column 0 are
+    fn drawn(self, grid: GridDimensions) -> (GridDimensions, f32) {
+        let inset = self.0 as u32;
+        let board = GridDimensions {
+            width: grid.width + 2 * inset,
+            height: grid.height + 2 * inset,
+        };
+        (board, inset as f32)
+    }
+}
+
+struct Wall;
+
+// --checkerboard: the board is drawn in two alternating shades instead of a flat color
+struct Checkerboard(bool);
+
+struct CheckerTile;
+
+// --border-warning, see fit_border_warning
+struct ShowBorderWarning(bool);
+
+struct BorderWarningTile;
+
+// --vision: how far from the head the board can be seen, in tiles. None sees all of it.
+struct VisionRadius(Option<u32>);
+
+struct FogTile;
+
+struct TailHint;
+
+// see growth_preview
+struct GrowthPreview;
+
+// --y-down puts grid row 0 at the top, like the rows of a map file.
+// only the drawing and the keys change, the grid itself works the same either way.
+#[derive(Copy, Clone)]
+struct YAxisDown(bool);
+
+// toggled with P
+struct Paused(bool);
+
+struct PausedText;
+
+// toggled with I while paused, see stats_screen
+struct ShowStats(bool);
+
+struct StatsText;
+
+// the current hint of the first launch tutorial
+struct TutorialText;
+
+// shown over the replay of the game that just ended
+struct InstantReplayText;
+
+// the camera that shows the board, as opposed to the one for the UI
+struct MainCamera;
+
+struct PathLine;
+
+// one edge of the outline draw_hitboxes puts around a taken tile
+struct HitboxEdge;
+
+// one of the beads corner_fills puts into the bends of the body
+struct CornerFill;
+
+// --textured draws the snek with the images in assets/textures instead of plain squares
+struct Textured(bool);
+
+// --pixel-art turns off anti-aliasing and texture smoothing for a crisp look
+struct PixelArt(bool);
+
+struct SnekTextures {
+    head: Handle<ColorMaterial>,
+    body: Handle<ColorMaterial>,
+    tail: Handle<ColorMaterial>,
+    images: [Handle<Texture>; 3],
+}
+
+// can be changed mid-game with C
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn next(self) -> Self {
+        match self {
+            Self::Easy => Self::Normal,
+            Self::Normal => Self::Hard,
+            Self::Hard => Self::Easy,
+        }
+    }
+
+    fn speed_factor(self) -> f64 {
+        match self {
+            Self::Easy => 0.7,
+            Self::Normal => 1.0,
+            Self::Hard => 1.4,
+        }
+    }
+
+    // fruits per second
+    fn spawn_rate(self) -> f32 {
+        match self {
+            Self::Easy => SPAWN_RATE * 1.5,
+            Self::Normal => SPAWN_RATE,
+            Self::Hard => SPAWN_RATE * 0.6,
+        }
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "normal" => Ok(Self::Normal),
+            "hard" => Ok(Self::Hard),
+            _ => Err(()),
+        }
+    }
+}
+
+struct DifficultyText;
+
+// move as soon as a turn is pressed instead of waiting for the next tick
+struct InstantTurn(bool);
+
+// --spawn-bias: on top of the SpawnPattern, whether fruit only goes ahead of the head
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum SpawnBias {
+    #[default]
+    Anywhere,
+    // only in the half of the board in front of the head, while there is room there
+    Forward,
+}
+
+impl SpawnBias {
+    fn forward<'a>(self, mut heads: impl Iterator<Item = (&'a Position, &'a SnekHead)>) -> Option<(Position, Direction)> {
+        match self {
+            Self::Anywhere => None,
+            Self::Forward => heads.next().map(|(pos, head)| (*pos, head.direction)),
+        }
+    }
+}
+
+impl std::str::FromStr for SpawnBias {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anywhere" => Ok(Self::Anywhere),
+            "forward" => Ok(Self::Forward),
+            _ => Err(()),
+        }
+    }
+}
+
+// where food_spawner puts new fruit. occupied tiles are always avoided.
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum SpawnPattern {
+    #[default]
+    Uniform,
+    // only on the outermost ring of tiles
+    EdgesOnly,
+    // anywhere, but more likely near the middle
+    CenterBias,
+}
+
+impl std::str::FromStr for SpawnPattern {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Self::Uniform),
+            "edges" => Ok(Self::EdgesOnly),
+            "center" => Ok(Self::CenterBias),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum SpawnMode {
+    // a new fruit every so often
+    #[default]
+    Timed,
+    // a new fruit only once the last one was eaten
+    OnDemand,
+    // every eaten fruit is replaced right away, so there are always --max-food of them (or one)
+    Continuous,
+}
+
+impl SpawnMode {
+    // how many fruit are kept on the board without waiting for the spawn timer, None if the timer spawns them
+    fn kept_on_board(self, max_food: &MaxFood) -> Option<usize> {
+        match self {
+            Self::Timed => None,
+            Self::OnDemand => Some(1),
+            Self::Continuous => Some(max_food.0.unwrap_or(1)),
+        }
+    }
+}
+
+impl std::str::FromStr for SpawnMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timed" => Ok(Self::Timed),
+            "on-demand" => Ok(Self::OnDemand),
+            "continuous" => Ok(Self::Continuous),
+            _ => Err(()),
+        }
+    }
+}
+
+// start in the middle of the grid instead of near the bottom left corner
+struct CenterStart(bool);
+
+// how many segments the snek starts with, head included. 1 is just the head.
+struct StartLength(usize);
+
+// what happens when the head runs into a wall
+#[derive(PartialEq)]
+enum WallMode {
+    Die,
+    // the snek turns around and keeps going
+    Bounce,
+    // the snek comes back in on the other side of the board
+    Wrap,
+}
+
+// what happens when the head runs into its own body
+enum TailCollisionMode {
+    Die,
+    // the snek survives but loses everything behind the bite
+    Truncate,
+}
+
+// every score gain is multiplied by factor until the timer runs out
+struct ScoreMultiplier {
+    factor: f32,
+    timer: Timer,
+}
+
+impl Default for ScoreMultiplier {
+    fn default() -> Self {
+        ScoreMultiplier {
+            factor: 1.0,
+            timer: Timer::from_seconds(0.0, false),
+        }
+    }
+}
+
+impl ScoreMultiplier {
+    fn apply(&self, points: u32) -> u32 {
+        (points as f32 * self.factor).round() as u32
+    }
+}
+
+// the chance for a timed spawn to be a cluster, from --cluster-chance
+struct ClusterChance(f32);
+
+// the fruit of one cluster all carry the same number
+struct FoodCluster(u32);
+
+// how much of the last cluster the snek ate from was eaten, and how long ago the first fruit of it went.
+// only one cluster counts at a time, moving on to another one starts over.
+#[derive(Default)]
+struct ClusterProgress {
+    cluster: Option<u32>,
+    eaten: usize,
+    timer: Timer,
+}
+
+impl ClusterProgress {
+    // true when this fruit finishes the cluster in time
+    fn eat(&mut self, cluster: u32) -> bool {
+        if self.cluster != Some(cluster) || self.timer.finished() {
+            self.cluster = Some(cluster);
+            self.eaten = 0;
+            self.timer = Timer::from_seconds(CLUSTER_SECONDS, false);
+        }
+        self.eaten += 1;
+        self.eaten == CLUSTER_SIZE
+    }
+}
+
+// only changes with --adaptive, the rest of the time the speed curve stays flat
+struct AdaptiveMode(bool);
+
+// scales the speed on top of the difficulty, within ADAPTIVE_LIMITS
+struct AdaptiveDifficulty {
+    factor: f64,
+}
+
+impl Default for AdaptiveDifficulty {
+    fn default() -> Self {
+        Self { factor: 1.0 }
+    }
+}
+
+// fruits eaten in a row, each within COMBO_SECONDS of the last
+#[derive(Default)]
+struct Combo {
+    count: u32,
+    timer: Timer,
+}
+
+impl Combo {
+    fn eat(&mut self) {
+        self.count += 1;
+        self.timer = Timer::from_seconds(COMBO_SECONDS, false);
+    }
+
+    fn tick(&mut self, delta: Duration) {
+        if self.timer.tick(delta).finished() {
+            self.count = 0;
+        }
+    }
+
+    // 0 until the second fruit in a row, then up to 1 at COMBO_FLAME_MAX
+    fn level(&self) -> f32 {
+        self.count.saturating_sub(1).min(COMBO_FLAME_MAX - 1) as f32 / (COMBO_FLAME_MAX - 1) as f32
+    }
+}
+
+struct ComboFlame;
+
+struct MultiplierText;
+
+struct MultiplierBar;
+
+#[derive(Default)]
+struct SpeedFreeze {
+    timer: Option<Timer>,
+}
+
+// after a momentum fruit the head can't turn until the timer is up. turns asked for in the meantime
+// still go to next_direction, so the last one is taken on the first tick after the lock.
+#[derive(Default)]
+struct DirectionLock {
+    timer: Option<Timer>,
+}
+
+// walks along its path one tile per movement tick, looping back to the start
+struct Obstacle {
+    path: Vec<Position>,
+    index: usize,
+}
+
+impl Obstacle {
+    fn position(&self) -> Position {
+        self.path[self.index]
+    }
+
+    // obstacles move before the snek, so the head either runs into the obstacle's new tile
+    // or the two swap tiles and pass through each other, which counts as well
+    fn hits(&self, old_head: Position, new_head: Position, thickness: Thickness) -> bool {
+        let previous = self.path[(self.index + self.path.len() - 1) % self.path.len()];
+        let (current, new_block) = (self.position(), thickness.footprint(new_head).collect::<Vec<Position>>());
+        new_block.contains(&current)
+            || (new_block.contains(&previous) && thickness.footprint(old_head).any(|pos| pos == current))
+    }
+}
+
+struct GameOverEvent;
+
+struct MovementTickEvent;
+
+// --tick-indicator shows a dot that flashes with every movement step
+struct ShowTickIndicator(bool);
+
+struct TickIndicator {
+    timer: Timer,
+}
+
+struct FoodEatenEvent {
+    position: Position,
+    points: u32,
+}
+
+struct NearMissEvent;
+
+// a fading colored border around the window, see border_flash
+struct BorderFlash {
+    timer: Timer,
+    color: Color,
+}
+
+impl Default for BorderFlash {
+    fn default() -> Self {
+        BorderFlash {
+            timer: Timer::from_seconds(0.0, false),
+            color: Color::NONE,
+        }
+    }
+}
+
+impl BorderFlash {
+    fn start(&mut self, color: Color) {
+        self.timer = Timer::from_seconds(BORDER_FLASH_SECONDS, false);
+        self.color = color;
+    }
+}
+
+struct BorderFlashStrip;
+
+// see capacity_warning
+#[derive(Default)]
+struct CapacityWarning(f32);
+
+// "+10" popup rising from an eaten fruit
+struct FloatingScore {
+    timer: Timer,
+    velocity: Vec2,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows_with_primary(width: u32, height: u32) -> Windows {
+        let mut windows = Windows::default();
+        windows.add(Window::new(WindowId::primary(), &WindowDescriptor::default(), width, height, 1.0, None));
+        windows
+    }
+
+    #[test]
+    fn zero_sized_window_is_skipped() {
+        assert!(usable_window(&Windows::default()).is_none());
+        assert!(usable_window(&windows_with_primary(0, 0)).is_none());
+        assert!(usable_window(&windows_with_primary(800, 0)).is_none());
+        assert!(usable_window(&windows_with_primary(800, 600)).is_some());
+    }
+
+    #[test]
+    fn edge_pattern_only_uses_border_tiles() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let grid = GridDimensions::default();
+        for _ in 0..100 {
+            assert!(is_edge(random_free_position(&mut rng, &HashSet::new(), SpawnPattern::EdgesOnly, grid).unwrap(), grid));
+        }
+    }
+
+    #[test]
+    fn forward_bias_spawns_ahead_of_the_head() {
+        let grid = GridDimensions::default();
+        let world = World::new();
+        let mut queue = bevy::ecs::system::CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut rng = StdRng::seed_from_u64(0);
+        let head = (Position { x: 5, y: 5 }, Direction::Right);
+        let mut spawn = |occupied: &mut HashSet<Position>| {
+            spawn_random_food(
+                &mut commands,
+                &Materials::default(),
+                &mut rng,
+                occupied,
+                SpawnPattern::Uniform,
+                Some(head),
+                (WildMode(false), FleeingFood(false), MomentumFood(false)),
+                GameStyle::Classic,
+                grid,
+                None,
+            )
+        };
+
+        let mut occupied = HashSet::new();
+        for _ in 0..20 {
+            assert!(spawn(&mut occupied));
+        }
+        assert!(occupied.iter().all(|pos| pos.x > 5));
+
+        // with everything ahead taken, it falls back to the rest of the board
+        let mut occupied = grid.tiles().filter(|pos| pos.x > 5).collect::<HashSet<Position>>();
+        assert!(spawn(&mut occupied));
+        assert!(occupied.iter().any(|pos| pos.x <= 5));
+    }
+
+    #[test]
+    fn full_board_has_no_free_position() {
+        let grid = GridDimensions::default();
+        let mut occupied = grid.tiles().collect::<HashSet<Position>>();
+        let mut rng = StdRng::seed_from_u64(0);
+        for pattern in [SpawnPattern::Uniform, SpawnPattern::EdgesOnly, SpawnPattern::CenterBias].iter() {
+            occupied.insert(Position { x: 0, y: 0 });
+            assert_eq!(random_free_position(&mut rng, &occupied, *pattern, grid), None);
+            occupied.remove(&Position { x: 0, y: 0 });
+            assert_eq!(random_free_position(&mut rng, &occupied, *pattern, grid), Some(Position { x: 0, y: 0 }));
+        }
+    }
+
+    #[test]
+    fn direction_between_neighbours() {
+        let origin = Position { x: 5, y: 5 };
+        assert_eq!(direction_between(origin, Position { x: 4, y: 5 }), Some(Direction::Left));
+        assert_eq!(direction_between(origin, Position { x: 6, y: 5 }), Some(Direction::Right));
+        assert_eq!(direction_between(origin, Position { x: 5, y: 6 }), Some(Direction::Up));
+        assert_eq!(direction_between(origin, Position { x: 5, y: 4 }), Some(Direction::Down));
+        assert_eq!(direction_between(origin, Position { x: 6, y: 6 }), None);
+        assert_eq!(direction_between(origin, origin), None);
+    }
+
+    // moves a snek body along the given directions, checking the incrementally updated set
+    // against the plain segment list after every step
+    fn assert_occupied_parity(mut body: Vec<Position>, moves: &[Direction]) -> usize {
+        let mut occupied = Occupied(body.iter().copied().collect());
+        let mut collisions = 0;
+        for direction in moves {
+            let new_head = body[0].neighbour(*direction);
+            assert_eq!(occupied.0.contains(&new_head), body.contains(&new_head));
+            if body.contains(&new_head) {
+                collisions += 1;
+            }
+
+            occupied.advance(*body.last().unwrap(), new_head);
+            body.insert(0, new_head);
+            body.pop();
+            assert_eq!(occupied.0, body.iter().copied().collect::<HashSet<Position>>());
+        }
+        collisions
+    }
+
+    #[test]
+    fn occupied_set_matches_segments() {
+        use Direction::*;
+        let straight = vec![Position { x: 3, y: 3 }, Position { x: 3, y: 2 }];
+        assert_eq!(assert_occupied_parity(straight, &[Up, Up, Right, Right, Down, Left]), 0);
+
+        let long = (0..6).map(|y| Position { x: 5, y: 10 - y }).collect::<Vec<Position>>();
+        // curling into the own body
+        assert_eq!(assert_occupied_parity(long, &[Right, Down, Left]), 1);
+        // chasing the tail: the tail moves out of the way, but counts as a hit like before
+        let square = vec![
+            Position { x: 1, y: 1 },
+            Position { x: 1, y: 0 },
+            Position { x: 0, y: 0 },
+            Position { x: 0, y: 1 },
+        ];
+        assert_eq!(assert_occupied_parity(square, &[Left]), 1);
+    }
+
+    #[test]
+    fn difficulty_cycles_through_all_levels() {
+        assert_eq!(Difficulty::Easy.next(), Difficulty::Normal);
+        assert_eq!(Difficulty::Normal.next(), Difficulty::Hard);
+        assert_eq!(Difficulty::Hard.next(), Difficulty::Easy);
+        assert!(movement_interval(START_LENGTH, Difficulty::Easy, 1.0) > movement_interval(START_LENGTH, Difficulty::Hard, 1.0));
+    }
+
+    #[test]
+    fn obstacle_patrols_away_from_the_start() {
+        let path = obstacle_path(GridDimensions::default());
+        for center_start in [false, true].iter() {
+            let head = start_position(*center_start, START_LENGTH, GridDimensions::default());
+            assert!(!path.contains(&head));
+            assert!(!path.contains(&Position { x: head.x, y: head.y - 1 }));
+        }
+        // consecutive tiles, including the step from the end back to the start
+        for (i, pos) in path.iter().enumerate() {
+            assert!(direction_between(*pos, path[(i + 1) % path.len()]).is_some());
+        }
+    }
+
+    #[test]
+    fn obstacle_hits_head_on_and_when_swapping() {
+        let obstacle = Obstacle { path: obstacle_path(GridDimensions::default()), index: 1 };
+        let (previous, current) = (obstacle.path[0], obstacle.path[1]);
+        let below = |pos: Position| Position { x: pos.x, y: pos.y - 1 };
+        assert!(obstacle.hits(below(current), current, Thickness(1)));
+        assert!(obstacle.hits(current, previous, Thickness(1)));
+        assert!(!obstacle.hits(below(previous), previous, Thickness(1)));
+    }
+
+    #[test]
+    fn bounces_off_each_wall() {
+        let grid = GridDimensions::default();
+        let (right, top) = (grid.width as i32 - 1, grid.height as i32 - 1);
+        assert_eq!(wall_bounce(Position { x: 0, y: 5 }, Direction::Left, grid, Thickness(1)), Some(Direction::Right));
+        assert_eq!(wall_bounce(Position { x: right, y: 5 }, Direction::Right, grid, Thickness(1)), Some(Direction::Left));
+        assert_eq!(wall_bounce(Position { x: 5, y: top }, Direction::Up, grid, Thickness(1)), Some(Direction::Down));
+        assert_eq!(wall_bounce(Position { x: 5, y: 0 }, Direction::Down, grid, Thickness(1)), Some(Direction::Up));
+        // moving along a wall or away from it is no bounce
+        assert_eq!(wall_bounce(Position { x: 0, y: 5 }, Direction::Up, grid, Thickness(1)), None);
+        assert_eq!(wall_bounce(Position { x: right, y: 5 }, Direction::Left, grid, Thickness(1)), None);
+        assert_eq!(wall_bounce(Position { x: 5, y: 5 }, Direction::Down, grid, Thickness(1)), None);
+    }
+
+    #[test]
+    fn growth_appears_after_the_delay() {
+        for delay in 0..4 {
+            let mut pending = PendingGrowth::default();
+            pending.eat(delay);
+            // the tick of the eat counts as tick 0
+            let grown_at = (0..10).find(|_| pending.tick());
+            assert_eq!(grown_at, Some(delay));
+            assert!(pending.0.is_empty());
+        }
+    }
+
+    #[test]
+    fn score_multiplier_scales_points() {
+        let mut multiplier = ScoreMultiplier::default();
+        assert_eq!(multiplier.apply(FOOD_POINTS), FOOD_POINTS);
+        multiplier.factor = DOUBLE_SCORE_FACTOR;
+        assert_eq!(multiplier.apply(FOOD_POINTS), 2 * FOOD_POINTS);
+        assert_eq!(multiplier.apply(TARGET_FOOD_POINTS), 2 * TARGET_FOOD_POINTS);
+    }
+
+    #[test]
+    fn y_axis_down_mirrors_rows() {
+        let height = 600.0;
+        let grid = GridDimensions::default();
+        for y in 0..grid.height {
+            let up = convert_y(y as f32, height, grid, YAxisDown(false));
+            let down = convert_y(y as f32, height, grid, YAxisDown(true));
+            assert!((up + down).abs() < 0.001);
+        }
+        assert!(convert_y(0.0, height, grid, YAxisDown(true)) > 0.0);
+    }
+
+    #[test]
+    fn capacity_warning_grows_as_the_board_fills() {
+        assert_eq!(capacity_level(500, 1000), 0.0);
+        assert_eq!(capacity_level(100, 1000), 0.0);
+        let nearly_full = capacity_level(50, 1000);
+        assert!(nearly_full > 0.4 && nearly_full < 0.6);
+        assert!(capacity_level(10, 1000) > nearly_full);
+        assert_eq!(capacity_level(0, 1000), 1.0);
+    }
+
+    #[test]
+    fn warp_avoids_fatal_first_steps() {
+        let taken = [Position { x: 5, y: 5 }].iter().copied().collect::<HashSet<Position>>();
+        let blocked = warp_blocked(&taken, Direction::Right, GridDimensions::default());
+        assert!(blocked.contains(&Position { x: 5, y: 5 }));
+        assert!(blocked.contains(&Position { x: 4, y: 5 }));
+        assert!(blocked.contains(&Position { x: WIDTH as i32 - 1, y: 0 }));
+        assert!(!blocked.contains(&Position { x: 0, y: 0 }));
+        assert!(!blocked.contains(&Position { x: 6, y: 5 }));
+    }
+
+    // the game without a window, on a fixed seed. no game time passes until `step`.
+    fn test_app(args: Args) -> App {
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .init_resource::<Materials>()
+            .add_plugin(SnekPlugin);
+        args.insert_settings(&mut builder);
+        builder.app
+    }
+
+    // pretends exactly one movement step passed, so the update is one tick
+    fn step(app: &mut App) -> Duration {
+        let step = app.world.get_resource::<MovementTimer>().unwrap().0.duration();
+        app.world.get_resource_mut::<FrameDelta>().unwrap().0 = step;
+        app.update();
+        step
+    }
+
+    // builds the game the way the benchmark does and lets it run a few ticks
+    #[test]
+    fn headless_app_runs() {
+        let mut app = test_app(Args::default());
+
+        for _ in 0..5 {
+            step(&mut app);
+        }
+
+        let segments = app.world.get_resource::<SnekSegments>().unwrap().0.clone();
+        assert_eq!(segments.len(), START_LENGTH);
+        let head = *app.world.get::<Position>(segments[0]).unwrap();
+        let start = start_position(false, START_LENGTH, GridDimensions::default());
+        assert_eq!(head, Position { x: start.x, y: start.y + 5 });
+        assert!(app.world.get::<SnekHead>(segments[0]).is_some());
+        assert_eq!(app.world.get_resource::<TickCount>().unwrap().0, 5);
+    }
+
+    // every system --no-render adds finds its resources without a window or a renderer
+    #[test]
+    fn no_render_app_runs() {
+        let mut app = headless(App::build(), &Args::default(), 0);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(app.world.get_resource::<SnekSegments>().unwrap().0.len(), START_LENGTH);
+        assert!(app.world.get_resource::<Windows>().is_none());
+    }
+
+    #[test]
+    fn head_only_snek_moves_eats_and_grows() {
+        let mut app = test_app(Args {
+            start_length: Some(1),
+            ..Default::default()
+        });
+        // spawns the snek without moving it
+        app.update();
+
+        let start = start_position(false, 1, GridDimensions::default());
+        assert_eq!(app.world.get_resource::<SnekSegments>().unwrap().0.len(), 1);
+        let fruit = Position { x: start.x, y: start.y + 1 };
+        app.world.spawn().insert(Food).insert(FoodKind::Regular).insert(fruit);
+
+        let snek = |app: &mut App| {
+            app.world.get_resource::<SnekSegments>().unwrap().0.iter()
+                .map(|e| *app.world.get::<Position>(*e).unwrap())
+                .collect::<Vec<Position>>()
+        };
+
+        step(&mut app);
+        // the new segment appears on the tile the head just left
+        assert_eq!(snek(&mut app), vec![fruit, start]);
+
+        step(&mut app);
+        let moved = vec![Position { x: start.x, y: start.y + 2 }, fruit];
+        assert_eq!(snek(&mut app), moved);
+        assert_eq!(app.world.get_resource::<Occupied>().unwrap().0, moved.into_iter().collect());
+    }
+
+    #[test]
+    fn boss_takes_several_bumps_to_eat() {
+        let mut app = test_app(Args {
+            start_length: Some(1),
+            boss_fruit: true,
+            ..Default::default()
+        });
+        app.update();
+
+        let start = start_position(false, 1, GridDimensions::default());
+        let corner = Position { x: start.x, y: start.y + 1 };
+        app.world.spawn().insert(BossFruit { hp: BOSS_HP });
+        for pos in boss_footprint(corner) {
+            app.world.spawn().insert(BossTile).insert(pos);
+            app.world.get_resource_mut::<BossTiles>().unwrap().0.insert(pos);
+        }
+
+        let head = |app: &mut App| {
+            let head = app.world.get_resource::<SnekSegments>().unwrap().0[0];
+            *app.world.get::<Position>(head).unwrap()
+        };
+        for hp in (1..BOSS_HP).rev() {
+            step(&mut app);
+            assert_eq!(head(&mut app), start);
+            let mut bosses = app.world.query::<&BossFruit>();
+            assert_eq!(bosses.iter(&app.world).next().unwrap().hp, hp);
+        }
+        step(&mut app);
+        assert!(app.world.get_resource::<BossTiles>().unwrap().0.is_empty());
+        assert!(app.world.query::<&BossFruit>().iter(&app.world).next().is_none());
+        assert_eq!(app.world.get_resource::<Score>().unwrap().0, BOSS_POINTS);
+
+        // the way is clear again
+        step(&mut app);
+        assert_eq!(head(&mut app), corner);
+    }
+
+    #[test]
+    fn fixed_length_stops_growing_but_keeps_scoring() {
+        let mut app = test_app(Args {
+            fixed_length: Some(3),
+            ..Default::default()
+        });
+        app.update();
+
+        // a fruit on every tile straight ahead
+        let start = start_position(false, START_LENGTH, GridDimensions::default());
+        for y in 1..=4 {
+            let fruit = Position { x: start.x, y: start.y + y };
+            app.world.spawn().insert(Food).insert(FoodKind::Regular).insert(fruit);
+        }
+        for _ in 0..6 {
+            step(&mut app);
+            assert!(app.world.get_resource::<SnekSegments>().unwrap().0.len() <= 3);
+        }
+
+        assert_eq!(app.world.get_resource::<SnekSegments>().unwrap().0.len(), 3);
+        assert_eq!(app.world.get_resource::<Occupied>().unwrap().0.len(), 3);
+        assert_eq!(app.world.get_resource::<Score>().unwrap().0, 4 * FOOD_POINTS);
+    }
+
+    #[test]
+    fn continuous_spawning_keeps_the_board_full() {
+        let mut app = test_app(Args {
+            spawn_mode: SpawnMode::Continuous,
+            max_food: Some(5),
+            ..Default::default()
+        });
+        app.update();
+        let food_count = |app: &mut App| app.world.query_filtered::<(), With<Food>>().iter(&app.world).count();
+        assert_eq!(food_count(&mut app), 5);
+
+        for eaten in 1..=4 {
+            // puts a plain fruit right in front of the head, unless one is already there
+            let ahead = app.world.query_filtered::<&Position, With<SnekHead>>()
+                .iter(&app.world)
+                .next()
+                .unwrap()
+                .neighbour(Direction::Up);
+            let (fruit, _) = app.world.query_filtered::<(Entity, &Position), With<Food>>()
+                .iter(&app.world)
+                .max_by_key(|(_, pos)| **pos == ahead)
+                .unwrap();
+            app.world.entity_mut(fruit).insert(ahead).insert(FoodKind::Regular);
+
+            step(&mut app);
+            assert_eq!(app.world.get_resource::<Score>().unwrap().0, eaten * FOOD_POINTS);
+            assert_eq!(food_count(&mut app), 5);
+        }
+    }
+
+    #[test]
+    fn feast_adds_a_fruit_for_every_one_eaten_until_the_cap() {
+        assert_eq!(feast_replacements(false, 0), 0);
+        assert_eq!(feast_replacements(true, 0), FEAST_REPLACEMENTS);
+        assert_eq!(feast_replacements(true, FEAST_FOOD_CAP - 1), 1);
+        assert_eq!(feast_replacements(true, FEAST_FOOD_CAP), 0);
+        // more than the cap, e.g. from the spawn timer, doesn't underflow
+        assert_eq!(feast_replacements(true, FEAST_FOOD_CAP + 3), 0);
+
+        let mut app = test_app(Args {
+            feast: true,
+            // keeps the spawn timer from adding any
+            max_food: Some(1),
+            ..Default::default()
+        });
+        app.update();
+        let grid = GridDimensions::default();
+        let start = start_position(false, START_LENGTH, grid);
+        // three fruit straight ahead, and the rest of the board two short of the cap after eating them
+        for y in 1..=3 {
+            app.world.spawn().insert(Food).insert(FoodKind::Regular).insert(Position { y: start.y + y, ..start });
+        }
+        for y in 0..FEAST_FOOD_CAP as i32 - 5 {
+            app.world.spawn().insert(Food).insert(FoodKind::Regular).insert(Position { x: grid.width as i32 - 1, y });
+        }
+        let food_count = |app: &mut App| app.world.query_filtered::<(), With<Food>>().iter(&app.world).count();
+        assert_eq!(food_count(&mut app), FEAST_FOOD_CAP - 2);
+
+        for on_board in [FEAST_FOOD_CAP - 1, FEAST_FOOD_CAP, FEAST_FOOD_CAP] {
+            step(&mut app);
+            assert_eq!(food_count(&mut app), on_board);
+        }
+        assert_eq!(app.world.get_resource::<Score>().unwrap().0, 3 * FOOD_POINTS);
+    }
+
+    #[test]
+    fn shuffling_moves_fruit_around_the_walls_and_keeps_it_as_it_was() {
+        let mut app = test_app(Args {
+            rooms: true,
+            ..Default::default()
+        });
+        app.world.insert_resource(Input::<KeyCode>::default());
+        app.world.insert_resource(Paused(false));
+        app.schedule.add_system_to_stage(CoreStage::Update, food_shuffle.system());
+        app.update();
+        let mut timer = Timer::from_seconds(TARGET_FOOD_SECONDS, false);
+        timer.tick(Duration::from_secs(1));
+        let start = Position { x: 1, y: 1 };
+        let fruit = app.world.spawn()
+            .insert(Food)
+            .insert(FoodKind::Target)
+            .insert(TargetFood { timer })
+            .insert(start)
+            .id();
+
+        let mut seen = HashSet::new();
+        for _ in 0..SHUFFLE_CHARGES {
+            let mut input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+            *input = Input::default();
+            input.press(KeyCode::Tab);
+            app.update();
+            let pos = *app.world.get::<Position>(fruit).unwrap();
+            assert!(!app.world.get_resource::<RoomWalls>().unwrap().0.contains(&pos));
+            assert!(!app.world.get_resource::<Occupied>().unwrap().0.contains(&pos));
+            // the same fruit, with the countdown where it was
+            assert_eq!(app.world.get::<TargetFood>(fruit).unwrap().timer.elapsed(), Duration::from_secs(1));
+            seen.insert(pos);
+        }
+        assert!(seen.iter().any(|pos| *pos != start));
+        assert_eq!(app.world.get_resource::<ShuffleCharges>().unwrap().0, 0);
+    }
+
+    #[test]
+    fn adaptive_speed_follows_survival_and_deaths() {
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .insert_resource(AdaptiveMode(true))
+            .insert_resource(AdaptiveDifficulty::default())
+            .insert_resource(FrameDelta(Duration::from_secs(50)))
+            .add_event::<FoodEatenEvent>()
+            .add_event::<GameOverEvent>()
+            .add_system(adapt_difficulty.system());
+        let mut app = builder.app;
+        let factor = |app: &App| app.world.get_resource::<AdaptiveDifficulty>().unwrap().factor;
+
+        app.update();
+        let survived = factor(&app);
+        assert!(survived > 1.0);
+
+        app.world.get_resource_mut::<FrameDelta>().unwrap().0 = Duration::ZERO;
+        app.world.get_resource_mut::<Events<GameOverEvent>>().unwrap().send(GameOverEvent);
+        app.update();
+        assert!(factor(&app) < survived);
+
+        for _ in 0..20 {
+            app.world.get_resource_mut::<Events<GameOverEvent>>().unwrap().send(GameOverEvent);
+            app.update();
+        }
+        assert_eq!(factor(&app), ADAPTIVE_LIMITS.0);
+    }
+
+    #[test]
+    fn cursor_maps_back_to_the_tile_under_it() {
+        let grid = GridDimensions::default();
+        let window = Vec2::new(1000.0, 1000.0);
+        let tile = window.x / grid.width as f32;
+        let center = (Vec2::ZERO, 1.0);
+        for &pos in &[Position { x: 0, y: 0 }, Position { x: 7, y: 21 }, Position { x: 29, y: 29 }] {
+            // position_translation puts the tile's center here, with 0:0 in the middle of the window
+            let drawn = Vec2::new(
+                convert(pos.x as f32, window.x, grid.width as f32),
+                convert_y(pos.y as f32, window.y, grid, YAxisDown(true)),
+            );
+            assert_eq!(cursor_tile(drawn + window / 2.0, window, center, (grid, 0.0), YAxisDown(true)), Some(pos));
+        }
+        assert_eq!(cursor_tile(Vec2::new(-1.0, 5.0), window, center, (grid, 0.0), YAxisDown(false)), None);
+        // the bottom left corner is a wall, one tile off the board
+        let walled = BorderWalls(true).drawn(grid);
+        assert_eq!(
+            cursor_tile(Vec2::new(1.0, 1.0), window, center, walled, YAxisDown(false)),
+            Some(Position { x: -1, y: -1 }),
+        );
+
+        // zoomed in 2x on the bottom left quarter, the window's center is that quarter's center
+        let zoomed = (-window / 4.0, 0.5);
+        let quarter = grid.width as i32 / 4;
+        assert_eq!(
+            cursor_tile(window / 2.0 + Vec2::splat(tile / 4.0), window, zoomed, (grid, 0.0), YAxisDown(false)),
+            Some(Position { x: quarter, y: quarter }),
+        );
+    }
+
+    #[test]
+    fn cluster_bonus_needs_the_whole_cluster_in_time() {
+        let mut progress = ClusterProgress::default();
+        assert_eq!((0..CLUSTER_SIZE).map(|_| progress.eat(1)).collect::<Vec<_>>(), vec![false, false, false, true]);
+
+        // too slow: what is left of the cluster can't make up the four anymore
+        progress.eat(2);
+        progress.eat(2);
+        progress.timer.tick(Duration::from_secs_f32(CLUSTER_SECONDS));
+        assert!(!progress.eat(2));
+        assert!(!progress.eat(2));
+
+        // another cluster in between starts over too
+        progress.eat(3);
+        progress.eat(4);
+        assert!(!(0..CLUSTER_SIZE - 1).any(|_| progress.eat(3)));
+    }
+
+    #[test]
+    fn stretched_segments_lag_but_stay_attached() {
+        let tile = Vec2::new(10.0, 10.0);
+        let ahead = Vec2::new(0.0, 0.0);
+        // half way there
+        let drawn = stretch_towards(Vec2::new(-10.0, 0.0), Vec2::new(0.0, -10.0), ahead, 0.5, tile);
+        assert_eq!(drawn, Vec2::new(-5.0, -5.0));
+        // too far behind: pulled up to the segment in front
+        let drawn = stretch_towards(Vec2::new(-15.0, 0.0), Vec2::new(-10.0, 0.0), ahead, 0.0, tile);
+        assert!(((drawn - ahead) / tile).length() <= STRETCH_MAX_GAP + 0.001);
+        // a warp isn't stretched out across the board
+        let far = Vec2::new(200.0, 0.0);
+        assert_eq!(stretch_towards(Vec2::ZERO, far, far, 0.1, tile), far);
+    }
+
+    #[test]
+    fn zoomed_camera_stays_on_the_board() {
+        let head = Vec3::new(-390.0, 100.0, 0.0);
+        assert_eq!(follow_head(head, 800.0, 600.0, 1.0), Vec2::ZERO);
+        assert_eq!(follow_head(head, 800.0, 600.0, 2.0), Vec2::ZERO);
+        // half the board is visible, so the center can move a quarter of the window either way
+        assert_eq!(follow_head(head, 800.0, 600.0, 0.5), Vec2::new(-200.0, 100.0));
+    }
+
+    #[test]
+    fn chasing_the_tail_is_safe() {
+        let mut app = test_app(Args {
+            start_length: Some(4),
+            ..Default::default()
+        });
+        app.update();
+
+        // right, down, left: a snek of four circles back onto the tile its tail is leaving
+        let start = start_position(false, 4, GridDimensions::default());
+        for direction in [Direction::Right, Direction::Down, Direction::Left].iter() {
+            for mut head in app.world.query::<&mut SnekHead>().iter_mut(&mut app.world) {
+                head.next_direction = *direction;
+            }
+            step(&mut app);
+        }
+
+        let segments = app.world.get_resource::<SnekSegments>().unwrap().0.clone();
+        assert_eq!(segments.len(), 4);
+        assert_eq!(*app.world.get::<Position>(segments[0]).unwrap(), Position { x: start.x, y: start.y - 1 });
+        assert_eq!(app.world.get_resource::<Occupied>().unwrap().0.len(), 4);
+    }
+
+    #[test]
+    fn biting_the_body_cuts_off_the_rest() {
+        let mut app = test_app(Args {
+            start_length: Some(5),
+            truncate_tail: true,
+            ..Default::default()
+        });
+        app.update();
+        let start = start_position(false, 5, GridDimensions::default());
+        let turn = |app: &mut App, direction: Direction| {
+            for mut head in app.world.query::<&mut SnekHead>().iter_mut(&mut app.world) {
+                head.next_direction = direction;
+            }
+            step(app);
+        };
+
+        // right, down, left: the head comes back onto the tile of the fourth segment (index 3)
+        turn(&mut app, Direction::Right);
+        turn(&mut app, Direction::Down);
+        let before = app.world.get_resource::<SnekSegments>().unwrap().0.clone();
+        turn(&mut app, Direction::Left);
+
+        let segments = app.world.get_resource::<SnekSegments>().unwrap().0.clone();
+        assert_eq!(segments, before[..3 + 1].to_vec());
+        for bitten_off in before[3 + 1..].iter() {
+            assert!(app.world.get_entity(*bitten_off).is_none());
+        }
+        let positions = segments.iter().map(|e| *app.world.get::<Position>(*e).unwrap()).collect::<Vec<Position>>();
+        assert_eq!(positions, vec![
+            Position { x: start.x, y: start.y - 1 },
+            Position { x: start.x + 1, y: start.y - 1 },
+            Position { x: start.x + 1, y: start.y },
+            start,
+        ]);
+        assert_eq!(app.world.get_resource::<Occupied>().unwrap().0, positions.into_iter().collect());
+    }
+
+    #[test]
+    fn hugging_the_wall_costs_points() {
+        let start = start_position(false, START_LENGTH, GridDimensions::default());
+        let hug = |args: Args| {
+            let mut app = test_app(Args {
+                wall_hug_penalty: 1.5,
+                ..args
+            });
+            app.update();
+            app.world.get_resource_mut::<Score>().unwrap().0 = 5;
+
+            // left until the head reaches the wall, then along it
+            let mut turns = vec![Direction::Left; start.x as usize];
+            turns.push(Direction::Up);
+            for direction in turns.iter() {
+                for mut head in app.world.query::<&mut SnekHead>().iter_mut(&mut app.world) {
+                    head.next_direction = *direction;
+                }
+                step(&mut app);
+            }
+            app.world.get_resource::<Score>().unwrap().0
+        };
+        // two ticks at the wall owe 3 points
+        assert_eq!(hug(Args::default()), 2);
+        // an edge the snek comes back in from isn't a wall
+        assert_eq!(hug(Args { wall_wrap: true, ..Default::default() }), 5);
+
+        // room walls count too
+        let mut app = test_app(Args {
+            wall_hug_penalty: 1.5,
+            ..Default::default()
+        });
+        app.update();
+        app.world.get_resource_mut::<Score>().unwrap().0 = 5;
+        app.world.get_resource_mut::<RoomWalls>().unwrap().0.insert(Position { x: start.x + 1, y: start.y + 1 });
+        step(&mut app);
+        assert_eq!(app.world.get_resource::<Score>().unwrap().0, 4);
+    }
+
+    #[test]
+    fn snake_ii_bonus_counts_down() {
+        let mut timer = Timer::from_seconds(TARGET_FOOD_SECONDS, false);
+        assert_eq!(countdown_points(&timer), TARGET_FOOD_POINTS);
+        assert_eq!(value_tier(&timer), 0);
+        timer.tick(Duration::from_secs_f32(TARGET_FOOD_SECONDS / 2.0));
+        assert_eq!(countdown_points(&timer), (TARGET_FOOD_POINTS + FOOD_POINTS) / 2);
+        assert_eq!(value_tier(&timer), 1);
+        timer.tick(Duration::from_secs_f32(TARGET_FOOD_SECONDS));
+        assert_eq!(countdown_points(&timer), FOOD_POINTS);
+        assert_eq!(value_tier(&timer), 2);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..200 {
+            let every_kind = (WildMode(true), FleeingFood(true), MomentumFood(true));
+            assert_eq!(random_food_kind(&mut rng, every_kind, GameStyle::SnakeII), FoodKind::Regular);
+        }
+    }
+
+    #[test]
+    fn target_fruit_lasts_longer_for_longer_sneks() {
+        assert_eq!(decay_factor(START_LENGTH), 1.0);
+        assert_eq!(decay_factor(1), 1.0);
+        assert!(decay_factor(START_LENGTH + 10) > 1.0);
+        assert_eq!(decay_factor(10_000), MAX_SLOW_DECAY);
+
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .insert_resource(SlowDecay(true))
+            .insert_resource(SnekSegments::default())
+            .add_system(slow_decay.system());
+        let mut app = builder.app;
+        let length = START_LENGTH + 20;
+        let segments = (0..length).map(|_| app.world.spawn().id()).collect();
+        app.world.get_resource_mut::<SnekSegments>().unwrap().0 = segments;
+        let timer = Timer::from_seconds(TARGET_FOOD_SECONDS, false);
+        let fruit = app.world.spawn().insert(TargetFood { timer }).id();
+        app.update();
+        let lasts = app.world.get::<TargetFood>(fruit).unwrap().timer.duration().as_secs_f32();
+        assert!((lasts - TARGET_FOOD_SECONDS * decay_factor(length)).abs() < 0.001);
+
+        // only once, when the fruit shows up
+        app.world.get_resource_mut::<SnekSegments>().unwrap().0.clear();
+        app.update();
+        assert_eq!(app.world.get::<TargetFood>(fruit).unwrap().timer.duration().as_secs_f32(), lasts);
+    }
+
+    #[test]
+    fn momentum_fruit_holds_off_turns_for_a_while() {
+        let mut app = test_app(Args {
+            start_length: Some(1),
+            momentum_food: true,
+            ..Default::default()
+        });
+        app.update();
+        let start = start_position(false, 1, GridDimensions::default());
+        app.world.spawn().insert(Food).insert(FoodKind::Momentum).insert(Position { y: start.y + 1, ..start });
+        let direction = |app: &mut App| app.world.query::<&SnekHead>().iter(&app.world).next().unwrap().direction;
+        step(&mut app);
+        assert!(app.world.get_resource::<DirectionLock>().unwrap().timer.is_some());
+
+        // the turn waits until the lock is up, then it is taken right away
+        for mut head in app.world.query::<&mut SnekHead>().iter_mut(&mut app.world) {
+            head.next_direction = Direction::Right;
+        }
+        let lock = Duration::from_secs_f32(DIRECTION_LOCK_SECONDS);
+        let mut locked_for = Duration::ZERO;
+        loop {
+            locked_for += step(&mut app);
+            if locked_for >= lock {
+                break;
+            }
+            assert_eq!(direction(&mut app), Direction::Up);
+        }
+        assert_eq!(direction(&mut app), Direction::Right);
+        assert!(app.world.get_resource::<DirectionLock>().unwrap().timer.is_none());
+    }
+
+    #[test]
+    fn co_op_votes_are_counted_by_the_aggregation() {
+        let mut votes = TurnVotes::default();
+        votes.vote(0, Direction::Left);
+        votes.vote(1, Direction::Right);
+        assert_eq!(votes.count(InputAggregation::LastWins), Some(Direction::Right));
+        // every tick starts over
+        assert_eq!(votes.count(InputAggregation::LastWins), None);
+
+        votes.vote(1, Direction::Right);
+        votes.vote(0, Direction::Left);
+        assert_eq!(votes.count(InputAggregation::LastWins), Some(Direction::Left));
+
+        votes.vote(0, Direction::Left);
+        votes.vote(1, Direction::Right);
+        assert_eq!(votes.count(InputAggregation::Consensus), None);
+        votes.vote(0, Direction::Left);
+        assert_eq!(votes.count(InputAggregation::Consensus), None);
+        votes.vote(0, Direction::Left);
+        votes.vote(1, Direction::Left);
+        assert_eq!(votes.count(InputAggregation::Consensus), Some(Direction::Left));
+        assert_eq!(votes.count(InputAggregation::Consensus), None);
+    }
+
+    #[test]
+    fn pausing_keeps_the_queued_turn() {
+        // steered by the keyboard, on the real clock
+        let mut app = test_app(Args::default());
+        app.world.insert_resource(Input::<KeyCode>::default());
+        app.world.insert_resource(Paused(false));
+        app.world.insert_resource(SlowMotion::default());
+        app.world.insert_resource(Transition::default());
+        app.world.insert_resource(instant_replay::InstantReplay::default());
+        app.schedule
+            .add_system_to_stage(CoreStage::First, frame_delta.system())
+            .add_system_to_stage(CoreStage::Update, snek_movement_input.system().before(SnekMovement::Movement));
+        let start = start_position(false, START_LENGTH, GridDimensions::default());
+        let head = |app: &mut App| {
+            let segments = app.world.get_resource::<SnekSegments>().unwrap().0.clone();
+            let position = *app.world.get::<Position>(segments[0]).unwrap();
+            let head = app.world.get::<SnekHead>(segments[0]).unwrap();
+            (position, head.direction, head.next_direction)
+        };
+        let press = |app: &mut App, key: Option<KeyCode>| {
+            let mut input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+            *input = Input::default();
+            if let Some(key) = key {
+                input.press(key);
+            }
+        };
+        let wait_past_a_tick = || std::thread::sleep(Duration::from_secs_f64(1.5 / MOVEMENT_RATE));
+
+        // the first frame takes no time, so the turn is only queued
+        press(&mut app, Some(KeyCode::D));
+        app.update();
+        assert_eq!(head(&mut app), (start, Direction::Up, Direction::Right));
+
+        // while paused the same key moves the camera instead, and the snek doesn't move
+        app.world.get_resource_mut::<Paused>().unwrap().0 = true;
+        press(&mut app, Some(KeyCode::W));
+        wait_past_a_tick();
+        app.update();
+        assert_eq!(head(&mut app), (start, Direction::Up, Direction::Right));
+
+        app.world.get_resource_mut::<Paused>().unwrap().0 = false;
+        press(&mut app, None);
+        wait_past_a_tick();
+        app.update();
+        let (position, direction, _) = head(&mut app);
+        assert_eq!(direction, Direction::Right);
+        assert_eq!(position, Position { x: start.x + 1, y: start.y });
+    }
+
+    #[test]
+    fn resizing_the_board_starts_over_on_it() {
+        let mut app = test_app(Args {
+            center_start: true,
+            ..Default::default()
+        });
+        app.world.insert_resource(Input::<KeyCode>::default());
+        app.world.insert_resource(Paused(true));
+        app.world.insert_resource(GridResize::default());
+        app.schedule.add_system_to_stage(CoreStage::Update, grid_resize_menu.system());
+        let press = |app: &mut App, key: Option<KeyCode>| {
+            let mut input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+            *input = Input::default();
+            if let Some(key) = key {
+                input.press(key);
+            }
+            app.update();
+        };
+
+        // narrower than the limit allows, and one row taller
+        for _ in 0..WIDTH {
+            press(&mut app, Some(KeyCode::Left));
+        }
+        press(&mut app, Some(KeyCode::Up));
+        let picked = GridDimensions { width: GRID_LIMITS.0, height: HEIGHT + 1 };
+        assert_eq!(*app.world.get_resource::<GridDimensions>().unwrap(), GridDimensions::default());
+        assert_eq!(app.world.get_resource::<GridResize>().unwrap().0, Some(picked));
+
+        press(&mut app, Some(KeyCode::Return));
+        press(&mut app, None);
+        assert_eq!(*app.world.get_resource::<GridDimensions>().unwrap(), picked);
+        assert_eq!(app.world.get_resource::<GridResize>().unwrap().0, None);
+
+        // a new snek in the middle of the new board, and an obstacle that fits on it
+        let segments = app.world.get_resource::<SnekSegments>().unwrap().0.clone();
+        assert_eq!(segments.len(), START_LENGTH);
+        assert_eq!(*app.world.get::<Position>(segments[0]).unwrap(), start_position(true, START_LENGTH, picked));
+        for obstacle in app.world.query::<&Obstacle>().iter(&app.world) {
+            assert!(obstacle.path.iter().all(|pos| pos.in_bounds(picked)));
+        }
+    }
+
+    #[test]
+    fn combo_builds_up_and_runs_out() {
+        let mut combo = Combo::default();
+        combo.eat();
+        assert_eq!(combo.level(), 0.0);
+        combo.tick(Duration::from_secs_f32(COMBO_SECONDS / 2.0));
+        combo.eat();
+        assert!(combo.level() > 0.0);
+        for _ in 0..2 * COMBO_FLAME_MAX {
+            combo.eat();
+        }
+        assert_eq!(combo.level(), 1.0);
+
+        combo.tick(Duration::from_secs_f32(COMBO_SECONDS));
+        assert_eq!(combo.count, 0);
+        assert_eq!(combo.level(), 0.0);
+    }
+
+    #[test]
+    fn near_misses_look_ahead_and_to_the_sides() {
+        let grid = GridDimensions::default();
+        let head = Position { x: 5, y: 5 };
+        let behind = [Position { x: 5, y: 4 }].iter().copied().collect::<HashSet<Position>>();
+        assert!(!next_to_danger(head, Direction::Up, &behind, grid, true));
+        let beside = [Position { x: 6, y: 5 }].iter().copied().collect::<HashSet<Position>>();
+        assert!(next_to_danger(head, Direction::Up, &beside, grid, true));
+
+        // at the wall, unless the snek bounces off it
+        let at_wall = Position { x: 0, y: 5 };
+        assert!(next_to_danger(at_wall, Direction::Up, &HashSet::new(), grid, true));
+        assert!(!next_to_danger(at_wall, Direction::Up, &HashSet::new(), grid, false));
+    }
+
+    #[test]
+    fn steering_avoids_blocked_tiles() {
+        let head = Position { x: 5, y: 5 };
+        let food = [Position { x: 8, y: 5 }];
+        assert_eq!(steer(head, Direction::Up, &HashSet::new(), &food, GridDimensions::default()), Some(Direction::Right));
+
+        // heading right with the fruit straight ahead but blocked: turns aside, never straight back
+        let blocked = [Position { x: 6, y: 5 }].iter().copied().collect::<HashSet<Position>>();
+        let turn = steer(head, Direction::Right, &blocked, &food, GridDimensions::default());
+        assert!(turn == Some(Direction::Up) || turn == Some(Direction::Down));
+
+        let boxed_in = [Direction::Left, Direction::Right, Direction::Up, Direction::Down].iter()
+            .map(|direction| head.neighbour(*direction))
+            .collect::<HashSet<Position>>();
+        assert_eq!(steer(head, Direction::Up, &boxed_in, &food, GridDimensions::default()), None);
+    }
+
+    #[test]
+    fn fleeing_food_steps_away_until_cornered() {
+        let grid = GridDimensions::default();
+        let head = Position { x: 5, y: 5 };
+        let next = flee_step(Position { x: 7, y: 5 }, head, &HashSet::new(), grid).unwrap();
+        assert_eq!(next, Position { x: 8, y: 5 });
+
+        // the way straight on is blocked, so it sidesteps while still getting further away
+        let blocked = [Position { x: 8, y: 5 }].iter().copied().collect::<HashSet<Position>>();
+        let next = flee_step(Position { x: 7, y: 5 }, head, &blocked, grid).unwrap();
+        assert!(next == Position { x: 7, y: 6 } || next == Position { x: 7, y: 4 });
+
+        // in the corner, with the head coming at it along the edge, it stays put and can be eaten
+        let corner = Position { x: grid.width as i32 - 1, y: 0 };
+        let head = Position { x: corner.x - 2, y: 0 };
+        let blocked = [Position { x: corner.x, y: 1 }].iter().copied().collect::<HashSet<Position>>();
+        assert_eq!(flee_step(corner, head, &blocked, grid), None);
+    }
+
+    #[test]
+    fn opposite_directions() {
+        assert_eq!(Direction::Left.opposite(), Direction::Right);
+        assert_eq!(Direction::Right.opposite(), Direction::Left);
+        assert_eq!(Direction::Up.opposite(), Direction::Down);
+        assert_eq!(Direction::Down.opposite(), Direction::Up);
+    }
+
+    #[test]
+    fn dead_snek_dissolves_from_the_tail() {
+        let mut world = World::new();
+        let head_first = (0..4).map(|_| world.spawn().id()).collect::<Vec<Entity>>();
+        let mut queue = bevy::ecs::system::CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut death_anim = DeathAnim::default();
+        death_anim.start(&mut commands, &head_first);
+
+        let step = Duration::from_secs_f32(DISSOLVE_STEP_SECONDS);
+        assert!(death_anim.advance(step / 2).is_empty());
+        assert_eq!(death_anim.advance(step), &[head_first[3]]);
+        // a long frame takes more than one, but never past the head
+        assert_eq!(death_anim.advance(step * 2), &[head_first[2], head_first[1]]);
+        assert_eq!(death_anim.advance(step * 10), &[head_first[0]]);
+        assert!(death_anim.advance(step).is_empty());
+    }
+
+    #[test]
+    fn seed_caption_says_whether_it_was_copied() {
+        assert_eq!(seed_caption(42, None), "Seed 42 (K to copy)");
+        assert_eq!(seed_caption(42, Some(true)), "Seed 42 (copied)");
+        assert_eq!(seed_caption(42, Some(false)), "Seed 42 (no clipboard)");
+    }
+
+    #[test]
+    fn size_tween_ends_at_the_new_size() {
+        let mut tween = SizeTween {
+            from: SegmentStyle::Beaded.segment_size(),
+            timer: Timer::from_seconds(SIZE_TWEEN_SECONDS, false),
+        };
+        let to = SegmentStyle::Solid.segment_size();
+        assert_eq!(tween.current(&to).width, 0.5);
+        tween.timer.tick(Duration::from_secs_f32(SIZE_TWEEN_SECONDS / 2.0));
+        assert!((tween.current(&to).width - 0.75).abs() < 0.001);
+        tween.timer.tick(Duration::from_secs_f32(SIZE_TWEEN_SECONDS));
+        assert_eq!(tween.current(&to).width, 1.0);
+    }
+
+    #[test]
+    fn eat_range_reaches_orthogonal_neighbours() {
+        let (near, far) = (Entity::new(0), Entity::new(1));
+        let head = Position { x: 5, y: 5 };
+        let food = [(far, Position { x: 6, y: 6 }), (near, Position { x: 5, y: 6 })];
+        assert_eq!(fruit_in_reach(head, food.iter().copied(), 0, Thickness(1)), None);
+        assert_eq!(fruit_in_reach(head, food.iter().copied(), 1, Thickness(1)), Some(near));
+        // a diagonal neighbour is two steps away
+        assert_eq!(fruit_in_reach(head, food[..1].iter().copied(), 1, Thickness(1)), None);
+        assert_eq!(fruit_in_reach(head, food[..1].iter().copied(), 2, Thickness(1)), Some(far));
+        let under_head = [(near, head)];
+        assert_eq!(fruit_in_reach(head, under_head.iter().copied(), 0, Thickness(1)), Some(near));
+    }
+
+    #[test]
+    fn wrapped_segments_slide_out_and_back_in() {
+        let grid = GridDimensions { width: 10, height: 10 };
+        let (from, to) = (Position { x: 9, y: 4 }, Position { x: 0, y: 4 });
+        assert_eq!(wrapped(from.neighbour(Direction::Right), grid), to);
+        assert_eq!(wrap_direction(from, to, grid), Some(Direction::Right));
+        // an ordinary step, or a jump across the board (a warp), isn't a wrap
+        assert_eq!(wrap_direction(Position { x: 8, y: 4 }, from, grid), None);
+        assert_eq!(wrap_direction(Position { x: 9, y: 9 }, to, grid), None);
+
+        let segment = Entity::new(0);
+        let mut slides = WrapSlides::default();
+        slides.update(&[(segment, from)], grid, Duration::ZERO);
+        let quarter = Duration::from_secs_f32(WRAP_SLIDE_SECONDS / 4.0);
+        slides.update(&[(segment, to)], grid, quarter);
+        // out over the right edge first, then in from the left
+        let drawn_at = |slides: &WrapSlides, x: f32| (slides.sliding[&segment].at(to) - Vec2::new(x, 4.0)).length() < 0.01;
+        assert!(drawn_at(&slides, 9.5));
+        slides.update(&[(segment, to)], grid, quarter * 2);
+        assert!(drawn_at(&slides, -0.5));
+        slides.update(&[(segment, to)], grid, quarter * 2);
+        assert!(slides.sliding.is_empty());
+        assert_eq!(slides.landed, vec![segment]);
+    }
+
+    #[test]
+    fn bends_get_a_filler_on_the_inside() {
+        let at = |x, y| Position { x, y };
+        // up, right, then a warp that doesn't count as a bend
+        let body = [at(3, 5), at(3, 4), at(4, 4), at(5, 4), at(9, 9), at(9, 8)];
+        assert_eq!(inner_corners(&body, Thickness(1)), vec![Vec2::new(3.5, 4.5)]);
+        assert!(inner_corners(&body[..2], Thickness(1)).is_empty());
+
+        let thick = [at(2, 6), at(2, 4), at(4, 4)];
+        assert_eq!(inner_corners(&thick, Thickness(2)), vec![Vec2::new(3.0, 5.0)]);
+    }
+
+    #[test]
+    fn hitboxes_outline_the_inside_of_the_tile() {
+        let tile = Vec2::new(40.0, 20.0);
+        let line = 20.0 * HITBOX_LINE_WIDTH;
+        for (offset, size) in hitbox_edges(tile) {
+            let (low, high) = (offset - size / 2.0, offset + size / 2.0);
+            assert!(low.cmpge(-tile / 2.0 - 0.001).all() && high.cmple(tile / 2.0 + 0.001).all());
+            // each one runs along a whole side, flush with it
+            assert!((size.x - tile.x).abs() < 0.001 || (size.y - tile.y).abs() < 0.001);
+            assert!((size.x - line).abs() < 0.001 || (size.y - line).abs() < 0.001);
+            assert!((high.x - tile.x / 2.0).abs() < 0.001
+                || (low.x + tile.x / 2.0).abs() < 0.001
+                || (high.y - tile.y / 2.0).abs() < 0.001
+                || (low.y + tile.y / 2.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn rooms_are_walled_off_except_for_the_doors() {
+        let grid = GridDimensions::default();
+        let walls = room_layout(grid);
+        let (middle_x, middle_y) = (grid.width as i32 / 2, grid.height as i32 / 2);
+        // two doors in each of the two walls
+        let gaps = (0..grid.height as i32).filter(|y| !walls.contains(&Position { x: middle_x, y: *y })).count();
+        assert_eq!(gaps, 2 * DOOR_WIDTH as usize);
+        assert!(!walls.contains(&Position { x: middle_x, y: grid.height as i32 / 4 }));
+        assert!(!walls.contains(&Position { x: grid.width as i32 / 4, y: middle_y }));
+        assert!(walls.contains(&Position { x: middle_x, y: middle_y }));
+        assert!(room_layout(GridDimensions { width: 5, height: 5 }).is_empty());
+        assert_eq!(room_view(Vec3::new(-10.0, 30.0, 0.0), 800.0, 600.0), Vec2::new(-200.0, 150.0));
+
+        let mut app = test_app(Args {
+            start_length: Some(1),
+            rooms: true,
+            ..Default::default()
+        });
+        app.update();
+        let start = start_position(false, 1, grid);
+        // straight up into the wall between the two rooms on the left, which starts the snek over
+        for _ in start.y..middle_y {
+            step(&mut app);
+        }
+        let head = app.world.get_resource::<SnekSegments>().unwrap().0[0];
+        assert_eq!(*app.world.get::<Position>(head).unwrap(), start);
+    }
+
+    #[test]
+    fn a_key_opens_the_way_into_the_next_room() {
+        let mut app = test_app(Args {
+            start_length: Some(1),
+            rooms: true,
+            keys: true,
+            ..Default::default()
+        });
+        app.update();
+        let grid = GridDimensions::default();
+        let head_at = |app: &App| {
+            let head = app.world.get_resource::<SnekSegments>().unwrap().0[0];
+            *app.world.get::<Position>(head).unwrap()
+        };
+        let steer = |app: &mut App, direction: Direction, ticks: i32| {
+            for mut head in app.world.query::<&mut SnekHead>().iter_mut(&mut app.world) {
+                head.next_direction = direction;
+            }
+            for _ in 0..ticks {
+                step(app);
+            }
+        };
+        // the first key is in the middle of the first room, and its door straight above it
+        let (key_x, key_y) = (grid.width as i32 / 4, grid.height as i32 / 4);
+        let start = head_at(&app);
+        steer(&mut app, Direction::Up, key_y - start.y);
+        steer(&mut app, Direction::Right, key_x - start.x);
+        assert_eq!(head_at(&app), Position { x: key_x, y: key_y });
+        assert_eq!(app.world.query::<&keys::Key>().iter(&app.world).count(), 2);
+
+        let past_the_wall = grid.height as i32 / 2 + 2;
+        steer(&mut app, Direction::Up, past_the_wall - key_y);
+        assert_eq!(head_at(&app), Position { x: key_x, y: past_the_wall });
+    }
+
+    #[test]
+    fn required_input_holds_the_snek_still() {
+        let mut app = test_app(Args {
+            start_length: Some(1),
+            require_input: true,
+            ..Default::default()
+        });
+        app.update();
+        let start = start_position(false, 1, GridDimensions::default());
+        let head = |app: &mut App| {
+            let head = app.world.get_resource::<SnekSegments>().unwrap().0[0];
+            *app.world.get::<Position>(head).unwrap()
+        };
+
+        step(&mut app);
+        assert_eq!(head(&mut app), start);
+        app.world.get_resource_mut::<SteerKeyHeld>().unwrap().0 = true;
+        step(&mut app);
+        assert_eq!(head(&mut app), Position { x: start.x, y: start.y + 1 });
+    }
+
+    #[test]
+    fn the_game_ends_when_the_time_is_up() {
+        let mut app = test_app(Args {
+            time_limit: Some(1.0),
+            ..Default::default()
+        });
+        app.update();
+        let mut time_ups = bevy::app::ManualEventReader::<TimeUpEvent>::default();
+        let pass = |app: &mut App, seconds: f32| {
+            app.world.get_resource_mut::<FrameDelta>().unwrap().0 = Duration::from_secs_f32(seconds);
+            app.update();
+        };
+
+        pass(&mut app, 0.6);
+        app.world.get_resource_mut::<Score>().unwrap().0 = 50;
+        // lives left don't matter, the game is over anyway
+        app.world.get_resource_mut::<Lives>().unwrap().0 = 2;
+        pass(&mut app, 0.6);
+        let events = app.world.get_resource::<Events<TimeUpEvent>>().unwrap();
+        assert_eq!(time_ups.iter(events).map(|time_up| time_up.score).collect::<Vec<u32>>(), vec![50]);
+        assert_eq!(app.world.get_resource::<Score>().unwrap().0, 0);
+        assert_eq!(app.world.get_resource::<Lives>().unwrap().0, START_LIVES);
+        assert_eq!(app.world.get_resource::<Stopwatch>().unwrap().0, Duration::ZERO);
+
+        // a paused game doesn't use up the time
+        pass(&mut app, 0.0);
+        assert_eq!(app.world.get_resource::<Stopwatch>().unwrap().0, Duration::ZERO);
+        assert_eq!(time_left_caption(Duration::from_secs_f32(61.5)), "Time 1:02");
+        assert_eq!(time_left_caption(Duration::ZERO), "Time 0:00");
+    }
+
+    #[test]
+    fn thick_sneks_collide_with_their_whole_block() {
+        let thickness = Thickness(2);
+        let grid = GridDimensions { width: 10, height: 10 };
+        let head = Position { x: 4, y: 6 };
+        // a whole block per step, and the block has to stay on the board
+        assert_eq!(thickness.step(head, Direction::Up), Position { x: 4, y: 8 });
+        assert!(thickness.fits(Position { x: 4, y: 8 }, grid));
+        assert!(!thickness.fits(Position { x: 9, y: 0 }, grid));
+        assert_eq!(wall_bounce(Position { x: 4, y: 8 }, Direction::Up, grid, thickness), Some(Direction::Down));
+
+        // an obstacle under only the right half of the block still hits it
+        let obstacle = Obstacle { path: vec![Position { x: 5, y: 8 }, Position { x: 6, y: 8 }], index: 0 };
+        assert!(obstacle.hits(head, Position { x: 4, y: 8 }, thickness));
+        assert!(!obstacle.hits(head, Position { x: 4, y: 8 }, Thickness(1)));
+
+        // fruit is eaten as soon as the blocks overlap
+        assert_eq!(thickness.gap(head, Position { x: 3, y: 5 }), 0);
+        assert_eq!(thickness.gap(head, Position { x: 6, y: 6 }), 1);
+        // and can't spawn where it would overlap the snek or hang off the board
+        let blocked = thickness.blocked_anchors(thickness.covered([head].iter()), grid);
+        assert!(blocked.contains(&Position { x: 3, y: 5 }));
+        assert!(!blocked.contains(&Position { x: 2, y: 5 }));
+        assert!(blocked.contains(&Position { x: 9, y: 3 }));
+
+        let mut app = test_app(Args {
+            start_length: Some(3),
+            thickness: Some(2),
+            ..Default::default()
+        });
+        app.update();
+        // the body trails down a block at a time
+        let segments = app.world.get_resource::<SnekSegments>().unwrap().0.clone();
+        let ys = segments.iter().map(|e| app.world.get::<Position>(*e).unwrap().y).collect::<Vec<i32>>();
+        assert_eq!(ys, vec![ys[2] + 4, ys[2] + 2, ys[2]]);
+    }
+
+    #[test]
+    fn losing_focus_pauses_the_game() {
+        let focus_app = |focus_pause: bool| {
+            let mut builder = App::build();
+            builder
+                .add_plugins(MinimalPlugins)
+                .add_event::<WindowFocused>()
+                .insert_resource(FocusPause(focus_pause))
+                .insert_resource(Paused(false))
+                .add_system(pause_on_focus_loss.system());
+            builder.app
+        };
+        let focus = |app: &mut App, focused: bool| {
+            app.world.get_resource_mut::<Events<WindowFocused>>().unwrap()
+                .send(WindowFocused { id: WindowId::primary(), focused });
+            app.update();
+            app.world.get_resource::<Paused>().unwrap().0
+        };
+        let mut app = focus_app(true);
+        assert!(!focus(&mut app, true));
+        assert!(focus(&mut app, false));
+        // coming back doesn't pick up right away
+        assert!(focus(&mut app, true));
+
+        let mut app = focus_app(false);
+        assert!(!focus(&mut app, false));
+    }
+
+    #[test]
+    fn snapped_tiles_meet_without_gaps_at_any_scale() {
+        for scale_factor in [1.0, 1.25, 1.5, 2.0] {
+            // an odd window size doesn't split evenly into tiles, not even in physical pixels
+            let (window, tiles) = (803.0, GridDimensions::default().width as f32);
+            for tile in 0..tiles as i32 {
+                let line = |line: f32| tile_line(line, window, tiles, Some(scale_factor));
+                let (low, high) = (line(tile as f32), line(tile as f32 + 1.0));
+                // on a physical pixel, and a full size sprite covers the tile
+                assert!(((low * scale_factor).round() - low * scale_factor).abs() < 0.001);
+                assert!(pixel_size(window / tiles, Some(scale_factor)) >= high - low);
+                // the middle of the tile is less than a physical pixel off
+                let centre = convert(tile as f32, window, tiles);
+                assert!(((low + high) / 2.0 - centre).abs() * scale_factor <= 1.0);
+            }
+            assert!((tile_line(3.5, window, tiles, None) - convert(3.0, window, tiles)).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn batched_sprites_only_move_when_their_tile_does() {
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .add_event::<WindowResized>()
+            .insert_resource(GridDimensions::default())
+            .insert_resource(BorderWalls(false))
+            .insert_resource(YAxisDown(false))
+            .insert_resource(StretchFactor(0.0))
+            .insert_resource(SnekSegments::default())
+            .insert_resource(BatchSprites(true))
+            .insert_resource(PixelSnap(true))
+            .add_system(position_translation.system());
+        let mut windows = Windows::default();
+        windows.add(Window::new(
+            WindowId::primary(),
+            &WindowDescriptor::default(),
+            800,
+            800,
+            1.0,
+            None,
+        ));
+        builder.insert_resource(windows);
+        let mut app = builder.app;
+        let still = app.world.spawn().insert(Position { x: 1, y: 1 }).insert(Transform::default()).id();
+        let moving = app.world.spawn().insert(Position { x: 2, y: 2 }).insert(Transform::default()).id();
+        app.update();
+        let placed = app.world.get::<Transform>(still).unwrap().translation;
+        assert_ne!(placed, Vec3::ZERO);
+
+        // a sprite put somewhere else stays there until its tile changes
+        let elsewhere = Vec3::new(-1000.0, -1000.0, 0.0);
+        for entity in [still, moving] {
+            app.world.get_mut::<Transform>(entity).unwrap().translation = elsewhere;
+        }
+        app.world.get_mut::<Position>(moving).unwrap().x = 3;
+        app.update();
+        assert_eq!(app.world.get::<Transform>(still).unwrap().translation, elsewhere);
+        assert_ne!(app.world.get::<Transform>(moving).unwrap().translation, elsewhere);
+
+        // without batching every sprite is put back on its tile
+        app.world.get_resource_mut::<BatchSprites>().unwrap().0 = false;
+        app.update();
+        assert_eq!(app.world.get::<Transform>(still).unwrap().translation, placed);
+    }
+
+    #[test]
+    fn vision_is_a_circle_around_the_head() {
+        let head = Position { x: 5, y: 5 };
+        assert!(in_sight(head, head, 0));
+        assert!(!in_sight(Position { x: 6, y: 5 }, head, 0));
+        assert!(in_sight(Position { x: 5, y: 8 }, head, 3));
+        assert!(in_sight(Position { x: 7, y: 7 }, head, 3));
+        // the corners of the square around it are too far
+        assert!(!in_sight(Position { x: 8, y: 8 }, head, 3));
+        assert!(!in_sight(Position { x: 1, y: 5 }, head, 3));
+    }
+
+    #[test]
+    fn the_border_warning_is_one_ring_of_tiles() {
+        let grid = GridDimensions { width: 20, height: 25 };
+        let ring = edge_tiles(grid).collect::<HashSet<Position>>();
+        assert_eq!(ring.len(), 2 * 20 + 2 * 25 - 4);
+        assert!(ring.contains(&Position { x: 19, y: 24 }));
+        assert!(ring.contains(&Position { x: 0, y: 12 }));
+        assert!(!ring.contains(&Position { x: 1, y: 1 }));
+    }
+
+    #[test]
+    fn checkerboard_neighbours_differ() {
+        let pos = Position { x: 3, y: 7 };
+        for direction in [Direction::Left, Direction::Right, Direction::Up, Direction::Down].iter() {
+            assert_ne!(checker_shade(pos), checker_shade(pos.neighbour(*direction)));
+        }
+        assert_eq!(checker_shade(Position { x: 0, y: 0 }), checker_shade(Position { x: 1, y: 1 }));
+    }
+
+    #[test]
+    fn turns_beyond_the_cap_are_dropped() {
+        let mut cooldown = TurnCooldown::default();
+        let frame = Duration::from_secs_f32(0.1);
+        // a turn every frame for a second, with at most four a second allowed
+        let taken = (0..10)
+            .filter(|_| {
+                let allowed = cooldown.allow(4.0);
+                cooldown.tick(frame);
+                allowed
+            })
+            .count();
+        assert_eq!(taken, 4);
+    }
+
+    #[test]
+    fn relative_turns_go_round() {
+        let mut direction = Direction::Up;
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            direction = relative_turn(direction, true);
+            seen.push(direction);
+        }
+        assert_eq!(seen, vec![Direction::Right, Direction::Down, Direction::Left, Direction::Up]);
+        assert_eq!(relative_turn(Direction::Up, false), Direction::Left);
+        assert_eq!(relative_turn(Direction::Left, false), Direction::Down);
+        // never straight back
+        for direction in [Direction::Left, Direction::Right, Direction::Up, Direction::Down].iter() {
+            for clockwise in [true, false].iter() {
+                let turned = relative_turn(*direction, *clockwise);
+                assert!(resolve_turn(*direction, turned).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn turns_are_accepted() {
+        assert_eq!(resolve_turn(Direction::Up, Direction::Left), Some(Direction::Left));
+        assert_eq!(resolve_turn(Direction::Up, Direction::Right), Some(Direction::Right));
+        assert_eq!(resolve_turn(Direction::Left, Direction::Up), Some(Direction::Up));
+        assert_eq!(resolve_turn(Direction::Left, Direction::Down), Some(Direction::Down));
+    }
+
+    #[test]
+    fn reversing_is_rejected() {
+        for direction in [Direction::Left, Direction::Right, Direction::Up, Direction::Down].iter() {
+            assert_eq!(resolve_turn(*direction, direction.opposite()), None);
+        }
+    }
+
+    #[test]
+    fn same_direction_is_not_a_turn() {
+        for direction in [Direction::Left, Direction::Right, Direction::Up, Direction::Down].iter() {
+            assert_eq!(resolve_turn(*direction, *direction), None);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::{Direction, SnekHead, TickCount};
+
+// A recording is a plain text file: the seed on the first line, then one accepted turn per line.
+//
+//     seed 1234567
+//     12 left
+//     17 up
+//
+// Replaying it needs the same command line options as the recorded game.
+pub struct Recorder(File);
+
+pub struct Replay(VecDeque<(u64, Direction)>);
+
+impl Recorder {
+    pub fn create(path: &Path, seed: u64) -> io::Result<Recorder> {
+        let mut file = File::create(path)?;
+        writeln!(file, "seed {}", seed)?;
+        Ok(Recorder(file))
+    }
+}
+
+// returns the seed and the turns
+pub fn load(path: &Path) -> io::Result<(u64, Replay)> {
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid line: {}", line));
+    let mut lines = BufReader::new(File::open(path)?).lines();
+
+    let first = lines.next().unwrap_or_else(|| Ok(String::new()))?;
+    let seed = first.strip_prefix("seed ")
+        .and_then(|seed| seed.parse().ok())
+        .ok_or_else(|| invalid(&first))?;
+
+    let mut turns = VecDeque::new();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let turn = line.split_once(' ')
+            .and_then(|(tick, direction)| Some((tick.parse().ok()?, parse_direction(direction)?)))
+            .ok_or_else(|| invalid(&line))?;
+        turns.push_back(turn);
+    }
+    Ok((seed, Replay(turns)))
+}
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    match s {
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        _ => None,
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Left => "left",
+        Direction::Right => "right",
+        Direction::Up => "up",
+        Direction::Down => "down",
+    }
+}
+
+// runs right before the snek moves, so a turn that is still pending is the one this tick takes
+pub fn record_turns(recorder: Option<ResMut<Recorder>>, tick: Res<TickCount>, heads: Query<&SnekHead>) {
+    if let Some(mut recorder) = recorder {
+        for head in heads.iter() {
+            if head.next_direction != head.direction {
+                let line = format!("{} {}", tick.0, direction_name(head.next_direction));
+                if let Err(e) = writeln!(recorder.0, "{}", line) {
+                    eprintln!("snek: could not record turn: {}", e);
+                }
+            }
+        }
+    }
+}
+
+pub fn replay_turns(replay: Option<ResMut<Replay>>, tick: Res<TickCount>, mut heads: Query<&mut SnekHead>) {
+    if let Some(mut replay) = replay {
+        while let Some((at, direction)) = replay.0.front().copied() {
+            if at > tick.0 {
+                break;
+            }
+            replay.0.pop_front();
+            for mut head in heads.iter_mut() {
+                head.next_direction = direction;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_loads_back() {
+        let path = std::env::temp_dir().join(format!("snek-replay-{}.txt", std::process::id()));
+        {
+            let mut recorder = Recorder::create(&path, 42).unwrap();
+            writeln!(recorder.0, "3 left").unwrap();
+            writeln!(recorder.0, "10 {}", direction_name(Direction::Down)).unwrap();
+        }
+        let (seed, replay) = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(seed, 42);
+        assert_eq!(replay.0.into_iter().collect::<Vec<_>>(), vec![(3, Direction::Left), (10, Direction::Down)]);
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{FoodEatenEvent, FrameDelta, GameOverEvent, Lives, Score, SnekSegments};
+
+const STATS_FILE: &str = "snek-stats.json";
+
+#[derive(Serialize, Deserialize, Default)]
+struct Totals {
+    games_played: u32,
+    fruit_eaten: u32,
+    longest_snek: usize,
+    // game time, so pauses and fades don't count
+    play_seconds: f64,
+    // only finished games, so the average isn't pulled down by the one still running
+    total_score: u64,
+}
+
+// Lifetime totals over every game played, kept in snek-stats.json in the working directory.
+// Fruit, length and play time are counted as the game goes, but only saved when a game ends.
+pub struct Stats {
+    path: PathBuf,
+    totals: Totals,
+}
+
+impl Stats {
+    pub fn load() -> io::Result<Stats> {
+        let path = PathBuf::from(STATS_FILE);
+        let totals = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Totals::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(Stats { path, totals })
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.totals).map_err(io::Error::other)?;
+        fs::write(&self.path, json + "\n")
+    }
+
+    fn average_score(&self) -> Option<u64> {
+        match self.totals.games_played {
+            0 => None,
+            games => Some(self.totals.total_score / games as u64),
+        }
+    }
+
+    // the stats screen, one line per total
+    pub fn summary(&self) -> String {
+        let seconds = self.totals.play_seconds as u64;
+        let average = self.average_score().map_or_else(|| "-".to_string(), |average| average.to_string());
+        format!(
+            "Lifetime stats\nGames played: {}\nFruit eaten: {}\nLongest snek: {}\nTime played: {}h {:02}m {:02}s\nAverage score: {}",
+            self.totals.games_played,
+            self.totals.fruit_eaten,
+            self.totals.longest_snek,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            average,
+        )
+    }
+}
+
+pub fn track_play(
+    stats: Option<ResMut<Stats>>,
+    delta: Res<FrameDelta>,
+    mut eaten_reader: EventReader<FoodEatenEvent>,
+    segments: Res<SnekSegments>,
+) {
+    if let Some(mut stats) = stats {
+        let eaten = eaten_reader.iter().count() as u32;
+        let length = segments.0.len();
+        // left alone while paused, so the stats screen isn't rebuilt every frame
+        if eaten > 0 || length > stats.totals.longest_snek || delta.0 > std::time::Duration::ZERO {
+            stats.totals.fruit_eaten += eaten;
+            stats.totals.longest_snek = stats.totals.longest_snek.max(length);
+            stats.totals.play_seconds += delta.0.as_secs_f64();
+        }
+    }
+}
+
+// runs before game_over, which resets the score once the last life is gone
+pub fn record_game(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    lives: Res<Lives>,
+    score: Res<Score>,
+    stats: Option<ResMut<Stats>>,
+) {
+    if game_over_reader.iter().next().is_none() || lives.0 > 1 {
+        return;
+    }
+    if let Some(mut stats) = stats {
+        stats.totals.games_played += 1;
+        stats.totals.total_score += score.0 as u64;
+        if let Err(e) = stats.save() {
+            eprintln!("snek: could not save {}: {}", stats.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_shows_the_totals() {
+        let mut stats = Stats { path: PathBuf::new(), totals: Totals::default() };
+        assert!(stats.summary().ends_with("Average score: -"));
+
+        stats.totals = Totals {
+            games_played: 4,
+            fruit_eaten: 30,
+            longest_snek: 12,
+            play_seconds: 3725.5,
+            total_score: 310,
+        };
+        assert_eq!(
+            stats.summary(),
+            "Lifetime stats\nGames played: 4\nFruit eaten: 30\nLongest snek: 12\nTime played: 1h 02m 05s\nAverage score: 77",
+        );
+    }
+}
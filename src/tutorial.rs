@@ -0,0 +1,140 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{Direction, FoodEatenEvent, FrameDelta, SnekHead, TutorialText};
+
+// only there once the tutorial was finished or skipped
+const DONE_FILE: &str = "snek-tutorial-done";
+// the last hint has nothing to wait for, it just stays up this long (in game time)
+const LAST_HINT_SECONDS: f32 = 5.0;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum Step {
+    Steer,
+    Eat,
+    Avoid,
+    Done,
+}
+
+// The hints shown on the first launch, one at a time. Each one stays up until the player did what
+// it says. Finishing or skipping it (X) leaves snek-tutorial-done behind, so it doesn't come back
+// unless asked for with --tutorial.
+pub struct Tutorial {
+    path: PathBuf,
+    step: Step,
+    last_hint: Timer,
+}
+
+impl Tutorial {
+    // None if the tutorial was done before, unless it is wanted again anyway
+    pub fn load(again: bool) -> Option<Tutorial> {
+        let path = PathBuf::from(DONE_FILE);
+        if !again && path.exists() {
+            return None;
+        }
+        Some(Tutorial::new(path))
+    }
+
+    fn new(path: PathBuf) -> Tutorial {
+        Tutorial {
+            path,
+            step: Step::Steer,
+            last_hint: Timer::from_seconds(LAST_HINT_SECONDS, false),
+        }
+    }
+
+    fn hint(&self) -> &'static str {
+        match self.step {
+            Step::Steer => "Use WASD to steer",
+            Step::Eat => "Eat the green fruit to grow",
+            Step::Avoid => "Avoid walls and yourself",
+            Step::Done => "",
+        }
+    }
+
+    // moves on to the next hint once the current one was followed. true if this finished the tutorial.
+    fn advance(&mut self, turned: bool, ate: bool, delta: Duration) -> bool {
+        let next = match self.step {
+            Step::Steer if turned => Step::Eat,
+            Step::Eat if ate => Step::Avoid,
+            Step::Avoid if self.last_hint.tick(delta).finished() => Step::Done,
+            step => step,
+        };
+        let finished = next == Step::Done && self.step != Step::Done;
+        self.step = next;
+        finished
+    }
+
+    fn finish(&mut self) {
+        self.step = Step::Done;
+        if let Err(e) = mark_done(&self.path) {
+            eprintln!("snek: could not save {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn mark_done(path: &Path) -> io::Result<()> {
+    fs::write(path, "")
+}
+
+pub fn progress(
+    tutorial: Option<ResMut<Tutorial>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    delta: Res<FrameDelta>,
+    mut eaten_reader: EventReader<FoodEatenEvent>,
+    heads: Query<&SnekHead>,
+) {
+    let mut tutorial = match tutorial {
+        Some(tutorial) if tutorial.step != Step::Done => tutorial,
+        _ => return,
+    };
+    if keyboard_input.just_pressed(KeyCode::X) {
+        tutorial.finish();
+        return;
+    }
+    // every snek starts out heading up
+    let turned = heads.iter().any(|head| head.next_direction != Direction::Up);
+    let ate = eaten_reader.iter().next().is_some();
+    if tutorial.advance(turned, ate, delta.0) {
+        tutorial.finish();
+    }
+}
+
+pub fn hint_text(tutorial: Option<Res<Tutorial>>, mut texts: Query<&mut Text, With<TutorialText>>) {
+    if let Some(tutorial) = tutorial.filter(|tutorial| tutorial.is_changed()) {
+        let hint = match tutorial.step {
+            Step::Done => String::new(),
+            _ => format!("{}\n(X to skip the tutorial)", tutorial.hint()),
+        };
+        for mut text in texts.iter_mut() {
+            // progress touches the tutorial every frame, the hint only changes now and then
+            if text.sections[0].value != hint {
+                text.sections[0].value = hint.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_follow_the_player() {
+        let mut tutorial = Tutorial::new(PathBuf::new());
+        let frame = Duration::from_secs_f32(0.1);
+        // eating before turning doesn't skip ahead
+        assert!(!tutorial.advance(false, true, frame));
+        assert_eq!(tutorial.step, Step::Steer);
+        assert!(!tutorial.advance(true, false, frame));
+        assert_eq!(tutorial.hint(), "Eat the green fruit to grow");
+        assert!(!tutorial.advance(true, true, frame));
+        assert_eq!(tutorial.step, Step::Avoid);
+        assert!(tutorial.advance(true, false, Duration::from_secs_f32(LAST_HINT_SECONDS)));
+        assert!(!tutorial.advance(true, true, frame));
+    }
+}